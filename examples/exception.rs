@@ -84,6 +84,12 @@ fn other_exception(
     (&TX).lock(|tx| {
         let tx = tx.as_mut().unwrap();
         writeln!(tx, "Exception {:?}, {:08x?}", cause, frame).unwrap();
+        writeln!(
+            tx,
+            "{}",
+            esp32_hal::backtrace::backtrace_from_context(&frame)
+        )
+        .unwrap();
     });
     loop {}
 }
@@ -216,6 +216,10 @@ fn main() -> ! {
             sdo: data_out,
             sdi: Some(pins.gpio25),
             cs: None,
+            wp: None,
+            hd: None,
+            cs1: None,
+            cs2: None,
         },
         spi::config::Config {
             baudrate: 3.MHz().into(),
@@ -0,0 +1,109 @@
+#![no_std]
+#![no_main]
+
+use core::{fmt::Write, panic::PanicInfo};
+
+use esp32_hal::{
+    clock_control::{sleep, ClockControl, XTAL_FREQUENCY_AUTO},
+    dport::Split,
+    dprintln,
+    interrupt::{Interrupt, InterruptLevel},
+    prelude::*,
+    serial::{config::Config, Event, Pins, Rx, Serial, Tx},
+    target,
+    timer::Timer,
+    Core::PRO,
+};
+
+static RX: CriticalSectionSpinLockMutex<Option<Rx<esp32::UART0>>> =
+    CriticalSectionSpinLockMutex::new(None);
+static TX: CriticalSectionSpinLockMutex<Option<Tx<esp32::UART0>>> =
+    CriticalSectionSpinLockMutex::new(None);
+
+#[entry]
+fn main() -> ! {
+    let dp = target::Peripherals::take().expect("Failed to obtain Peripherals");
+
+    let (_, dport_clock_control) = dp.DPORT.split();
+
+    let clkcntrl = ClockControl::new(
+        dp.RTCCNTL,
+        dp.APB_CTRL,
+        dport_clock_control,
+        XTAL_FREQUENCY_AUTO,
+    )
+    .unwrap();
+
+    let (clkcntrl_config, mut watchdog) = clkcntrl.freeze().unwrap();
+    watchdog.disable();
+
+    let (_, _, _, mut watchdog0) = Timer::new(dp.TIMG0, clkcntrl_config);
+    let (_, _, _, mut watchdog1) = Timer::new(dp.TIMG1, clkcntrl_config);
+    watchdog0.disable();
+    watchdog1.disable();
+
+    let pins = dp.GPIO.split();
+
+    let serial: Serial<_, _, _> = Serial::new(
+        dp.UART0,
+        Pins {
+            tx: pins.gpio1,
+            rx: pins.gpio3,
+            cts: None,
+            rts: None,
+        },
+        Config::default()
+            .baudrate(115200.Hz())
+            .rx_fifo_full_threshold(16)
+            .rx_timeout(Some(4)),
+        clkcntrl_config,
+    )
+    .unwrap();
+
+    let (mut tx, mut rx) = serial.split();
+
+    writeln!(tx, "\n\nESP32 Started\n\n").unwrap();
+
+    // fire when 16 bytes are received or the line is idle for 4 symbols after receiving data
+    rx.listen(Event::Rxne);
+    rx.listen(Event::Idle);
+    rx.listen(Event::Overflow);
+
+    (&RX).lock(|data| *data = Some(rx));
+    (&TX).lock(|data| *data = Some(tx));
+
+    interrupt::enable_with_priority(PRO, Interrupt::UART0_INTR, InterruptLevel(1)).unwrap();
+
+    loop {
+        sleep(1.s());
+    }
+}
+
+#[interrupt]
+fn UART0_INTR() {
+    (&RX).lock(|rx| {
+        let rx = rx.as_mut().unwrap();
+
+        (&TX).lock(|tx| {
+            let tx = tx.as_mut().unwrap();
+
+            if rx.is_interrupt_pending(Event::Overflow) {
+                writeln!(tx, "\nRX FIFO overflow").unwrap();
+                rx.clear_interrupt(Event::Overflow);
+            }
+
+            while let Ok(x) = rx.read() {
+                write!(tx, "{}", x as char).unwrap();
+            }
+        });
+
+        rx.clear_interrupt(Event::Rxne);
+        rx.clear_interrupt(Event::Idle);
+    });
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    dprintln!("\n\n*** {:?}", info);
+    loop {}
+}
@@ -137,6 +137,10 @@ fn main() -> ! {
             sdo: pins.gpio23,
             sdi: Some(pins.gpio25),
             cs: Some(pins.gpio22),
+            wp: None,
+            hd: None,
+            cs1: None,
+            cs2: None,
         },
         spi::config::Config {
             baudrate: 26.MHz().into(),
@@ -0,0 +1,46 @@
+//! Hardware independent modules of esp32-hal, compiled for the host
+//!
+//! The module hierarchy mirrors the one of esp32-hal, so `crate::` paths within the included
+//! modules resolve in the same way.
+
+#![no_std]
+
+#[path = "../../src/ring_buffer.rs"]
+pub mod ring_buffer;
+
+#[path = "../../src/console"]
+pub mod console {
+    pub mod editor;
+    mod error;
+    pub mod parser;
+
+    pub use error::Error;
+}
+
+#[path = "../../src/coredump"]
+pub mod coredump {
+    pub mod elf;
+}
+
+#[path = "../../src/crashlog"]
+pub mod crashlog {
+    // the helpers for the crash log in memory are not used on the host
+    #[allow(dead_code)]
+    pub mod buffer;
+}
+
+#[path = "../../src/serial"]
+pub mod serial {
+    /// Stand-in for the UART errors wrapped by the LIN errors
+    #[derive(PartialEq, Eq, Copy, Clone, Debug)]
+    pub enum Error {}
+
+    pub mod lin {
+        pub mod frame;
+    }
+}
+
+#[path = "../../src/spi"]
+pub mod spi {
+    pub mod transaction;
+}
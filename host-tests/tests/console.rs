@@ -0,0 +1,180 @@
+use esp32_hal_host_tests::console::editor::{Action, LineEditor, MAX_LINE_LENGTH};
+use esp32_hal_host_tests::console::parser::{
+    optional_u32_argument, parse_u32, split, u32_argument, MAX_ARGUMENTS,
+};
+use esp32_hal_host_tests::console::Error;
+
+fn input(editor: &mut LineEditor, data: &[u8]) -> Vec<Action> {
+    data.iter().map(|&byte| editor.input(byte)).collect()
+}
+
+#[test]
+fn editor_echoes_printable_characters() {
+    let mut editor = LineEditor::new();
+    assert_eq!(
+        input(&mut editor, b"ab c"),
+        [
+            Action::Echo(b'a'),
+            Action::Echo(b'b'),
+            Action::Echo(b' '),
+            Action::Echo(b'c')
+        ]
+    );
+    assert_eq!(editor.line(), "ab c");
+    assert_eq!(editor.input(b'\r'), Action::Line);
+    assert_eq!(editor.line(), "ab c");
+}
+
+#[test]
+fn editor_backspace_and_delete() {
+    let mut editor = LineEditor::new();
+    input(&mut editor, b"abc");
+    assert_eq!(editor.input(0x08), Action::Erase);
+    assert_eq!(editor.line(), "ab");
+    assert_eq!(editor.input(0x7f), Action::Erase);
+    assert_eq!(editor.line(), "a");
+    assert_eq!(editor.input(b'x'), Action::Echo(b'x'));
+    assert_eq!(editor.line(), "ax");
+
+    input(&mut editor, &[0x08, 0x08]);
+    assert!(editor.is_empty());
+    assert_eq!(editor.input(0x08), Action::Bell);
+}
+
+#[test]
+fn editor_erase_line_and_cancel() {
+    let mut editor = LineEditor::new();
+    input(&mut editor, b"hello");
+    assert_eq!(editor.input(0x15), Action::EraseLine(5));
+    assert!(editor.is_empty());
+
+    input(&mut editor, b"abc");
+    assert_eq!(editor.input(0x03), Action::Cancel);
+    assert!(editor.is_empty());
+}
+
+#[test]
+fn editor_line_endings() {
+    let mut editor = LineEditor::new();
+
+    // CR LF is a single line ending
+    assert_eq!(
+        input(&mut editor, b"a\r\nb\n"),
+        [
+            Action::Echo(b'a'),
+            Action::Line,
+            Action::None,
+            Action::Echo(b'b'),
+            Action::Line
+        ]
+    );
+    assert_eq!(editor.line(), "b");
+
+    // the next input starts a new line
+    assert_eq!(editor.input(b'c'), Action::Echo(b'c'));
+    assert_eq!(editor.line(), "c");
+
+    // an empty line
+    assert_eq!(input(&mut editor, b"\r\r"), [Action::Line, Action::Line]);
+    assert!(editor.is_empty());
+}
+
+#[test]
+fn editor_ignores_other_characters() {
+    let mut editor = LineEditor::new();
+    assert_eq!(
+        input(&mut editor, &[0x1b, 0x00, 0x80, 0xff]),
+        [Action::None; 4]
+    );
+    assert!(editor.is_empty());
+}
+
+#[test]
+fn editor_full_line() {
+    let mut editor = LineEditor::new();
+    for _ in 0..MAX_LINE_LENGTH {
+        assert_eq!(editor.input(b'x'), Action::Echo(b'x'));
+    }
+    assert_eq!(editor.input(b'y'), Action::Bell);
+    assert_eq!(editor.line().len(), MAX_LINE_LENGTH);
+
+    assert_eq!(editor.input(0x08), Action::Erase);
+    assert_eq!(editor.input(b'y'), Action::Echo(b'y'));
+    assert!(editor.line().ends_with('y'));
+}
+
+fn split_line(line: &str) -> Result<Vec<&str>, Error> {
+    let mut args = [""; MAX_ARGUMENTS];
+    let count = split(line, &mut args)?;
+    Ok(args[..count].to_vec())
+}
+
+#[test]
+fn split_arguments() {
+    assert_eq!(split_line(""), Ok(vec![]));
+    assert_eq!(split_line("   "), Ok(vec![]));
+    assert_eq!(split_line("peek"), Ok(vec!["peek"]));
+    assert_eq!(
+        split_line("  poke  0x3ffb0000 \t 42 "),
+        Ok(vec!["poke", "0x3ffb0000", "42"])
+    );
+}
+
+#[test]
+fn split_quoted_arguments() {
+    assert_eq!(
+        split_line(r#"echo "hello world" x"#),
+        Ok(vec!["echo", "hello world", "x"])
+    );
+    assert_eq!(split_line(r#"echo """#), Ok(vec!["echo", ""]));
+    assert_eq!(split_line(r#"echo "open"#), Err(Error::UnterminatedQuote));
+}
+
+#[test]
+fn split_too_many_arguments() {
+    assert_eq!(split_line("1 2 3 4 5 6 7 8").map(|args| args.len()), Ok(8));
+    assert_eq!(
+        split_line("1 2 3 4 5 6 7 8 9"),
+        Err(Error::TooManyArguments)
+    );
+}
+
+#[test]
+fn parse_numbers() {
+    assert_eq!(parse_u32("0"), Ok(0));
+    assert_eq!(parse_u32("1234"), Ok(1234));
+    assert_eq!(parse_u32("0x3FF4_8000"), Ok(0x3ff4_8000));
+    assert_eq!(parse_u32("0Xff"), Ok(0xff));
+    assert_eq!(parse_u32("0b1010"), Ok(10));
+    assert_eq!(parse_u32("1_000_000"), Ok(1_000_000));
+    assert_eq!(parse_u32("4294967295"), Ok(u32::MAX));
+    assert_eq!(parse_u32("0xffffffff"), Ok(u32::MAX));
+}
+
+#[test]
+fn parse_invalid_numbers() {
+    for arg in [
+        "",
+        "0x",
+        "_",
+        "12a",
+        "0b102",
+        "-1",
+        "4294967296",
+        "0x100000000",
+    ]
+    .iter()
+    {
+        assert_eq!(parse_u32(arg), Err(Error::InvalidNumber), "{:?}", arg);
+    }
+}
+
+#[test]
+fn numeric_arguments() {
+    let args = ["peek", "0x10", "x"];
+    assert_eq!(u32_argument(&args, 1), Ok(0x10));
+    assert_eq!(u32_argument(&args, 2), Err(Error::InvalidNumber));
+    assert_eq!(u32_argument(&args, 3), Err(Error::MissingArgument));
+    assert_eq!(optional_u32_argument(&args, 1), Ok(Some(0x10)));
+    assert_eq!(optional_u32_argument(&args, 3), Ok(None));
+}
@@ -0,0 +1,110 @@
+//! The reference data is generated by `fixtures/coredump_notes.py`, see there.
+
+use esp32_hal_host_tests::coredump::elf::{self, ProgramHeader, Registers, SliceOutput};
+
+const REFERENCE: &[u8] = include_bytes!("fixtures/coredump_notes.bin");
+
+#[test]
+fn core_file_matches_reference() {
+    let mut registers = Registers {
+        pc: 0x400d_1234,
+        ps: 0x0006_0720,
+        lbeg: 0x4000_c2e0,
+        lend: 0x4000_c2f6,
+        lcount: 0,
+        sar: 0x1d,
+        a: [0; 16],
+    };
+    registers.a[0] = 0x800d_5678;
+    registers.a[1] = 0x3ffb_1f00;
+    for index in 2..16 {
+        registers.a[index] = 0x1000 + index as u32;
+    }
+
+    let notes_size = 2 * elf::note_size(elf::PRSTATUS_NAME, elf::PRSTATUS_SIZE)
+        + elf::note_size(elf::INFO_NAME, elf::INFO_SIZE)
+        + elf::note_size(elf::EXTRA_INFO_NAME, elf::EXTRA_INFO_SIZE);
+    let notes_offset = (elf::ELF_HEADER_SIZE + 2 * elf::PROGRAM_HEADER_SIZE) as u32;
+
+    let mut buffer = [0u8; 2048];
+    let mut output = SliceOutput::new(&mut buffer);
+
+    elf::write_elf_header(&mut output, 2).unwrap();
+    elf::write_program_header(
+        &mut output,
+        &ProgramHeader {
+            segment_type: elf::PT_NOTE,
+            offset: notes_offset,
+            address: 0,
+            size: notes_size as u32,
+            flags: 0,
+        },
+    )
+    .unwrap();
+    elf::write_program_header(
+        &mut output,
+        &ProgramHeader {
+            segment_type: elf::PT_LOAD,
+            offset: notes_offset + notes_size as u32,
+            address: 0x3ffb_1ef0,
+            size: 0x110,
+            flags: elf::PF_R | elf::PF_W,
+        },
+    )
+    .unwrap();
+
+    elf::write_note(
+        &mut output,
+        elf::PRSTATUS_NAME,
+        elf::NT_PRSTATUS,
+        &elf::prstatus(&registers, 1),
+    )
+    .unwrap();
+    elf::write_note(
+        &mut output,
+        elf::PRSTATUS_NAME,
+        elf::NT_PRSTATUS,
+        &elf::prstatus(&Registers::default(), 2),
+    )
+    .unwrap();
+    elf::write_note(
+        &mut output,
+        elf::INFO_NAME,
+        elf::ESP_CORE_DUMP_INFO_TYPE,
+        &elf::info(0x0100, "0123456789abcdef".repeat(4).as_bytes()),
+    )
+    .unwrap();
+    elf::write_note(
+        &mut output,
+        elf::EXTRA_INFO_NAME,
+        elf::ESP_CORE_DUMP_EXTRA_INFO_TYPE,
+        &elf::extra_info(1, 29, 0x0000_0004, 0x400d_1234),
+    )
+    .unwrap();
+
+    assert_eq!(output.as_bytes().len(), REFERENCE.len());
+    assert_eq!(output.as_bytes(), REFERENCE);
+}
+
+#[test]
+fn note_size_includes_padding() {
+    assert_eq!(elf::note_size("CORE", 3), 12 + 8 + 4);
+    assert_eq!(elf::note_size("ABC", 4), 12 + 4 + 4);
+}
+
+#[test]
+fn app_sha256_keeps_the_terminator() {
+    let data = elf::info(1, &[b'f'; 100]);
+    assert_eq!(
+        data[4..4 + elf::APP_SHA256_SIZE - 1],
+        [b'f'; elf::APP_SHA256_SIZE - 1]
+    );
+    assert_eq!(data[elf::INFO_SIZE - 1], 0);
+}
+
+#[test]
+fn slice_output_reports_a_full_buffer() {
+    let mut buffer = [0u8; 40];
+    let mut output = SliceOutput::new(&mut buffer);
+    assert_eq!(elf::write_elf_header(&mut output, 1), Err(elf::BufferFull));
+}
@@ -0,0 +1,139 @@
+use core::fmt::Write;
+use core::mem::{size_of, MaybeUninit};
+
+use esp32_hal_host_tests::crashlog::buffer::{
+    crc32, crc32_update, CrashLog, LOG_SIZE, PANIC_MESSAGE_SIZE, RECORD_SIZE,
+};
+
+fn contents(log: &CrashLog) -> Vec<u8> {
+    let (first, second) = log.as_slices();
+    [first, second].concat()
+}
+
+#[test]
+fn crc32_check_values() {
+    assert_eq!(crc32(b""), 0);
+    assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+    assert_eq!(
+        crc32(b"The quick brown fox jumps over the lazy dog"),
+        0x414f_a339
+    );
+    assert_eq!(crc32_update(crc32(b"12345"), b"6789"), 0xcbf4_3926);
+}
+
+#[test]
+fn write_and_read() {
+    let mut log = CrashLog::new();
+    assert!(log.is_valid());
+    assert!(log.is_empty());
+
+    log.write(b"first\n");
+    writeln!(log, "second {}", 2).unwrap();
+    assert!(log.is_valid());
+    assert_eq!(contents(&log), b"first\nsecond 2\n");
+
+    let mut data = [0u8; 4];
+    assert_eq!(log.read(6, &mut data), 4);
+    assert_eq!(&data, b"seco");
+    assert_eq!(log.read(13, &mut data), 2);
+
+    log.clear();
+    assert!(log.is_valid());
+    assert!(log.is_empty());
+}
+
+#[test]
+fn wrap_around_removes_whole_lines() {
+    let mut log = CrashLog::new();
+
+    for index in 0..200 {
+        writeln!(log, "line {:03}", index).unwrap();
+    }
+    assert!(log.is_valid());
+
+    let data = contents(&log);
+    assert!(data.len() <= LOG_SIZE);
+    assert!(data.len() > LOG_SIZE - 9);
+    assert!(data.starts_with(b"line "));
+    assert!(data.ends_with(b"line 199\n"));
+    assert!(data
+        .chunks(9)
+        .all(|line| line.len() == 9 && line[8] == b'\n'));
+}
+
+#[test]
+fn oversized_records_are_truncated() {
+    let mut log = CrashLog::new();
+
+    log.write(&[b'x'; LOG_SIZE + 10]);
+    assert_eq!(log.len(), LOG_SIZE);
+    log.write(b"\n");
+    assert_eq!(contents(&log), b"\n");
+
+    log.clear();
+    writeln!(log, "{}", "y".repeat(RECORD_SIZE * 2)).unwrap();
+    assert_eq!(log.len(), RECORD_SIZE);
+    assert!(log.is_valid());
+}
+
+#[test]
+fn corruption_is_detected() {
+    let mut log = CrashLog::new();
+    log.write(b"some data\n");
+    log.set_panic_message(b"panic");
+    assert!(log.is_valid());
+
+    // the header, the start of the log data and the panic message, in the #[repr(C)] layout
+    let header = 4 * size_of::<u32>() + 2 * size_of::<usize>();
+    let size = size_of::<CrashLog>();
+    let offsets = (0..header)
+        .chain(header..header + 10)
+        .chain(size - PANIC_MESSAGE_SIZE..size);
+
+    for offset in offsets {
+        let mut corrupted = log.clone();
+        unsafe { *(&mut corrupted as *mut CrashLog as *mut u8).add(offset) ^= 0x10 };
+        assert!(!corrupted.is_valid(), "flipped bit at offset {}", offset);
+    }
+}
+
+#[test]
+fn garbage_is_not_valid() {
+    // a simple LCG, as memory which is not initialized at boot contains arbitrary values
+    let mut seed = 0x1234_5678u32;
+    for _ in 0..16 {
+        let mut memory = MaybeUninit::<CrashLog>::uninit();
+        let bytes = unsafe {
+            core::slice::from_raw_parts_mut(memory.as_mut_ptr() as *mut u8, size_of::<CrashLog>())
+        };
+        for byte in bytes.iter_mut() {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            *byte = (seed >> 16) as u8;
+        }
+
+        // every bit pattern is a valid CrashLog, as it only contains integers
+        let log = unsafe { memory.assume_init() };
+        assert!(!log.is_valid());
+    }
+
+    let zeroed: CrashLog = unsafe { MaybeUninit::zeroed().assume_init() };
+    assert!(!zeroed.is_valid());
+}
+
+#[test]
+fn panic_message_is_truncated_at_a_character_boundary() {
+    let mut log = CrashLog::new();
+    assert_eq!(log.panic_message(), None);
+
+    log.set_panic_message("message".as_bytes());
+    assert_eq!(log.panic_message(), Some("message"));
+
+    // the two byte character straddles the end of the buffer
+    let message = format!("{}é", "a".repeat(PANIC_MESSAGE_SIZE - 1));
+    log.set_panic_message(message.as_bytes());
+    assert!(log.is_valid());
+    assert_eq!(
+        log.panic_message(),
+        Some("a".repeat(PANIC_MESSAGE_SIZE - 1).as_str())
+    );
+}
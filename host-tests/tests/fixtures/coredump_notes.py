@@ -0,0 +1,145 @@
+#!/usr/bin/env python3
+"""Generate coredump_notes.bin, the reference data of tests/coredump.rs
+
+espcoredump.py needs an ESP-IDF installation and an application ELF file, so it cannot be used
+to create the reference offline. Instead the data is packed here with `struct`, following the
+ELF core file layout that espcoredump.py parses:
+- ELF header and program headers (Elf32_Ehdr, Elf32_Phdr)
+- NT_PRSTATUS notes: the Xtensa process status followed by the register set
+- ESP_CORE_DUMP_INFO note: version and application SHA256
+- EXTRA_INFO note: crashed thread and (register number, value) pairs
+
+Run from the host-tests directory:
+    python3 tests/fixtures/coredump_notes.py
+"""
+
+import os
+import struct
+
+ET_CORE = 4
+EM_XTENSA = 94
+PT_LOAD = 1
+PT_NOTE = 4
+PF_W = 2
+PF_R = 4
+
+NT_PRSTATUS = 1
+ESP_CORE_DUMP_INFO_TYPE = 8266
+ESP_CORE_DUMP_EXTRA_INFO_TYPE = 677
+
+EXCCAUSE_REGISTER = 232
+EXCVADDR_REGISTER = 238
+EPC1_REGISTER = 177
+
+APP_SHA256_SIZE = 66
+
+ELF_HEADER_SIZE = 52
+PROGRAM_HEADER_SIZE = 32
+
+
+def pad4(data):
+    return data + b"\0" * (-len(data) % 4)
+
+
+def elf_header(program_headers):
+    ident = bytes([0x7F]) + b"ELF" + bytes([1, 1, 1])
+    return struct.pack(
+        "<16sHHIIIIIHHHHHH",
+        ident,
+        ET_CORE,
+        EM_XTENSA,
+        1,
+        0,
+        ELF_HEADER_SIZE,
+        0,
+        0,
+        ELF_HEADER_SIZE,
+        PROGRAM_HEADER_SIZE,
+        program_headers,
+        0,
+        0,
+        0,
+    )
+
+
+def program_header(segment_type, offset, address, size, flags):
+    return struct.pack("<8I", segment_type, offset, address, address, size, size, flags, 4)
+
+
+def note(name, note_type, description):
+    name = name.encode() + b"\0"
+    header = struct.pack("<3I", len(name), len(description), note_type)
+    return header + pad4(name) + pad4(description)
+
+
+def prstatus(thread_id, pc, ps, lbeg, lend, lcount, sar, a):
+    # signal info, current signal with padding, pending and held signals
+    status = struct.pack("<3ih2xII", 0, 0, 0, 0, 0, 0)
+    # pid, ppid, pgrp, sid, then the user and system times
+    status += struct.pack("<4I", thread_id, 0, 0, 0) + bytes(32)
+    assert len(status) == 72
+
+    # window start 1 and window base 0: only the current window is live
+    registers = struct.pack("<8I", pc, ps, lbeg, lend, lcount, sar, 1, 0)
+    registers += bytes(56 * 4)
+    registers += struct.pack("<64I", *(a + [0] * (64 - len(a))))
+    return status + registers
+
+
+def info(version, app_sha256):
+    return struct.pack("<I%ds" % APP_SHA256_SIZE, version, app_sha256)
+
+
+def extra_info(crashed_thread_id, exccause, excvaddr, epc1):
+    return struct.pack(
+        "<7I",
+        crashed_thread_id,
+        EXCCAUSE_REGISTER,
+        exccause,
+        EXCVADDR_REGISTER,
+        excvaddr,
+        EPC1_REGISTER,
+        epc1,
+    )
+
+
+def main():
+    notes = note(
+        "CORE",
+        NT_PRSTATUS,
+        prstatus(
+            1,
+            0x400D1234,
+            0x00060720,
+            0x4000C2E0,
+            0x4000C2F6,
+            0,
+            0x1D,
+            [0x800D5678, 0x3FFB1F00] + [0x1000 + index for index in range(2, 16)],
+        ),
+    )
+    notes += note("CORE", NT_PRSTATUS, prstatus(2, 0, 0, 0, 0, 0, 0, []))
+    notes += note(
+        "ESP_CORE_DUMP_INFO",
+        ESP_CORE_DUMP_INFO_TYPE,
+        info(0x0100, b"0123456789abcdef" * 4),
+    )
+    notes += note(
+        "EXTRA_INFO", ESP_CORE_DUMP_EXTRA_INFO_TYPE, extra_info(1, 29, 0x00000004, 0x400D1234)
+    )
+
+    notes_offset = ELF_HEADER_SIZE + 2 * PROGRAM_HEADER_SIZE
+    data = elf_header(2)
+    data += program_header(PT_NOTE, notes_offset, 0, len(notes), 0)
+    data += program_header(
+        PT_LOAD, notes_offset + len(notes), 0x3FFB1EF0, 0x110, PF_R | PF_W
+    )
+    data += notes
+
+    path = os.path.join(os.path.dirname(os.path.abspath(__file__)), "coredump_notes.bin")
+    with open(path, "wb") as file:
+        file.write(data)
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,83 @@
+use esp32_hal_host_tests::serial::lin::frame::{
+    checksum, classic_checksum, enhanced_checksum, id_from_protected_id, protected_id,
+    ChecksumType, Error,
+};
+
+/// Identifiers and protected identifiers from the table in the LIN 2.x specification
+const PROTECTED_IDS: [(u8, u8); 16] = [
+    (0x00, 0x80),
+    (0x01, 0xc1),
+    (0x02, 0x42),
+    (0x03, 0x03),
+    (0x04, 0xc4),
+    (0x05, 0x85),
+    (0x06, 0x06),
+    (0x07, 0x47),
+    (0x08, 0x08),
+    (0x09, 0x49),
+    (0x10, 0x50),
+    (0x20, 0x20),
+    (0x3c, 0x3c),
+    (0x3d, 0x7d),
+    (0x3e, 0xfe),
+    (0x3f, 0xbf),
+];
+
+#[test]
+fn protected_ids() {
+    for &(id, pid) in PROTECTED_IDS.iter() {
+        assert_eq!(protected_id(id), Ok(pid), "id {:#04x}", id);
+        assert_eq!(id_from_protected_id(pid), Ok(id), "pid {:#04x}", pid);
+    }
+}
+
+#[test]
+fn invalid_id() {
+    assert_eq!(protected_id(0x40), Err(Error::InvalidId));
+    assert_eq!(protected_id(0xff), Err(Error::InvalidId));
+}
+
+#[test]
+fn parity_errors() {
+    for &(_, pid) in PROTECTED_IDS.iter() {
+        assert_eq!(id_from_protected_id(pid ^ 0x40), Err(Error::IdParity));
+        assert_eq!(id_from_protected_id(pid ^ 0x80), Err(Error::IdParity));
+    }
+}
+
+#[test]
+fn all_ids_round_trip() {
+    for id in 0..=0x3f {
+        let pid = protected_id(id).unwrap();
+        assert_eq!(pid & 0x3f, id);
+        assert_eq!(id_from_protected_id(pid), Ok(id));
+    }
+}
+
+#[test]
+fn classic_checksum_example() {
+    // example of the checksum calculation in the LIN 2.x specification
+    assert_eq!(classic_checksum(&[0x4a, 0x55, 0x93, 0xe5]), 0xe6);
+}
+
+#[test]
+fn enhanced_checksum_includes_pid() {
+    // 0x50 + 0x4a = 0x9a, + 0x55 = 0xef, + 0x93 = 0x183 -> 0x83, + 0xe5 = 0x168 -> 0x69
+    assert_eq!(enhanced_checksum(0x50, &[0x4a, 0x55, 0x93, 0xe5]), 0x96);
+    assert_eq!(
+        checksum(ChecksumType::Enhanced, 0x50, &[0x4a, 0x55, 0x93, 0xe5]),
+        0x96
+    );
+    assert_eq!(
+        checksum(ChecksumType::Classic, 0x50, &[0x4a, 0x55, 0x93, 0xe5]),
+        0xe6
+    );
+}
+
+#[test]
+fn checksum_carry() {
+    assert_eq!(classic_checksum(&[0xff]), 0x00);
+    assert_eq!(classic_checksum(&[0xff, 0x01]), 0xfe);
+    assert_eq!(classic_checksum(&[0xff; 8]), 0x00);
+    assert_eq!(classic_checksum(&[0x00; 8]), 0xff);
+}
@@ -0,0 +1,104 @@
+use esp32_hal_host_tests::ring_buffer::RingBuffer;
+
+fn contents<S: AsRef<[u8]> + AsMut<[u8]>>(buffer: &RingBuffer<S>) -> Vec<u8> {
+    let (first, second) = buffer.as_slices();
+    [first, second].concat()
+}
+
+#[test]
+fn push_and_pop() {
+    let mut buffer = RingBuffer::new([0u8; 4]);
+    assert!(buffer.is_empty());
+    assert_eq!(buffer.capacity(), 4);
+
+    for byte in 1..=4 {
+        assert!(buffer.push(byte));
+    }
+    assert!(buffer.is_full());
+    assert!(!buffer.push(5));
+    assert_eq!(contents(&buffer), [1, 2, 3, 4]);
+
+    assert_eq!(buffer.pop(), Some(1));
+    assert_eq!(buffer.pop(), Some(2));
+    assert!(buffer.push(5));
+    assert!(buffer.push(6));
+    assert_eq!(buffer.len(), 4);
+
+    for byte in 3..=6 {
+        assert_eq!(buffer.pop(), Some(byte));
+    }
+    assert_eq!(buffer.pop(), None);
+    assert!(buffer.is_empty());
+}
+
+#[test]
+fn push_overwrite_discards_oldest() {
+    let mut buffer = RingBuffer::new([0u8; 4]);
+    for byte in 1..=6 {
+        buffer.push_overwrite(byte);
+    }
+    assert!(buffer.is_full());
+    assert_eq!(contents(&buffer), [3, 4, 5, 6]);
+
+    let (first, second) = buffer.as_slices();
+    assert_eq!(first, [3, 4]);
+    assert_eq!(second, [5, 6]);
+}
+
+#[test]
+fn read_across_wrap() {
+    let mut buffer = RingBuffer::new([0u8; 5]);
+    for byte in 1..=8 {
+        buffer.push_overwrite(byte);
+    }
+
+    let mut data = [0u8; 3];
+    assert_eq!(buffer.read(&mut data), 3);
+    assert_eq!(data, [4, 5, 6]);
+    assert_eq!(buffer.len(), 2);
+
+    let mut data = [0u8; 8];
+    assert_eq!(buffer.read(&mut data), 2);
+    assert_eq!(data[..2], [7, 8]);
+    assert!(buffer.is_empty());
+    assert_eq!(buffer.read(&mut data), 0);
+}
+
+#[test]
+fn discard_and_clear() {
+    let mut buffer = RingBuffer::new([0u8; 4]);
+    for byte in 1..=3 {
+        buffer.push(byte);
+    }
+    buffer.discard(2);
+    assert_eq!(contents(&buffer), [3]);
+    buffer.discard(10);
+    assert!(buffer.is_empty());
+
+    buffer.push(1);
+    buffer.clear();
+    assert!(buffer.is_empty());
+    assert_eq!(buffer.pop(), None);
+}
+
+#[test]
+fn slice_storage() {
+    let mut storage = [0u8; 3];
+    let mut buffer = RingBuffer::new(&mut storage[..]);
+    buffer.push(1);
+    buffer.push(2);
+    assert_eq!(buffer.pop(), Some(1));
+    buffer.release()[0] = 9;
+    assert_eq!(storage, [9, 2, 0]);
+}
+
+#[test]
+fn zero_capacity() {
+    let mut buffer = RingBuffer::new([0u8; 0]);
+    assert!(buffer.is_full());
+    assert!(!buffer.push(1));
+    buffer.push_overwrite(1);
+    assert!(buffer.is_empty());
+    assert_eq!(buffer.pop(), None);
+    assert!(buffer.is_consistent());
+}
@@ -0,0 +1,55 @@
+use esp32_hal_host_tests::spi::transaction::{
+    address_register_value, command_register_value, Transaction, MAX_ADDRESS_BITS, MAX_COMMAND_BITS,
+};
+
+#[test]
+fn command_msb_first() {
+    // the controller sends bits 7 to 0 and then bits 15 to 8
+    assert_eq!(command_register_value(0x9f, 8, false), 0x009f);
+    assert_eq!(command_register_value(0x1234, 16, false), 0x3412);
+    assert_eq!(command_register_value(0x5, 4, false), 0x0050);
+}
+
+#[test]
+fn command_lsb_first() {
+    assert_eq!(command_register_value(0x9f, 8, true), 0x009f);
+    assert_eq!(command_register_value(0x1234, 16, true), 0x1234);
+}
+
+#[test]
+fn address_msb_first() {
+    // the controller sends from bit 31 downwards
+    assert_eq!(address_register_value(0x12_3456, 24, false), 0x1234_5600);
+    assert_eq!(address_register_value(0x1234_5678, 32, false), 0x1234_5678);
+}
+
+#[test]
+fn address_lsb_first() {
+    // the controller starts with the lowest bit of the highest byte
+    assert_eq!(address_register_value(0x12_3456, 24, true), 0x5634_1200);
+    assert_eq!(address_register_value(0x1234_5678, 32, true), 0x7856_3412);
+}
+
+#[test]
+fn phase_lengths() {
+    assert!(Transaction::new().is_empty());
+    assert!(Transaction::new().has_valid_lengths());
+
+    let mut data = [0u8; 4];
+    let transaction = Transaction::new()
+        .command(0x0b, 8)
+        .address(0x1000, 24)
+        .dummy_cycles(8)
+        .read(&mut data);
+    assert!(!transaction.is_empty());
+    assert!(transaction.has_valid_lengths());
+
+    assert!(!Transaction::new().command(0, 0).has_valid_lengths());
+    assert!(!Transaction::new()
+        .command(0, MAX_COMMAND_BITS + 1)
+        .has_valid_lengths());
+    assert!(!Transaction::new().address(0, 0).has_valid_lengths());
+    assert!(!Transaction::new()
+        .address(0, MAX_ADDRESS_BITS + 1)
+        .has_valid_lengths());
+}
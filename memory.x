@@ -180,6 +180,16 @@ _heap_end = ABSOLUTE(ORIGIN(dram_seg))+LENGTH(dram_seg)+LENGTH(reserved_for_boot
 _text_heap_end = ABSOLUTE(ORIGIN(iram_seg)+LENGTH(iram_seg));
 _external_heap_end = ABSOLUTE(ORIGIN(psram_seg)+LENGTH(psram_seg));
 
+/* executable memory, used to validate backtraces */
+_vectors_seg_start = ABSOLUTE(ORIGIN(vectors_seg));
+_vectors_seg_end = ABSOLUTE(ORIGIN(vectors_seg)+LENGTH(vectors_seg));
+_iram_seg_start = ABSOLUTE(ORIGIN(iram_seg));
+_iram_seg_end = ABSOLUTE(ORIGIN(iram_seg)+LENGTH(iram_seg));
+_rtc_fast_iram_seg_start = ABSOLUTE(ORIGIN(rtc_fast_iram_seg));
+_rtc_fast_iram_seg_end = ABSOLUTE(ORIGIN(rtc_fast_iram_seg)+LENGTH(rtc_fast_iram_seg));
+_irom_seg_start = ABSOLUTE(ORIGIN(irom_seg));
+_irom_seg_end = ABSOLUTE(ORIGIN(irom_seg)+LENGTH(irom_seg));
+
 _stack_start_cpu1 = _heap_end;
 _stack_end_cpu1 = _stack_start_cpu1 + STACK_SIZE;
 _stack_start_cpu0 = _stack_end_cpu1;
@@ -0,0 +1,216 @@
+//! Stack backtraces
+//!
+//! Walks the stack frames of the windowed ABI: the base save area (the 4 words below the stack
+//! pointer) of each frame holds the return address (A0) and stack pointer (A1) of the calling
+//! frame. The live register windows are spilled to the stack first, so these areas are valid.
+//!
+//! The walk stops at the first frame with an invalid stack pointer or program counter. The
+//! resulting program counters can be symbolized on the host, e.g. with
+//! `xtensa-esp32-elf-addr2line -pfiaC -e <elf file> <addresses>`.
+//!
+//! # Example
+//! ```
+//! dprintln!("{}", backtrace::backtrace());
+//! ```
+
+use core::fmt;
+
+/// Maximum number of frames in a backtrace
+pub const MAX_BACKTRACE_DEPTH: usize = 16;
+
+extern "C" {
+    static _vectors_seg_start: u8;
+    static _vectors_seg_end: u8;
+    static _iram_seg_start: u8;
+    static _iram_seg_end: u8;
+    static _rtc_fast_iram_seg_start: u8;
+    static _rtc_fast_iram_seg_end: u8;
+    static _irom_seg_start: u8;
+    static _irom_seg_end: u8;
+}
+
+/// Data RAM range containing the stacks
+const STACK_RANGE: (u32, u32) = (0x3FFA_E000, 0x4000_0000);
+
+/// Number of nested calls needed to spill all 64 physical registers with call12
+const SPILL_DEPTH: u32 = 6;
+
+/// Backtrace: the program counters of the frames, innermost first
+#[derive(Copy, Clone)]
+pub struct Backtrace {
+    pcs: [u32; MAX_BACKTRACE_DEPTH],
+    length: usize,
+    corrupted: bool,
+}
+
+impl Backtrace {
+    /// Program counters of the frames, innermost first
+    pub fn as_slice(&self) -> &[u32] {
+        &self.pcs[..self.length]
+    }
+
+    /// Returns true if the walk stopped at an invalid frame (instead of the end of the stack or
+    /// the maximum depth)
+    pub fn is_corrupted(&self) -> bool {
+        self.corrupted
+    }
+}
+
+impl fmt::Display for Backtrace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Backtrace:")?;
+        for pc in self.as_slice() {
+            write!(f, " {:#010x}", pc)?;
+        }
+        if self.corrupted {
+            f.write_str(" |<-CORRUPTED")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Backtrace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+/// Executable memory ranges (from the segments in memory.x): the vectors, IRAM, RTC fast memory
+/// and flash (IROM)
+fn executable_ranges() -> [(u32, u32); 4] {
+    unsafe {
+        [
+            (
+                &_vectors_seg_start as *const u8 as u32,
+                &_vectors_seg_end as *const u8 as u32,
+            ),
+            (
+                &_iram_seg_start as *const u8 as u32,
+                &_iram_seg_end as *const u8 as u32,
+            ),
+            (
+                &_rtc_fast_iram_seg_start as *const u8 as u32,
+                &_rtc_fast_iram_seg_end as *const u8 as u32,
+            ),
+            (
+                &_irom_seg_start as *const u8 as u32,
+                &_irom_seg_end as *const u8 as u32,
+            ),
+        ]
+    }
+}
+
+/// Returns true if the address is in executable memory
+pub fn is_executable(address: u32) -> bool {
+    executable_ranges()
+        .iter()
+        .any(|(start, end)| address >= *start && address < *end)
+}
+
+/// Returns true if the stack pointer is aligned and in data RAM
+pub fn is_valid_stack_pointer(sp: u32) -> bool {
+    sp % 16 == 0 && sp > STACK_RANGE.0 + 16 && sp <= STACK_RANGE.1
+}
+
+/// Convert a return address (A0) to the address of the call instruction
+///
+/// The top two bits of a return address hold the window increment of the call instead of the
+/// top bits of the address.
+pub fn return_address_to_pc(return_address: u32) -> u32 {
+    ((return_address & 0x3fff_ffff) | 0x4000_0000) - 3
+}
+
+/// Walk the stack starting from the given frame
+///
+/// `pc` is the program counter of the innermost frame, `sp` its stack pointer and
+/// `return_address` its A0 register.
+///
+/// # Safety
+/// The register windows of all frames must have been spilled to the stack.
+pub unsafe fn walk(pc: u32, sp: u32, return_address: u32) -> Backtrace {
+    let mut backtrace = Backtrace {
+        pcs: [0; MAX_BACKTRACE_DEPTH],
+        length: 0,
+        corrupted: false,
+    };
+
+    if !is_executable(pc) || !is_valid_stack_pointer(sp) {
+        backtrace.corrupted = true;
+        return backtrace;
+    }
+    backtrace.pcs[0] = pc;
+    backtrace.length = 1;
+
+    let mut sp = sp;
+    let mut return_address = return_address;
+
+    while backtrace.length < MAX_BACKTRACE_DEPTH && return_address != 0 {
+        let pc = return_address_to_pc(return_address);
+
+        // base save area below the stack pointer: A0 and A1 of the calling frame
+        let base_save = sp as *const u32;
+        let next_return_address = core::ptr::read_volatile(base_save.offset(-4));
+        let next_sp = core::ptr::read_volatile(base_save.offset(-3));
+
+        if !is_executable(pc) || !is_valid_stack_pointer(next_sp) {
+            backtrace.corrupted = true;
+            break;
+        }
+
+        backtrace.pcs[backtrace.length] = pc;
+        backtrace.length += 1;
+
+        sp = next_sp;
+        return_address = next_return_address;
+    }
+
+    backtrace
+}
+
+global_asm!(
+    "
+    .section .text.__esp32_hal_spill_windows, \"ax\"
+    .global __esp32_hal_spill_windows
+    .type __esp32_hal_spill_windows, @function
+    .align 4
+__esp32_hal_spill_windows:
+    entry   a1, 48
+    beqz    a2, 1f
+    addi    a14, a2, -1
+    call12  __esp32_hal_spill_windows
+1:
+    retw
+    "
+);
+
+extern "C" {
+    /// Nested calls with call12, causing window overflows which save all live register windows
+    /// to their stack frames
+    fn __esp32_hal_spill_windows(depth: u32);
+}
+
+/// Save all live register windows to the stack
+pub fn spill_windows() {
+    unsafe { __esp32_hal_spill_windows(SPILL_DEPTH) };
+}
+
+/// Get the backtrace of the current call stack
+///
+/// The first entry is in this function.
+#[inline(never)]
+pub fn backtrace() -> Backtrace {
+    spill_windows();
+
+    let frame = crate::registers::current_frame();
+    unsafe { walk(frame.pc, frame.sp, frame.return_address) }
+}
+
+/// Get the backtrace from an exception context
+///
+/// The first entry is the program counter where the exception occurred. Relies on the
+/// exception handler of xtensa-lx-rt having saved the register windows of the interrupted code.
+#[cfg(feature = "rt")]
+pub fn backtrace_from_context(context: &xtensa_lx_rt::exception::Context) -> Backtrace {
+    spill_windows();
+    unsafe { walk(context.PC, context.A1, context.A0) }
+}
@@ -181,6 +181,26 @@ impl<'a> super::ClockControlConfig {
             .lock(|_| unsafe { CLOCK_CONTROL.as_mut().unwrap().start_app_core(entry) })
     }
 
+    /// Enter light sleep
+    ///
+    /// The chip sleeps until woken by one of the enabled wake-up sources
+    /// (see [enable_wakeup_source](super::wakeup::enable_wakeup_source)) or after the
+    /// optional timeout. RAM and peripheral state are retained.
+    ///
+    /// The other core is stalled before entering sleep and resumed after waking up (unless it
+    /// was already stalled via [park_core](ClockControlConfig::park_core)). It is stopped at
+    /// an arbitrary point, so it should not hold locks needed by the sleeping core.
+    ///
+    /// *Note: data being sent (e.g. by a UART) is corrupted, so flush before sleeping.*
+    pub fn light_sleep(&mut self, timeout: Option<MicroSecondsU64>) -> Result<(), Error> {
+        // the tick count is read outside the critical section, as reading takes a while
+        let alarm =
+            timeout.map(|timeout| self.rtc_tick_count() + timeout * self.slow_rtc_frequency());
+
+        (&CLOCK_CONTROL_MUTEX)
+            .lock(|_| unsafe { CLOCK_CONTROL.as_mut().unwrap().light_sleep(alarm) })
+    }
+
     // The following routines handle thread and interrupt safety themselves
 
     /// Get RTC tick count since boot
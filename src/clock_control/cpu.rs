@@ -8,45 +8,122 @@ use xtensa_lx::set_stack_pointer;
 
 static mut START_CORE1_FUNCTION: Option<fn() -> !> = None;
 
+/// Stall a core via the RTC software stall registers
+///
+/// Does not need the clock control, so it can also be used when panicking.
+///
+/// # Safety
+/// The core is stopped at an arbitrary point, any locks it holds are never released.
+pub unsafe fn park_core(core: Core) {
+    let rtc_control = &*target::RTCCNTL::ptr();
+
+    match core {
+        PRO => {
+            rtc_control
+                .sw_cpu_stall
+                .modify(|_, w| w.sw_stall_procpu_c1().bits(0x21));
+            rtc_control
+                .options0
+                .modify(|_, w| w.sw_stall_procpu_c0().bits(0x02));
+        }
+        APP => {
+            rtc_control
+                .sw_cpu_stall
+                .modify(|_, w| w.sw_stall_appcpu_c1().bits(0x21));
+            rtc_control
+                .options0
+                .modify(|_, w| w.sw_stall_appcpu_c0().bits(0x02));
+        }
+    }
+}
+
+/// Release a core stalled via [park_core]
+///
+/// # Safety
+/// The core may have been stopped for a reason, e.g. a panic or a flash operation in progress.
+pub(crate) unsafe fn unpark_core(core: Core) {
+    let rtc_control = &*target::RTCCNTL::ptr();
+
+    match core {
+        PRO => {
+            rtc_control
+                .sw_cpu_stall
+                .modify(|_, w| w.sw_stall_procpu_c1().bits(0));
+            rtc_control
+                .options0
+                .modify(|_, w| w.sw_stall_procpu_c0().bits(0));
+        }
+        APP => {
+            rtc_control
+                .sw_cpu_stall
+                .modify(|_, w| w.sw_stall_appcpu_c1().bits(0));
+            rtc_control
+                .options0
+                .modify(|_, w| w.sw_stall_appcpu_c0().bits(0));
+        }
+    }
+}
+
+/// Check if the core is stalled via the RTC software stall registers
+pub(crate) fn is_core_parked(core: Core) -> bool {
+    let rtc_control = unsafe { &*target::RTCCNTL::ptr() };
+    let sw_cpu_stall = rtc_control.sw_cpu_stall.read();
+    let options0 = rtc_control.options0.read();
+
+    match core {
+        PRO => {
+            sw_cpu_stall.sw_stall_procpu_c1().bits() == 0x21
+                && options0.sw_stall_procpu_c0().bits() == 0x02
+        }
+        APP => {
+            sw_cpu_stall.sw_stall_appcpu_c1().bits() == 0x21
+                && options0.sw_stall_appcpu_c0().bits() == 0x02
+        }
+    }
+}
+
+/// Gate the clock of the APP core
+///
+/// The APP core can be started again via [start_app_core](super::ClockControl::start_app_core).
+///
+/// # Safety
+/// The core is stopped at an arbitrary point, any locks it holds are never released.
+pub(crate) unsafe fn stop_app_core() {
+    (*target::DPORT::ptr())
+        .appcpu_ctrl_b
+        .modify(|_, w| w.appcpu_clkgate_en().clear_bit());
+}
+
 impl super::ClockControl {
     pub unsafe fn park_core(&mut self, core: Core) {
-        match core {
-            PRO => {
-                self.rtc_control
-                    .sw_cpu_stall
-                    .modify(|_, w| w.sw_stall_procpu_c1().bits(0x21));
-                self.rtc_control
-                    .options0
-                    .modify(|_, w| w.sw_stall_procpu_c0().bits(0x02));
-            }
-            APP => {
-                self.rtc_control
-                    .sw_cpu_stall
-                    .modify(|_, w| w.sw_stall_appcpu_c1().bits(0x21));
-                self.rtc_control
-                    .options0
-                    .modify(|_, w| w.sw_stall_appcpu_c0().bits(0x02));
-            }
-        }
+        park_core(core);
     }
 
     pub fn unpark_core(&mut self, core: Core) {
+        unsafe { unpark_core(core) };
+    }
+
+    /// Check if the core is stalled via the RTC software stall registers
+    pub(super) fn is_core_parked(&self, core: Core) -> bool {
+        is_core_parked(core)
+    }
+
+    /// Check if the core has been started (the PRO core always runs)
+    pub(super) fn is_core_running(&self, core: Core) -> bool {
         match core {
-            PRO => {
-                self.rtc_control
-                    .sw_cpu_stall
-                    .modify(|_, w| unsafe { w.sw_stall_procpu_c1().bits(0) });
-                self.rtc_control
-                    .options0
-                    .modify(|_, w| unsafe { w.sw_stall_procpu_c0().bits(0) });
-            }
+            PRO => true,
             APP => {
-                self.rtc_control
-                    .sw_cpu_stall
-                    .modify(|_, w| unsafe { w.sw_stall_appcpu_c1().bits(0) });
-                self.rtc_control
-                    .options0
-                    .modify(|_, w| unsafe { w.sw_stall_appcpu_c0().bits(0) });
+                self.dport_control
+                    .appcpu_ctrl_b()
+                    .read()
+                    .appcpu_clkgate_en()
+                    .bit_is_set()
+                    && self
+                        .dport_control
+                        .appcpu_ctrl_a()
+                        .read()
+                        .appcpu_resetting()
+                        .bit_is_clear()
             }
         }
     }
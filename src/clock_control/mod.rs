@@ -10,7 +10,6 @@
 //! - 8M and 8MD256 enable/disable
 //! - 150kHz enable/disable
 //! - APLL support
-//! - Implement light sleep
 //! - 32kHz Xtal support
 //! - Allow 8.5MHz clock to be tuned
 //! - Automatic enabling/disabling of 8MHz source (when not in use for rtc_fast_clk or cpu frequency)
@@ -28,6 +27,7 @@ pub mod config;
 pub mod cpu;
 pub mod dfs;
 mod pll;
+pub mod wakeup;
 pub mod watchdog;
 
 /////////////////////////////////
@@ -102,6 +102,9 @@ const DELAY_SLOW_CLK_SWITCH: MicroSeconds = MicroSeconds(300);
 const DELAY_8M_ENABLE: MicroSeconds = MicroSeconds(50);
 const DELAY_DBIAS_RAISE: MicroSeconds = MicroSeconds(3);
 
+// Maximum time (while awake) between requesting light sleep and the sleep or its rejection
+const LIGHT_SLEEP_ENTRY_TIMEOUT: MicroSeconds = MicroSeconds(10_000);
+
 // number of wait cycles when enabling 8MHz clock
 const CK8M_WAIT_DEFAULT: u8 = 20;
 
@@ -140,6 +143,10 @@ pub enum Error {
     InvalidRegisterValue,
     InvalidCore,
     CoreAlreadyRunning,
+    /// Light sleep was rejected by the hardware
+    SleepRejected,
+    /// Light sleep was neither entered nor rejected by the hardware
+    SleepTimeout,
 }
 
 /// CPU/APB/REF clock source
@@ -1340,6 +1347,112 @@ impl ClockControl {
     pub fn rtc_nanoseconds(&self) -> NanoSecondsU64 {
         self.rtc_tick_count() / self.slow_rtc_frequency
     }
+
+    /// Enter light sleep until woken by one of the enabled wake-up sources or the RTC tick
+    /// count reaches the alarm
+    ///
+    /// The other core is stalled during sleep (unless it is already stalled or not running),
+    /// so it does not run on the Xtal clock or access peripherals while the chip sleeps.
+    fn light_sleep(&mut self, alarm: Option<TicksU64>) -> Result<(), Error> {
+        let other_core = crate::get_other_core();
+        let park_other_core = self.is_core_running(other_core) && !self.is_core_parked(other_core);
+
+        if park_other_core {
+            unsafe { self.park_core(other_core) };
+        }
+
+        let result = self.enter_light_sleep(alarm);
+
+        if park_other_core {
+            self.unpark_core(other_core);
+        }
+
+        result
+    }
+
+    /// Enter light sleep on the current core
+    ///
+    /// The CPU is switched to the Xtal clock during sleep, as the PLL is powered down. The
+    /// timer wake-up source and sleep timer are restored afterwards.
+    fn enter_light_sleep(&mut self, alarm: Option<TicksU64>) -> Result<(), Error> {
+        let cpu_source = self.cpu_source;
+        let cpu_frequency = self.cpu_frequency;
+        self.set_cpu_frequency(CPUSource::Xtal, self.xtal_frequency, true)?;
+
+        let timer_wakeup = wakeup::is_wakeup_source_enabled(wakeup::WakeupSource::Timer);
+        let sleep_timer0 = self.rtc_control.slp_timer0.read().bits();
+        let sleep_timer1 = self.rtc_control.slp_timer1.read().bits();
+
+        if let Some(alarm) = alarm {
+            let alarm = u64::from(alarm);
+
+            self.rtc_control
+                .slp_timer0
+                .write(|w| unsafe { w.bits(alarm as u32) });
+            self.rtc_control.slp_timer1.write(|w| unsafe {
+                w.slp_val_hi()
+                    .bits((alarm >> 32) as u16)
+                    .main_timer_alarm_en()
+                    .set_bit()
+            });
+        }
+        wakeup::enable_wakeup_source(wakeup::WakeupSource::Timer, alarm.is_some());
+
+        self.rtc_control.int_clr.write(|w| {
+            w.slp_reject_int_clr()
+                .set_bit()
+                .slp_wakeup_int_clr()
+                .set_bit()
+        });
+        self.rtc_control
+            .slp_reject_conf
+            .write(|w| unsafe { w.bits(0) });
+
+        // start entering sleep and wait for wake-up (or rejection)
+        self.rtc_control
+            .state0
+            .modify(|_, w| w.sleep_en().set_bit());
+
+        // the CPU does not run during sleep, so this only limits the time spent awake
+        let timeout = self.time_to_cpu_cycles(LIGHT_SLEEP_ENTRY_TIMEOUT) as u32;
+        let start = get_cycle_count();
+        let result = loop {
+            let int_raw = self.rtc_control.int_raw.read();
+            if int_raw.slp_reject_int_raw().bit_is_set() {
+                break Err(Error::SleepRejected);
+            }
+            if int_raw.slp_wakeup_int_raw().bit_is_set() {
+                break Ok(());
+            }
+            if get_cycle_count().wrapping_sub(start) > timeout {
+                self.rtc_control
+                    .state0
+                    .modify(|_, w| w.sleep_en().clear_bit());
+                break Err(Error::SleepTimeout);
+            }
+        };
+
+        self.rtc_control.int_clr.write(|w| {
+            w.slp_reject_int_clr()
+                .set_bit()
+                .slp_wakeup_int_clr()
+                .set_bit()
+        });
+
+        if alarm.is_some() {
+            self.rtc_control
+                .slp_timer0
+                .write(|w| unsafe { w.bits(sleep_timer0) });
+            self.rtc_control
+                .slp_timer1
+                .write(|w| unsafe { w.bits(sleep_timer1) });
+        }
+        wakeup::enable_wakeup_source(wakeup::WakeupSource::Timer, timer_wakeup);
+
+        self.set_cpu_frequency(cpu_source, cpu_frequency, false)?;
+
+        result
+    }
 }
 
 /// Custom debug formatter
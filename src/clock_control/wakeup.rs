@@ -0,0 +1,86 @@
+//! Wake-up sources and causes for light sleep
+//!
+//! Peripherals that can wake the chip (e.g. [Serial](crate::serial::Serial)) enable their
+//! wake-up source themselves. The timer wake-up source is handled by
+//! [light_sleep](super::ClockControlConfig::light_sleep).
+
+use crate::prelude::*;
+use crate::target::RTCCNTL;
+
+/// Light sleep wake-up source
+///
+/// The value of each source is its bit position in the wake-up enable register.
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum WakeupSource {
+    /// External wake-up via a single RTC GPIO
+    Ext0 = 0,
+    /// External wake-up via multiple RTC GPIOs
+    Ext1 = 1,
+    /// GPIO wake-up (see [listen_with_options](crate::gpio::Pin::listen_with_options))
+    Gpio = 2,
+    /// RTC timer
+    Timer = 3,
+    /// SDIO
+    Sdio = 4,
+    /// WiFi MAC
+    Mac = 5,
+    /// UART0 RX activity
+    Uart0 = 6,
+    /// UART1 RX activity
+    Uart1 = 7,
+    /// Touch sensor
+    Touch = 8,
+    /// ULP coprocessor
+    Ulp = 9,
+    /// Bluetooth
+    Bt = 10,
+}
+
+// The wake-up enable register is shared by all peripherals
+static WAKEUP_MUTEX: CriticalSectionSpinLockMutex<()> = CriticalSectionSpinLockMutex::new(());
+
+/// Enable or disable a wake-up source
+pub fn enable_wakeup_source(source: WakeupSource, enable: bool) {
+    let rtccntl = unsafe { &*RTCCNTL::ptr() };
+    let mask = 1 << source as u16;
+
+    (&WAKEUP_MUTEX).lock(|_| {
+        rtccntl.wakeup_state.modify(|r, w| unsafe {
+            let enabled = r.wakeup_ena().bits();
+            w.wakeup_ena().bits(if enable {
+                enabled | mask
+            } else {
+                enabled & !mask
+            })
+        })
+    });
+}
+
+/// Check if a wake-up source is enabled
+pub fn is_wakeup_source_enabled(source: WakeupSource) -> bool {
+    let rtccntl = unsafe { &*RTCCNTL::ptr() };
+    rtccntl.wakeup_state.read().wakeup_ena().bits() & (1 << source as u16) != 0
+}
+
+/// Get the source which woke the chip from the last (light or deep) sleep
+///
+/// After a wake-up from deep sleep the reset reason is
+/// [DeepSleep](crate::reset::ResetReason::DeepSleep). Returns None when not woken from sleep.
+/// When multiple sources triggered at the same time, the one with the lowest bit position is
+/// returned (see [is_wakeup_cause] to check for a specific source).
+pub fn wakeup_cause() -> Option<WakeupSource> {
+    use WakeupSource::*;
+
+    [
+        Ext0, Ext1, Gpio, Timer, Sdio, Mac, Uart0, Uart1, Touch, Ulp, Bt,
+    ]
+    .iter()
+    .copied()
+    .find(|source| is_wakeup_cause(*source))
+}
+
+/// Check if the source contributed to the wake-up from the last sleep
+pub fn is_wakeup_cause(source: WakeupSource) -> bool {
+    let rtccntl = unsafe { &*RTCCNTL::ptr() };
+    rtccntl.wakeup_state.read().wakeup_cause().bits() & (1 << source as u16) != 0
+}
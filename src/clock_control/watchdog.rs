@@ -180,6 +180,37 @@ impl Watchdog {
     }
 }
 
+/// Reset the system after the given number of slow RTC clock cycles
+///
+/// Directly programs the watchdog, without needing the clock control, so it can also be used
+/// when panicking. Overrides any configuration.
+pub(crate) fn system_reset_after(ticks: u32) {
+    let rtc_control = unsafe { &(*RTCCNTL::ptr()) };
+
+    rtc_control
+        .wdtwprotect
+        .write(|w| unsafe { w.bits(WATCHDOG_UNBLOCK_KEY) });
+
+    rtc_control.wdtfeed.write(|w| w.wdt_feed().set_bit());
+    rtc_control
+        .wdtconfig1
+        .write(|w| unsafe { w.wdt_stg0_hold().bits(ticks) });
+    rtc_control.wdtconfig0.modify(|_, w| {
+        w.wdt_flashboot_mod_en()
+            .clear_bit()
+            .wdt_pause_in_slp()
+            .clear_bit()
+            .wdt_stg0()
+            .variant(WatchdogAction::RESETSYSTEM)
+            .wdt_en()
+            .set_bit()
+    });
+
+    rtc_control
+        .wdtwprotect
+        .write(|w| unsafe { w.bits(WATCHDOG_BLOCK_VALUE) });
+}
+
 /// Enable watchdog timer, only change stage 1 period, don't change default action
 impl WatchdogEnable for Watchdog {
     type Time = MicroSeconds;
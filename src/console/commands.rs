@@ -0,0 +1,242 @@
+//! Console commands
+//!
+//! Commands are stored in static tables. [BUILTIN_COMMANDS] contains the commands provided by
+//! this crate, applications can add their own table(s) via
+//! [Console::new](super::Console::new).
+
+use core::fmt::Write;
+
+use super::parser::{optional_u32_argument, u32_argument};
+use super::Error;
+use crate::clock_control::ClockControlConfig;
+use crate::efuse::Efuse;
+
+/// Command handler
+///
+/// Receives the output to write to and all arguments, including the command name at index 0.
+pub type Handler = fn(output: &mut dyn Write, args: &[&str]) -> Result<(), Error>;
+
+/// Console command
+pub struct Command {
+    /// Name used to invoke the command
+    pub name: &'static str,
+    /// Usage of the arguments, shown by the help command
+    pub usage: &'static str,
+    /// Short description, shown by the help command
+    pub help: &'static str,
+    /// Function executing the command
+    pub handler: Handler,
+}
+
+/// Maximum number of words printed by peek
+const MAX_PEEK_COUNT: u32 = 256;
+/// Number of words printed per line by peek
+const WORDS_PER_LINE: u32 = 4;
+
+/// Address ranges accessible by peek, and by poke if writable: (start, end, writable)
+const MEMORY_REGIONS: &[(u32, u32, bool)] = &[
+    // peripherals (DPORT bus)
+    (0x3ff0_0000, 0x3ff8_0000, true),
+    // RTC fast memory (data bus)
+    (0x3ff8_0000, 0x3ff8_2000, true),
+    // internal ROM 1
+    (0x3ff9_0000, 0x3ffa_0000, false),
+    // internal SRAM 2 and SRAM 1 (data bus)
+    (0x3ffa_e000, 0x4000_0000, true),
+    // internal ROM 0
+    (0x4000_0000, 0x4006_0000, false),
+    // internal SRAM 0 and SRAM 1 (instruction bus)
+    (0x4007_0000, 0x400c_0000, true),
+    // RTC fast memory (instruction bus)
+    (0x400c_0000, 0x400c_2000, true),
+    // RTC slow memory
+    (0x5000_0000, 0x5000_2000, true),
+    // peripherals (AHB bus)
+    (0x6000_0000, 0x6004_0000, true),
+];
+
+/// Start of the external RAM (data bus)
+#[cfg(feature = "external_ram")]
+const EXTERNAL_RAM_START: u32 = 0x3f80_0000;
+
+/// Commands provided by the console
+pub static BUILTIN_COMMANDS: &[Command] = &[
+    Command {
+        name: "peek",
+        usage: "<address> [count]",
+        help: "read 32-bit words from RAM, ROM or registers",
+        handler: peek,
+    },
+    Command {
+        name: "poke",
+        usage: "<address> <value>",
+        help: "write a 32-bit word to RAM or a register",
+        handler: poke,
+    },
+    Command {
+        name: "efuse",
+        usage: "",
+        help: "show chip information from the efuses",
+        handler: efuse,
+    },
+    Command {
+        name: "clocks",
+        usage: "",
+        help: "show the clock frequencies",
+        handler: clocks,
+    },
+    Command {
+        name: "heap",
+        usage: "",
+        help: "show the heap usage",
+        handler: heap,
+    },
+];
+
+fn word_address(args: &[&str]) -> Result<u32, Error> {
+    let address = u32_argument(args, 1)?;
+    if address % 4 != 0 {
+        return Err(Error::Alignment);
+    }
+    Ok(address)
+}
+
+/// Check that `count` words from `address` are within a single accessible region
+///
+/// Accessing unmapped addresses causes an exception, and flash is not accessible as it may
+/// not be mapped into the cache.
+fn check_range(address: u32, count: u32, write: bool) -> Result<(), Error> {
+    let end = count
+        .checked_mul(4)
+        .and_then(|length| address.checked_add(length))
+        .ok_or(Error::InvalidAddress)?;
+    let contains = |start: u32, region_end: u32| address >= start && end <= region_end;
+
+    let valid = MEMORY_REGIONS
+        .iter()
+        .any(|&(start, region_end, writable)| contains(start, region_end) && (writable || !write));
+
+    #[cfg(feature = "external_ram")]
+    let valid = valid
+        || contains(
+            EXTERNAL_RAM_START,
+            EXTERNAL_RAM_START + crate::external_ram::get_size() as u32,
+        );
+
+    if valid {
+        Ok(())
+    } else {
+        Err(Error::InvalidAddress)
+    }
+}
+
+fn peek(output: &mut dyn Write, args: &[&str]) -> Result<(), Error> {
+    let address = word_address(args)?;
+    let count = optional_u32_argument(args, 2)?.unwrap_or(1);
+    if count == 0 || count > MAX_PEEK_COUNT {
+        return Err(Error::InvalidArgument);
+    }
+    check_range(address, count, false)?;
+
+    for index in 0..count {
+        let word_address = address.wrapping_add(index * 4);
+        if index % WORDS_PER_LINE == 0 {
+            if index != 0 {
+                writeln!(output)?;
+            }
+            write!(output, "{:08x}:", word_address)?;
+        }
+        let value = unsafe { core::ptr::read_volatile(word_address as *const u32) };
+        write!(output, " {:08x}", value)?;
+    }
+    writeln!(output)?;
+
+    Ok(())
+}
+
+fn poke(output: &mut dyn Write, args: &[&str]) -> Result<(), Error> {
+    let address = word_address(args)?;
+    let value = u32_argument(args, 2)?;
+    check_range(address, 1, true)?;
+
+    unsafe { core::ptr::write_volatile(address as *mut u32, value) };
+    writeln!(output, "{:08x}: {:08x}", address, value)?;
+
+    Ok(())
+}
+
+fn efuse(output: &mut dyn Write, _args: &[&str]) -> Result<(), Error> {
+    let mac = Efuse::get_mac_address();
+    writeln!(
+        output,
+        "MAC address:       {:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}",
+        mac[0], mac[1], mac[2], mac[3], mac[4], mac[5]
+    )?;
+    writeln!(output, "Chip type:         {:?}", Efuse::get_chip_type())?;
+    writeln!(output, "Core count:        {}", Efuse::get_core_count())?;
+    writeln!(
+        output,
+        "Max CPU frequency: {}",
+        Efuse::get_max_cpu_fequency()
+    )?;
+    writeln!(
+        output,
+        "Bluetooth:         {}",
+        if Efuse::is_bluetooth_enabled() {
+            "enabled"
+        } else {
+            "disabled"
+        }
+    )?;
+    match Efuse::get_adc_vref() {
+        Some(vref) => writeln!(output, "ADC vref:          {}mV", vref)?,
+        None => writeln!(output, "ADC vref:          not calibrated")?,
+    }
+
+    Ok(())
+}
+
+fn clocks(output: &mut dyn Write, _args: &[&str]) -> Result<(), Error> {
+    writeln!(output, "{:#?}", ClockControlConfig {})?;
+    Ok(())
+}
+
+#[cfg(feature = "alloc")]
+fn heap(output: &mut dyn Write, _args: &[&str]) -> Result<(), Error> {
+    use crate::alloc::{AllocatorSize, DEFAULT_ALLOCATOR, DRAM_ALLOCATOR, IRAM_ALLOCATOR};
+
+    fn heap_line(
+        output: &mut dyn Write,
+        name: &str,
+        allocator: &dyn AllocatorSize,
+    ) -> Result<(), Error> {
+        writeln!(
+            output,
+            "{:<9}{:>10}{:>10}{:>10}",
+            name,
+            allocator.size(),
+            allocator.used(),
+            allocator.free()
+        )?;
+        Ok(())
+    }
+
+    writeln!(
+        output,
+        "{:<9}{:>10}{:>10}{:>10}",
+        "heap", "size", "used", "free"
+    )?;
+    heap_line(output, "default", &DEFAULT_ALLOCATOR)?;
+    heap_line(output, "dram", &DRAM_ALLOCATOR)?;
+    heap_line(output, "iram", &IRAM_ALLOCATOR)?;
+    #[cfg(feature = "external_ram")]
+    heap_line(output, "external", &crate::alloc::EXTERNAL_ALLOCATOR)?;
+
+    Ok(())
+}
+
+#[cfg(not(feature = "alloc"))]
+fn heap(output: &mut dyn Write, _args: &[&str]) -> Result<(), Error> {
+    writeln!(output, "heap statistics require the alloc feature")?;
+    Ok(())
+}
@@ -0,0 +1,127 @@
+//! Line editor
+//!
+//! Collects received characters into a line, handling backspace and line endings. The editor
+//! does not perform any I/O itself: each input returns an [Action] telling the caller what to
+//! echo, so it can be used (and tested) independent of the hardware.
+
+/// Maximum number of characters in a line
+pub const MAX_LINE_LENGTH: usize = 128;
+
+const BACKSPACE: u8 = 0x08;
+const DELETE: u8 = 0x7f;
+const CTRL_C: u8 = 0x03;
+const CTRL_U: u8 = 0x15;
+
+/// Action resulting from an input character
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum Action {
+    /// Nothing to do
+    None,
+    /// Character added to the line, echo it
+    Echo(u8),
+    /// Last character removed from the line, erase it from the terminal
+    Erase,
+    /// Number of characters removed from the line, erase them from the terminal
+    EraseLine(usize),
+    /// Line complete, it can be retrieved via [LineEditor::line]
+    Line,
+    /// Line cancelled
+    Cancel,
+    /// Character rejected (line full or nothing to erase), ring the bell
+    Bell,
+}
+
+/// Line editor
+pub struct LineEditor {
+    buffer: [u8; MAX_LINE_LENGTH],
+    length: usize,
+    last_was_cr: bool,
+    complete: bool,
+}
+
+impl LineEditor {
+    /// Create a new (empty) line editor
+    pub const fn new() -> Self {
+        LineEditor {
+            buffer: [0; MAX_LINE_LENGTH],
+            length: 0,
+            last_was_cr: false,
+            complete: false,
+        }
+    }
+
+    /// Process an input character
+    ///
+    /// Lines are terminated by CR, LF or CR LF. Backspace and delete remove the last character,
+    /// ctrl-U removes the complete line and ctrl-C cancels the line. Other control characters
+    /// and non-ASCII characters are ignored.
+    ///
+    /// After a [Action::Line] the line is available until the next input.
+    pub fn input(&mut self, byte: u8) -> Action {
+        if self.complete {
+            self.clear();
+        }
+
+        let last_was_cr = self.last_was_cr;
+        self.last_was_cr = byte == b'\r';
+
+        match byte {
+            // LF directly after CR is part of the same line ending
+            b'\n' if last_was_cr => Action::None,
+            b'\r' | b'\n' => {
+                self.complete = true;
+                Action::Line
+            }
+            BACKSPACE | DELETE => {
+                if self.length > 0 {
+                    self.length -= 1;
+                    Action::Erase
+                } else {
+                    Action::Bell
+                }
+            }
+            CTRL_U => {
+                let length = self.length;
+                self.length = 0;
+                Action::EraseLine(length)
+            }
+            CTRL_C => {
+                self.length = 0;
+                Action::Cancel
+            }
+            b' '..=b'~' => {
+                if self.length < MAX_LINE_LENGTH {
+                    self.buffer[self.length] = byte;
+                    self.length += 1;
+                    Action::Echo(byte)
+                } else {
+                    Action::Bell
+                }
+            }
+            _ => Action::None,
+        }
+    }
+
+    /// The current (or completed) line
+    pub fn line(&self) -> &str {
+        // only printable ASCII characters are stored
+        core::str::from_utf8(&self.buffer[..self.length]).unwrap_or("")
+    }
+
+    /// Returns true if the line is empty
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    /// Clear the line
+    pub fn clear(&mut self) {
+        self.length = 0;
+        self.complete = false;
+    }
+}
+
+impl Default for LineEditor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
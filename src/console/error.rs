@@ -0,0 +1,32 @@
+//! Console errors
+
+use core::fmt;
+
+/// Console errors
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum Error {
+    /// Command not found in the command tables
+    UnknownCommand,
+    /// More than [MAX_ARGUMENTS](super::parser::MAX_ARGUMENTS) arguments
+    TooManyArguments,
+    /// Closing quote missing
+    UnterminatedQuote,
+    /// Required argument missing
+    MissingArgument,
+    /// Argument is not a valid number
+    InvalidNumber,
+    /// Argument out of range
+    InvalidArgument,
+    /// Address is not word aligned
+    Alignment,
+    /// Address range outside of the accessible memory and peripherals
+    InvalidAddress,
+    /// Failed to write the output
+    Format,
+}
+
+impl From<fmt::Error> for Error {
+    fn from(_: fmt::Error) -> Self {
+        Error::Format
+    }
+}
@@ -0,0 +1,193 @@
+//! Interactive serial console
+//!
+//! Reads lines with echo and backspace handling from a serial receiver and dispatches them to
+//! commands from static command tables. No heap allocation is used.
+//!
+//! The [line editor](editor) and [argument parser](parser) do not depend on the hardware.
+//!
+//! # Example
+//! ```
+//! fn hello(output: &mut dyn core::fmt::Write, args: &[&str]) -> Result<(), console::Error> {
+//!     writeln!(output, "Hello {}", args.get(1).unwrap_or(&"world"))?;
+//!     Ok(())
+//! }
+//!
+//! static COMMANDS: &[Command] = &[Command {
+//!     name: "hello",
+//!     usage: "[name]",
+//!     help: "say hello",
+//!     handler: hello,
+//! }];
+//!
+//! let (tx, rx) = serial.split();
+//! let mut console = Console::new(tx, rx, &[BUILTIN_COMMANDS, COMMANDS]);
+//! console.run();
+//! ```
+
+use core::fmt::{self, Write};
+use embedded_hal::serial::Read;
+
+pub mod commands;
+pub mod editor;
+mod error;
+pub mod parser;
+
+pub use commands::{Command, Handler, BUILTIN_COMMANDS};
+use editor::{Action, LineEditor};
+pub use error::Error;
+
+/// Default prompt
+pub const DEFAULT_PROMPT: &str = "> ";
+
+/// Serial console
+pub struct Console<TX: Write, RX: Read<u8>> {
+    tx: TX,
+    rx: RX,
+    command_tables: &'static [&'static [Command]],
+    prompt: &'static str,
+    editor: LineEditor,
+    prompt_shown: bool,
+}
+
+impl<TX: Write, RX: Read<u8>> Console<TX, RX> {
+    /// Create a new console using the given command tables
+    ///
+    /// When commands with the same name appear in multiple tables, the first one is used.
+    pub fn new(tx: TX, rx: RX, command_tables: &'static [&'static [Command]]) -> Self {
+        Console {
+            tx,
+            rx,
+            command_tables,
+            prompt: DEFAULT_PROMPT,
+            editor: LineEditor::new(),
+            prompt_shown: false,
+        }
+    }
+
+    /// Change the prompt
+    pub fn change_prompt(&mut self, prompt: &'static str) -> &mut Self {
+        self.prompt = prompt;
+        self
+    }
+
+    /// Release the serial transmitter and receiver
+    pub fn release(self) -> (TX, RX) {
+        (self.tx, self.rx)
+    }
+
+    /// Process all received characters
+    ///
+    /// Completed lines are executed directly. Returns when no more characters are available.
+    pub fn poll(&mut self) -> Result<(), Error> {
+        if !self.prompt_shown {
+            self.tx.write_str(self.prompt)?;
+            self.prompt_shown = true;
+        }
+
+        while let Ok(byte) = self.rx.read() {
+            match self.editor.input(byte) {
+                Action::None => {}
+                Action::Echo(byte) => self.tx.write_char(byte as char)?,
+                Action::Erase => self.tx.write_str("\x08 \x08")?,
+                Action::EraseLine(count) => {
+                    for _ in 0..count {
+                        self.tx.write_str("\x08 \x08")?;
+                    }
+                }
+                Action::Bell => self.tx.write_char('\x07')?,
+                Action::Cancel => {
+                    self.tx.write_str("^C\r\n")?;
+                    self.tx.write_str(self.prompt)?;
+                }
+                Action::Line => {
+                    self.tx.write_str("\r\n")?;
+                    self.execute_line()?;
+                    self.tx.write_str(self.prompt)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Run the console forever
+    pub fn run(&mut self) -> ! {
+        loop {
+            // output errors can not be reported anywhere, just continue
+            let _ = self.poll();
+        }
+    }
+
+    fn execute_line(&mut self) -> Result<(), Error> {
+        let mut output = CrLf(&mut self.tx);
+
+        let mut args = [""; parser::MAX_ARGUMENTS];
+        let count = match parser::split(self.editor.line(), &mut args) {
+            Ok(0) => return Ok(()),
+            Ok(count) => count,
+            Err(error) => return report_error(&mut output, error),
+        };
+        let args = &args[..count];
+
+        if args[0] == "help" {
+            return help(&mut output, self.command_tables);
+        }
+
+        let result = match find_command(self.command_tables, args[0]) {
+            Some(command) => (command.handler)(&mut output, args),
+            None => Err(Error::UnknownCommand),
+        };
+
+        match result {
+            Ok(()) => Ok(()),
+            Err(Error::Format) => Err(Error::Format),
+            Err(error) => report_error(&mut output, error),
+        }
+    }
+}
+
+/// Find a command by name
+pub fn find_command(
+    command_tables: &'static [&'static [Command]],
+    name: &str,
+) -> Option<&'static Command> {
+    command_tables
+        .iter()
+        .flat_map(|table| table.iter())
+        .find(|command| command.name == name)
+}
+
+fn help(
+    output: &mut dyn Write,
+    command_tables: &'static [&'static [Command]],
+) -> Result<(), Error> {
+    for command in command_tables.iter().flat_map(|table| table.iter()) {
+        writeln!(
+            output,
+            "{:<8}{:<20}{}",
+            command.name, command.usage, command.help
+        )?;
+    }
+    writeln!(output, "{:<8}{:<20}{}", "help", "", "show this help")?;
+    Ok(())
+}
+
+fn report_error(output: &mut dyn Write, error: Error) -> Result<(), Error> {
+    writeln!(output, "error: {:?}", error)?;
+    Ok(())
+}
+
+/// Translates LF into CR LF for the terminal
+struct CrLf<'a, TX: Write>(&'a mut TX);
+
+impl<TX: Write> Write for CrLf<'_, TX> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for (index, part) in s.split('\n').enumerate() {
+            if index != 0 {
+                self.0.write_str("\r\n")?;
+            }
+            self.0.write_str(part)?;
+        }
+        Ok(())
+    }
+}
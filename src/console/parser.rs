@@ -0,0 +1,88 @@
+//! Command line parsing
+//!
+//! Splits a line into arguments and parses numeric arguments. Like the
+//! [line editor](super::editor), this does not depend on the hardware.
+
+use super::Error;
+
+/// Maximum number of arguments (including the command name)
+pub const MAX_ARGUMENTS: usize = 8;
+
+/// Split the line into whitespace separated arguments
+///
+/// Arguments containing whitespace can be enclosed in double quotes.
+/// Returns the number of arguments stored in `args`.
+pub fn split<'a>(line: &'a str, args: &mut [&'a str]) -> Result<usize, Error> {
+    let mut count = 0;
+    let mut rest = line.trim_start();
+
+    while !rest.is_empty() {
+        let (arg, remaining) = if let Some(quoted) = rest.strip_prefix('"') {
+            match quoted.find('"') {
+                Some(end) => (&quoted[..end], &quoted[end + 1..]),
+                None => return Err(Error::UnterminatedQuote),
+            }
+        } else {
+            match rest.find(char::is_whitespace) {
+                Some(end) => (&rest[..end], &rest[end..]),
+                None => (rest, ""),
+            }
+        };
+
+        if count == args.len() {
+            return Err(Error::TooManyArguments);
+        }
+        args[count] = arg;
+        count += 1;
+
+        rest = remaining.trim_start();
+    }
+
+    Ok(count)
+}
+
+/// Parse an unsigned number
+///
+/// Supports decimal, hexadecimal (0x prefix) and binary (0b prefix) numbers. Underscores can be
+/// used as separators.
+pub fn parse_u32(arg: &str) -> Result<u32, Error> {
+    let (digits, radix) =
+        if let Some(hex) = arg.strip_prefix("0x").or_else(|| arg.strip_prefix("0X")) {
+            (hex, 16)
+        } else if let Some(binary) = arg.strip_prefix("0b").or_else(|| arg.strip_prefix("0B")) {
+            (binary, 2)
+        } else {
+            (arg, 10)
+        };
+
+    let mut value: u32 = 0;
+    let mut has_digits = false;
+
+    for c in digits.chars() {
+        if c == '_' {
+            continue;
+        }
+        let digit = c.to_digit(radix).ok_or(Error::InvalidNumber)?;
+        value = value
+            .checked_mul(radix)
+            .and_then(|value| value.checked_add(digit))
+            .ok_or(Error::InvalidNumber)?;
+        has_digits = true;
+    }
+
+    if has_digits {
+        Ok(value)
+    } else {
+        Err(Error::InvalidNumber)
+    }
+}
+
+/// Get and parse a required numeric argument
+pub fn u32_argument(args: &[&str], index: usize) -> Result<u32, Error> {
+    parse_u32(args.get(index).ok_or(Error::MissingArgument)?)
+}
+
+/// Get and parse an optional numeric argument
+pub fn optional_u32_argument(args: &[&str], index: usize) -> Result<Option<u32>, Error> {
+    args.get(index).map(|arg| parse_u32(arg)).transpose()
+}
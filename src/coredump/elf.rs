@@ -0,0 +1,295 @@
+//! ELF core file serialisation
+//!
+//! Writes the parts of an Xtensa ELF core file in the layout expected by `espcoredump.py`:
+//! - ELF header and program headers
+//! - notes: `NT_PRSTATUS` with the registers of each core, `ESP_CORE_DUMP_INFO` and
+//!   `EXTRA_INFO` with the exception registers
+//! - the contents of the memory segments
+//!
+//! All values are little endian.
+//!
+//! *Note: this module does not depend on the hardware, so it is also compiled and tested on the
+//! host (see the `host-tests` directory).*
+
+/// Size of the ELF header
+pub const ELF_HEADER_SIZE: usize = 52;
+/// Size of a program header
+pub const PROGRAM_HEADER_SIZE: usize = 32;
+
+/// Program header type of loadable (memory) segments
+pub const PT_LOAD: u32 = 1;
+/// Program header type of note segments
+pub const PT_NOTE: u32 = 4;
+
+/// Segment flags: readable, writable, executable
+pub const PF_R: u32 = 4;
+pub const PF_W: u32 = 2;
+pub const PF_X: u32 = 1;
+
+/// Note type with the registers of a thread (core)
+pub const NT_PRSTATUS: u32 = 1;
+/// Note type with the core dump version and application SHA256
+pub const ESP_CORE_DUMP_INFO_TYPE: u32 = 8266;
+/// Note type with the crashed thread and exception registers
+pub const ESP_CORE_DUMP_EXTRA_INFO_TYPE: u32 = 677;
+
+/// Name of the register notes
+pub const PRSTATUS_NAME: &str = "CORE";
+/// Name of the core dump information note
+pub const INFO_NAME: &str = "ESP_CORE_DUMP_INFO";
+/// Name of the extra information note
+pub const EXTRA_INFO_NAME: &str = "EXTRA_INFO";
+
+/// Length of the application ELF SHA256 (hex string including terminator) in the info note
+pub const APP_SHA256_SIZE: usize = 66;
+
+const ET_CORE: u16 = 4;
+const EM_XTENSA: u16 = 94;
+
+/// Size of the process status preceding the registers in a `NT_PRSTATUS` note
+const PRSTATUS_HEADER_SIZE: usize = 72;
+/// Number of words in the register set: 8 special registers, 56 reserved, 64 address registers
+const GREGSET_WORDS: usize = 128;
+const GREGSET_RESERVED_WORDS: usize = 56;
+
+/// Size of the `NT_PRSTATUS` note description
+pub const PRSTATUS_SIZE: usize = PRSTATUS_HEADER_SIZE + GREGSET_WORDS * 4;
+/// Size of the `ESP_CORE_DUMP_INFO` note description
+pub const INFO_SIZE: usize = 4 + APP_SHA256_SIZE;
+/// Size of the `EXTRA_INFO` note description
+pub const EXTRA_INFO_SIZE: usize = 4 + 3 * 8;
+
+/// Register numbers used in the `EXTRA_INFO` note
+const EXCCAUSE_REGISTER: u32 = 232;
+const EXCVADDR_REGISTER: u32 = 238;
+const EPC1_REGISTER: u32 = 177;
+
+/// Destination of the serialised data
+pub trait Output {
+    type Error;
+
+    /// Append data
+    fn write(&mut self, data: &[u8]) -> Result<(), Self::Error>;
+}
+
+/// Output into a byte slice (e.g. for comparing with a reference file)
+pub struct SliceOutput<'a> {
+    buffer: &'a mut [u8],
+    length: usize,
+}
+
+impl<'a> SliceOutput<'a> {
+    /// Create a new output writing to the start of the buffer
+    pub fn new(buffer: &'a mut [u8]) -> Self {
+        SliceOutput { buffer, length: 0 }
+    }
+
+    /// The data written so far
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buffer[..self.length]
+    }
+}
+
+/// Error when the slice is too small
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub struct BufferFull;
+
+impl Output for SliceOutput<'_> {
+    type Error = BufferFull;
+
+    fn write(&mut self, data: &[u8]) -> Result<(), BufferFull> {
+        let end = self.length + data.len();
+        if end > self.buffer.len() {
+            return Err(BufferFull);
+        }
+        self.buffer[self.length..end].copy_from_slice(data);
+        self.length = end;
+        Ok(())
+    }
+}
+
+/// Register context of a core
+///
+/// The address registers are those of the current window: after spilling the register windows
+/// only the current window is live, so the window base is 0 and the window start is 1.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Registers {
+    pub pc: u32,
+    pub ps: u32,
+    pub lbeg: u32,
+    pub lend: u32,
+    pub lcount: u32,
+    pub sar: u32,
+    pub a: [u32; 16],
+}
+
+/// Program header
+#[derive(Copy, Clone, Debug)]
+pub struct ProgramHeader {
+    /// Segment type ([PT_LOAD] or [PT_NOTE])
+    pub segment_type: u32,
+    /// Offset of the segment data in the file
+    pub offset: u32,
+    /// Memory address of the segment (0 for notes)
+    pub address: u32,
+    /// Size of the segment data
+    pub size: u32,
+    /// Segment flags ([PF_R], [PF_W], [PF_X])
+    pub flags: u32,
+}
+
+fn align4(value: usize) -> usize {
+    (value + 3) & !3
+}
+
+const PADDING: [u8; 4] = [0; 4];
+
+/// Write the ELF header of a core file with the given number of program headers
+///
+/// The program headers directly follow the ELF header.
+pub fn write_elf_header<O: Output>(output: &mut O, program_headers: u16) -> Result<(), O::Error> {
+    let mut header = [0u8; ELF_HEADER_SIZE];
+
+    // identification: magic, 32 bit, little endian, version 1
+    header[0..7].copy_from_slice(&[0x7f, b'E', b'L', b'F', 1, 1, 1]);
+    header[16..18].copy_from_slice(&ET_CORE.to_le_bytes());
+    header[18..20].copy_from_slice(&EM_XTENSA.to_le_bytes());
+    // version
+    header[20..24].copy_from_slice(&1u32.to_le_bytes());
+    // entry (24..28) is 0, program header offset
+    header[28..32].copy_from_slice(&(ELF_HEADER_SIZE as u32).to_le_bytes());
+    // section header offset (32..36) and flags (36..40) are 0, header size
+    header[40..42].copy_from_slice(&(ELF_HEADER_SIZE as u16).to_le_bytes());
+    header[42..44].copy_from_slice(&(PROGRAM_HEADER_SIZE as u16).to_le_bytes());
+    header[44..46].copy_from_slice(&program_headers.to_le_bytes());
+    // section header entry size, count and string table index (46..52) are 0
+
+    output.write(&header)
+}
+
+/// Write a program header
+pub fn write_program_header<O: Output>(
+    output: &mut O,
+    header: &ProgramHeader,
+) -> Result<(), O::Error> {
+    let mut data = [0u8; PROGRAM_HEADER_SIZE];
+
+    data[0..4].copy_from_slice(&header.segment_type.to_le_bytes());
+    data[4..8].copy_from_slice(&header.offset.to_le_bytes());
+    // virtual and physical address
+    data[8..12].copy_from_slice(&header.address.to_le_bytes());
+    data[12..16].copy_from_slice(&header.address.to_le_bytes());
+    // size in file and in memory
+    data[16..20].copy_from_slice(&header.size.to_le_bytes());
+    data[20..24].copy_from_slice(&header.size.to_le_bytes());
+    data[24..28].copy_from_slice(&header.flags.to_le_bytes());
+    // alignment
+    data[28..32].copy_from_slice(&4u32.to_le_bytes());
+
+    output.write(&data)
+}
+
+/// Size of a note, including the padding of the name and description
+pub fn note_size(name: &str, description_size: usize) -> usize {
+    12 + align4(name.len() + 1) + align4(description_size)
+}
+
+/// Write a note
+pub fn write_note<O: Output>(
+    output: &mut O,
+    name: &str,
+    note_type: u32,
+    description: &[u8],
+) -> Result<(), O::Error> {
+    let name_size = name.len() + 1;
+
+    output.write(&(name_size as u32).to_le_bytes())?;
+    output.write(&(description.len() as u32).to_le_bytes())?;
+    output.write(&note_type.to_le_bytes())?;
+
+    // name including terminator, padded
+    output.write(name.as_bytes())?;
+    output.write(&PADDING[..align4(name_size) - name.len()])?;
+
+    output.write(description)?;
+    output.write(&PADDING[..align4(description.len()) - description.len()])
+}
+
+/// Description of a `NT_PRSTATUS` note
+///
+/// `thread_id` identifies the thread (core) in the debugger and the `EXTRA_INFO` note.
+pub fn prstatus(registers: &Registers, thread_id: u32) -> [u8; PRSTATUS_SIZE] {
+    let mut data = [0u8; PRSTATUS_SIZE];
+
+    // only the thread id is used from the process status: pr_pid at offset 24
+    data[24..28].copy_from_slice(&thread_id.to_le_bytes());
+
+    let special = [
+        registers.pc,
+        registers.ps,
+        registers.lbeg,
+        registers.lend,
+        registers.lcount,
+        registers.sar,
+        // window start: only the current window is live
+        1,
+        // window base
+        0,
+    ];
+
+    let mut offset = PRSTATUS_HEADER_SIZE;
+    for value in special.iter() {
+        data[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+        offset += 4;
+    }
+
+    // reserved words stay 0, followed by the 64 address registers, of which only the current
+    // window (the first 16) is used
+    offset += GREGSET_RESERVED_WORDS * 4;
+    for value in registers.a.iter() {
+        data[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+        offset += 4;
+    }
+
+    data
+}
+
+/// Description of the `ESP_CORE_DUMP_INFO` note
+///
+/// `app_sha256` is the hex string of the SHA256 of the application ELF file, it is truncated
+/// to fit.
+pub fn info(version: u32, app_sha256: &[u8]) -> [u8; INFO_SIZE] {
+    let mut data = [0u8; INFO_SIZE];
+
+    data[0..4].copy_from_slice(&version.to_le_bytes());
+    // keep the terminator
+    let length = app_sha256.len().min(APP_SHA256_SIZE - 1);
+    data[4..4 + length].copy_from_slice(&app_sha256[..length]);
+
+    data
+}
+
+/// Description of the `EXTRA_INFO` note
+pub fn extra_info(
+    crashed_thread_id: u32,
+    exccause: u32,
+    excvaddr: u32,
+    epc1: u32,
+) -> [u8; EXTRA_INFO_SIZE] {
+    let mut data = [0u8; EXTRA_INFO_SIZE];
+
+    data[0..4].copy_from_slice(&crashed_thread_id.to_le_bytes());
+
+    let registers = [
+        (EXCCAUSE_REGISTER, exccause),
+        (EXCVADDR_REGISTER, excvaddr),
+        (EPC1_REGISTER, epc1),
+    ];
+    for (index, (register, value)) in registers.iter().enumerate() {
+        let offset = 4 + index * 8;
+        data[offset..offset + 4].copy_from_slice(&register.to_le_bytes());
+        data[offset + 4..offset + 8].copy_from_slice(&value.to_le_bytes());
+    }
+
+    data
+}
@@ -0,0 +1,709 @@
+//! Core dumps to flash
+//!
+//! Writes an ELF core file to the `coredump` data partition (type `data`, subtype `coredump`) in
+//! the format of ESP-IDF (ELF with CRC32), so it can be analyzed with `espcoredump.py`. The dump
+//! contains:
+//! - the registers of the crashed core
+//! - the stacks of both cores
+//! - the memory regions selected by the application
+//!
+//! The partition has to be added to the partition table, e.g.:
+//! ```text
+//! coredump, data, coredump, 0x3f0000, 0x10000,
+//! ```
+//!
+//! The registers of the other core are not captured: it is stalled at an arbitrary point, possibly
+//! with interrupts disabled, so it cannot be asked to save them. Its thread is reported with all
+//! registers 0 (so debuggers show no backtrace for it) and its complete stack is included.
+//!
+//! The length of the dump is written last, so an interrupted write (e.g. a reset or power loss
+//! while writing) leaves an empty partition instead of a partial dump. At the next boot [status]
+//! reports the stored dump, which can be read via [read] or directly from flash:
+//! ```text
+//! espcoredump.py info_corefile --core <dump file> --core-format raw <elf file>
+//! ```
+//!
+//! The dump does not contain the SHA256 of the application, so `espcoredump.py` cannot check
+//! that the dump belongs to the given ELF file.
+//!
+//! # Example
+//! ```
+//! // the first 4kB of data RAM
+//! static REGIONS: [MemoryRegion; 1] = [MemoryRegion::new(0x3ffb_0000, 0x1000)];
+//!
+//! match coredump::status() {
+//!     Ok(Status::Valid { length }) => dprintln!("Core dump of {} bytes stored", length),
+//!     Ok(Status::Corrupted) => dprintln!("Core dump is corrupted"),
+//!     _ => {}
+//! }
+//! coredump::write_on_panic(Some(&REGIONS));
+//! ```
+//!
+//! *Note: the flash cache is disabled while accessing the flash, during which interrupts are
+//! disabled and the other core is stalled.*
+
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use crate::clock_control::cpu;
+use crate::crashlog::buffer::crc32_update;
+use crate::ram;
+use crate::registers::{current_frame, ExceptionRegisters};
+use crate::Core;
+
+pub mod elf;
+
+use elf::{Output, ProgramHeader, Registers};
+
+/// Version of the ESP-IDF core dump format: ELF with CRC32 for the ESP32
+const VERSION: u32 = 0x0100;
+/// Size of the header preceding the ELF file in flash
+const HEADER_SIZE: u32 = 20;
+/// Size of the CRC32 following the ELF file in flash
+const CHECKSUM_SIZE: u32 = 4;
+/// Value of the length when the partition is empty (erased flash)
+const EMPTY: u32 = 0xffff_ffff;
+
+const PARTITION_TABLE_ADDRESS: u32 = 0x8000;
+const PARTITION_TABLE_ENTRIES: u32 = 95;
+const PARTITION_ENTRY_SIZE: usize = 32;
+const PARTITION_MAGIC: u16 = 0x50aa;
+const PARTITION_TYPE_DATA: u8 = 1;
+const PARTITION_SUBTYPE_COREDUMP: u8 = 3;
+
+const SECTOR_SIZE: u32 = 4096;
+
+/// Size of the RAM buffer for flash accesses in words
+const BUFFER_WORDS: usize = 64;
+const BUFFER_SIZE: usize = BUFFER_WORDS * 4;
+
+// ROM functions, see esp32.rom.ld of ESP-IDF
+const ROM_SPI_READ: usize = 0x4006_2ed8;
+const ROM_SPI_WRITE: usize = 0x4006_2d50;
+const ROM_SPI_ERASE_SECTOR: usize = 0x4006_2ccc;
+const ROM_SPI_UNLOCK: usize = 0x4006_28b0;
+const ROM_CACHE_FLUSH: usize = 0x4000_9a14;
+const ROM_CACHE_READ_ENABLE: usize = 0x4000_9a84;
+const ROM_CACHE_READ_DISABLE: usize = 0x4000_9ab8;
+
+/// Core dump errors
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum Error {
+    /// No coredump partition in the partition table
+    NoPartition,
+    /// The dump does not fit in the partition
+    TooLarge,
+    /// Read, write or erase of the flash failed
+    Flash,
+    /// A dump is already being written
+    Busy,
+    /// Offset or length outside of the stored dump
+    OutOfRange,
+}
+
+/// State of the core dump partition
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum Status {
+    /// No dump stored
+    Empty,
+    /// Valid dump of `length` bytes (including the header and CRC)
+    Valid { length: u32 },
+    /// The stored dump has an invalid header or CRC
+    Corrupted,
+}
+
+/// Memory region to include in the dump
+///
+/// The region is extended to word boundaries.
+#[derive(Copy, Clone, Debug)]
+pub struct MemoryRegion {
+    pub start: u32,
+    pub length: u32,
+}
+
+impl MemoryRegion {
+    /// Create a new memory region
+    pub const fn new(start: u32, length: u32) -> Self {
+        MemoryRegion { start, length }
+    }
+
+    /// Memory region of a value (e.g. a static)
+    pub fn of<T>(value: &T) -> Self {
+        MemoryRegion {
+            start: value as *const T as u32,
+            length: core::mem::size_of::<T>() as u32,
+        }
+    }
+
+    fn aligned(&self) -> MemoryRegion {
+        let start = self.start & !3;
+        let end = (self.start + self.length + 3) & !3;
+        MemoryRegion {
+            start,
+            length: end - start,
+        }
+    }
+}
+
+/// Location of the coredump partition in flash
+#[derive(Copy, Clone, Debug)]
+struct Partition {
+    offset: u32,
+    size: u32,
+}
+
+/// Registers of the crashed core and its exception registers
+struct CrashContext {
+    registers: Registers,
+    exception: ExceptionRegisters,
+}
+
+static WRITING: AtomicBool = AtomicBool::new(false);
+
+static mut PANIC_REGIONS: Option<&'static [MemoryRegion]> = None;
+static PANIC_ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Buffer in internal RAM, used while the flash cache is disabled
+static mut BUFFER: [u32; BUFFER_WORDS] = [0; BUFFER_WORDS];
+
+extern "C" {
+    static _stack_start_cpu0: u32;
+    static _stack_end_cpu0: u32;
+    static _stack_start_cpu1: u32;
+    static _stack_end_cpu1: u32;
+}
+
+/// Stack range of a core
+fn stack_range(core: Core) -> (u32, u32) {
+    unsafe {
+        match core {
+            Core::PRO => (
+                &_stack_start_cpu0 as *const u32 as u32,
+                &_stack_end_cpu0 as *const u32 as u32,
+            ),
+            Core::APP => (
+                &_stack_start_cpu1 as *const u32 as u32,
+                &_stack_end_cpu1 as *const u32 as u32,
+            ),
+        }
+    }
+}
+
+/// Used part of the stack of a core: from the base save area below the stack pointer to the end
+/// of the stack, or the whole stack if the stack pointer is outside of it
+fn stack_region(core: Core, sp: u32) -> MemoryRegion {
+    let (start, end) = stack_range(core);
+    let used_start = (sp & !3).wrapping_sub(16);
+
+    if used_start >= start && used_start < end {
+        MemoryRegion::new(used_start, end - used_start)
+    } else {
+        MemoryRegion::new(start, end - start)
+    }
+}
+
+/// Thread id of a core in the dump
+fn thread_id(core: Core) -> u32 {
+    core as u32 + 1
+}
+
+/// Registers at the call site, with the register windows spilled to the stack
+#[inline(never)]
+fn current_registers() -> Registers {
+    crate::backtrace::spill_windows();
+
+    let frame = current_frame();
+    let mut registers = Registers {
+        pc: frame.pc,
+        ps: frame.ps,
+        lbeg: frame.lbeg,
+        lend: frame.lend,
+        lcount: frame.lcount,
+        sar: frame.sar,
+        a: [0; 16],
+    };
+    registers.a[0] = frame.return_address;
+    registers.a[1] = frame.sp;
+    registers
+}
+
+/// Call a flash function of the ROM with the flash cache disabled
+///
+/// Must be in RAM, as code in flash cannot be executed while the cache is disabled, so
+/// interrupts are disabled and the other core is stalled directly in this function. Returns
+/// true if the ROM function succeeded.
+#[ram]
+fn rom_flash_operation(function: usize, address: u32, data: *mut u32, length: u32) -> bool {
+    let other_core = crate::get_other_core();
+    let parked = cpu::is_core_parked(other_core);
+
+    unsafe { cpu::park_core(other_core) };
+    let mask = xtensa_lx::interrupt::disable();
+
+    let result = unsafe {
+        let cache_read_disable: unsafe extern "C" fn(u32) =
+            core::mem::transmute(ROM_CACHE_READ_DISABLE);
+        let cache_read_enable: unsafe extern "C" fn(u32) =
+            core::mem::transmute(ROM_CACHE_READ_ENABLE);
+        let cache_flush: unsafe extern "C" fn(u32) = core::mem::transmute(ROM_CACHE_FLUSH);
+
+        cache_read_disable(0);
+        cache_read_disable(1);
+
+        let result = if function == ROM_SPI_ERASE_SECTOR {
+            let unlock: unsafe extern "C" fn() -> i32 = core::mem::transmute(ROM_SPI_UNLOCK);
+            let erase: unsafe extern "C" fn(u32) -> i32 = core::mem::transmute(function);
+            unlock() == 0 && erase(address / SECTOR_SIZE) == 0
+        } else if function == ROM_SPI_WRITE {
+            let unlock: unsafe extern "C" fn() -> i32 = core::mem::transmute(ROM_SPI_UNLOCK);
+            let write: unsafe extern "C" fn(u32, *const u32, u32) -> i32 =
+                core::mem::transmute(function);
+            unlock() == 0 && write(address, data, length) == 0
+        } else {
+            let read: unsafe extern "C" fn(u32, *mut u32, u32) -> i32 =
+                core::mem::transmute(function);
+            read(address, data, length) == 0
+        };
+
+        cache_flush(0);
+        cache_flush(1);
+        cache_read_enable(0);
+        cache_read_enable(1);
+
+        result
+    };
+
+    unsafe { xtensa_lx::interrupt::set_mask(mask) };
+    if !parked {
+        unsafe { cpu::unpark_core(other_core) };
+    }
+
+    result
+}
+
+/// Read from flash into the RAM buffer
+fn flash_read(address: u32, length: usize) -> Result<&'static [u8], Error> {
+    unsafe {
+        if !rom_flash_operation(ROM_SPI_READ, address, BUFFER.as_mut_ptr(), length as u32) {
+            return Err(Error::Flash);
+        }
+        Ok(core::slice::from_raw_parts(
+            BUFFER.as_ptr() as *const u8,
+            length,
+        ))
+    }
+}
+
+/// Write the first `length` bytes (a multiple of 4) of the RAM buffer to flash
+fn flash_write(address: u32, length: usize) -> Result<(), Error> {
+    unsafe {
+        if rom_flash_operation(ROM_SPI_WRITE, address, BUFFER.as_mut_ptr(), length as u32) {
+            Ok(())
+        } else {
+            Err(Error::Flash)
+        }
+    }
+}
+
+fn flash_erase(address: u32, length: u32) -> Result<(), Error> {
+    let mut sector = address;
+    while sector < address + length {
+        if !rom_flash_operation(ROM_SPI_ERASE_SECTOR, sector, core::ptr::null_mut(), 0) {
+            return Err(Error::Flash);
+        }
+        sector += SECTOR_SIZE;
+    }
+    Ok(())
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    let mut bytes = [0u8; 4];
+    bytes.copy_from_slice(&data[offset..offset + 4]);
+    u32::from_le_bytes(bytes)
+}
+
+/// Find the coredump partition in the partition table
+fn find_partition() -> Result<Partition, Error> {
+    for index in 0..PARTITION_TABLE_ENTRIES {
+        let entry = flash_read(
+            PARTITION_TABLE_ADDRESS + index * PARTITION_ENTRY_SIZE as u32,
+            PARTITION_ENTRY_SIZE,
+        )?;
+
+        // the table ends with an erased entry or the MD5 entry
+        if u16::from_le_bytes([entry[0], entry[1]]) != PARTITION_MAGIC {
+            break;
+        }
+
+        if entry[2] == PARTITION_TYPE_DATA && entry[3] == PARTITION_SUBTYPE_COREDUMP {
+            return Ok(Partition {
+                offset: read_u32(entry, 4),
+                size: read_u32(entry, 8),
+            });
+        }
+    }
+
+    Err(Error::NoPartition)
+}
+
+/// Writes the dump to flash via the RAM buffer, calculating the CRC
+///
+/// The first word (the length of the dump) is written as erased flash, it is written by
+/// [FlashOutput::finish] after all other data.
+struct FlashOutput {
+    start: u32,
+    address: u32,
+    end: u32,
+    buffered: usize,
+    crc: u32,
+}
+
+impl FlashOutput {
+    fn new(partition: Partition, length: u32) -> Self {
+        FlashOutput {
+            start: partition.offset,
+            address: partition.offset,
+            end: partition.offset + length,
+            buffered: 0,
+            crc: 0,
+        }
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        if self.buffered == 0 {
+            return Ok(());
+        }
+
+        if self.address == self.start {
+            unsafe { BUFFER[0] = EMPTY };
+        }
+        flash_write(self.address, self.buffered)?;
+
+        self.address += self.buffered as u32;
+        self.buffered = 0;
+        Ok(())
+    }
+
+    fn write_unchecked(&mut self, data: &[u8]) -> Result<(), Error> {
+        if self.address + (self.buffered + data.len()) as u32 > self.end {
+            return Err(Error::TooLarge);
+        }
+
+        let buffer = unsafe { &mut *(&mut BUFFER as *mut _ as *mut [u8; BUFFER_SIZE]) };
+        for byte in data {
+            buffer[self.buffered] = *byte;
+            self.buffered += 1;
+            if self.buffered == BUFFER_SIZE {
+                self.flush()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Write the contents of a memory region, using word accesses (e.g. for IRAM)
+    fn write_memory(&mut self, region: &MemoryRegion) -> Result<(), Error> {
+        let region = region.aligned();
+        for address in (region.start..region.start + region.length).step_by(4) {
+            let word = unsafe { core::ptr::read_volatile(address as *const u32) };
+            self.write(&word.to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Write the CRC and the buffered data, then the length to mark the dump as valid
+    fn finish(mut self) -> Result<(), Error> {
+        let crc = self.crc;
+        self.write_unchecked(&crc.to_le_bytes())?;
+        self.flush()?;
+
+        let length = self.end - self.start;
+        unsafe { BUFFER[0] = length };
+        flash_write(self.start, 4)
+    }
+}
+
+impl Output for FlashOutput {
+    type Error = Error;
+
+    fn write(&mut self, data: &[u8]) -> Result<(), Error> {
+        self.crc = crc32_update(self.crc, data);
+        self.write_unchecked(data)
+    }
+}
+
+/// Write the core dump of the crashed core and the other core
+fn write_dump(
+    crashed_core: Core,
+    context: &CrashContext,
+    regions: &[MemoryRegion],
+) -> Result<(), Error> {
+    if WRITING.swap(true, Ordering::SeqCst) {
+        return Err(Error::Busy);
+    }
+
+    let result = write_dump_locked(crashed_core, context, regions);
+    WRITING.store(false, Ordering::SeqCst);
+    result
+}
+
+fn write_dump_locked(
+    crashed_core: Core,
+    context: &CrashContext,
+    regions: &[MemoryRegion],
+) -> Result<(), Error> {
+    let partition = find_partition()?;
+
+    let other_core = match crashed_core {
+        Core::PRO => Core::APP,
+        Core::APP => Core::PRO,
+    };
+    let other_registers = Registers::default();
+
+    let stacks = [
+        stack_region(crashed_core, context.registers.a[1]),
+        stack_region(other_core, 0),
+    ];
+
+    // layout: notes, then the stacks, then the memory regions
+    let notes_size = 2 * elf::note_size(elf::PRSTATUS_NAME, elf::PRSTATUS_SIZE)
+        + elf::note_size(elf::INFO_NAME, elf::INFO_SIZE)
+        + elf::note_size(elf::EXTRA_INFO_NAME, elf::EXTRA_INFO_SIZE);
+    let segments = 1 + stacks.len() + regions.len();
+    let headers_size = elf::ELF_HEADER_SIZE + segments * elf::PROGRAM_HEADER_SIZE;
+    let memory_size: u32 = stacks
+        .iter()
+        .chain(regions.iter())
+        .map(|region| region.aligned().length)
+        .sum();
+    let elf_size = (headers_size + notes_size) as u32 + memory_size;
+    let length = HEADER_SIZE + elf_size + CHECKSUM_SIZE;
+
+    if length > partition.size {
+        return Err(Error::TooLarge);
+    }
+
+    flash_erase(partition.offset, length)?;
+
+    let mut output = FlashOutput::new(partition, length);
+
+    // ESP-IDF header: length, version, number of tasks, TCB size, number of memory regions
+    for word in [length, VERSION, 2, 0, regions.len() as u32].iter() {
+        output.write(&word.to_le_bytes())?;
+    }
+
+    elf::write_elf_header(&mut output, segments as u16)?;
+
+    let mut offset = headers_size as u32;
+    elf::write_program_header(
+        &mut output,
+        &ProgramHeader {
+            segment_type: elf::PT_NOTE,
+            offset,
+            address: 0,
+            size: notes_size as u32,
+            flags: 0,
+        },
+    )?;
+    offset += notes_size as u32;
+
+    for region in stacks.iter().chain(regions.iter()) {
+        let region = region.aligned();
+        elf::write_program_header(
+            &mut output,
+            &ProgramHeader {
+                segment_type: elf::PT_LOAD,
+                offset,
+                address: region.start,
+                size: region.length,
+                flags: elf::PF_R | elf::PF_W,
+            },
+        )?;
+        offset += region.length;
+    }
+
+    elf::write_note(
+        &mut output,
+        elf::PRSTATUS_NAME,
+        elf::NT_PRSTATUS,
+        &elf::prstatus(&context.registers, thread_id(crashed_core)),
+    )?;
+    elf::write_note(
+        &mut output,
+        elf::PRSTATUS_NAME,
+        elf::NT_PRSTATUS,
+        &elf::prstatus(&other_registers, thread_id(other_core)),
+    )?;
+    elf::write_note(
+        &mut output,
+        elf::INFO_NAME,
+        elf::ESP_CORE_DUMP_INFO_TYPE,
+        &elf::info(VERSION, &[]),
+    )?;
+    elf::write_note(
+        &mut output,
+        elf::EXTRA_INFO_NAME,
+        elf::ESP_CORE_DUMP_EXTRA_INFO_TYPE,
+        &elf::extra_info(
+            thread_id(crashed_core),
+            context.exception.exccause,
+            context.exception.excvaddr,
+            context.exception.epc1,
+        ),
+    )?;
+
+    for region in stacks.iter().chain(regions.iter()) {
+        output.write_memory(region)?;
+    }
+
+    output.finish()
+}
+
+/// Write a core dump of the current state
+///
+/// Stops the other core and writes the registers of the current core, the stacks of both cores
+/// and the given memory regions to the coredump partition. The registers of the current core are
+/// those at the call site, the registers of the other core are reported as 0.
+///
+/// After the dump the other core stays stopped, so this is intended for fatal errors, after which
+/// the chip is reset.
+pub fn write(regions: &[MemoryRegion]) -> Result<(), Error> {
+    xtensa_lx::interrupt::disable();
+    unsafe { cpu::park_core(crate::get_other_core()) };
+
+    let context = CrashContext {
+        registers: current_registers(),
+        exception: ExceptionRegisters::read(),
+    };
+    write_dump(crate::get_core(), &context, regions)
+}
+
+/// Write a core dump from an exception handler
+///
+/// Like [write], but the registers of the current core are taken from the exception context.
+#[cfg(feature = "rt")]
+pub fn write_from_exception(
+    context: &xtensa_lx_rt::exception::Context,
+    regions: &[MemoryRegion],
+) -> Result<(), Error> {
+    xtensa_lx::interrupt::disable();
+    unsafe { cpu::park_core(crate::get_other_core()) };
+    crate::backtrace::spill_windows();
+
+    let crash_context = CrashContext {
+        registers: Registers {
+            pc: context.PC,
+            ps: context.PS,
+            lbeg: context.LBEG,
+            lend: context.LEND,
+            lcount: context.LCOUNT,
+            sar: context.SAR,
+            a: [
+                context.A0,
+                context.A1,
+                context.A2,
+                context.A3,
+                context.A4,
+                context.A5,
+                context.A6,
+                context.A7,
+                context.A8,
+                context.A9,
+                context.A10,
+                context.A11,
+                context.A12,
+                context.A13,
+                context.A14,
+                context.A15,
+            ],
+        },
+        exception: ExceptionRegisters {
+            exccause: context.EXCCAUSE,
+            epc1: context.PC,
+            excvaddr: context.EXCVADDR,
+        },
+    };
+    write_dump(crate::get_core(), &crash_context, regions)
+}
+
+/// Select whether the [panic handler](crate::panic_handler) writes a core dump and which memory
+/// regions it includes
+pub fn write_on_panic(regions: Option<&'static [MemoryRegion]>) {
+    xtensa_lx::interrupt::free(|_| unsafe {
+        PANIC_REGIONS = regions;
+        PANIC_ENABLED.store(regions.is_some(), Ordering::SeqCst);
+    });
+}
+
+/// Memory regions to include in a core dump written by the panic handler, None if disabled
+#[cfg(feature = "panic_handler")]
+pub(crate) fn panic_regions() -> Option<&'static [MemoryRegion]> {
+    if PANIC_ENABLED.load(Ordering::SeqCst) {
+        unsafe { PANIC_REGIONS }
+    } else {
+        None
+    }
+}
+
+/// Get the state of the coredump partition, e.g. at boot to report a dump of the previous session
+pub fn status() -> Result<Status, Error> {
+    let partition = find_partition()?;
+
+    let header = flash_read(partition.offset, HEADER_SIZE as usize)?;
+    let length = read_u32(header, 0);
+    let version = read_u32(header, 4);
+
+    if length == EMPTY {
+        return Ok(Status::Empty);
+    }
+    if version != VERSION
+        || length % 4 != 0
+        || length < HEADER_SIZE + CHECKSUM_SIZE
+        || length > partition.size
+    {
+        return Ok(Status::Corrupted);
+    }
+
+    let data_end = partition.offset + length - CHECKSUM_SIZE;
+    let mut crc = 0;
+    let mut address = partition.offset;
+    while address < data_end {
+        let chunk = ((data_end - address) as usize).min(BUFFER_SIZE);
+        crc = crc32_update(crc, flash_read(address, chunk)?);
+        address += chunk as u32;
+    }
+
+    if crc == read_u32(flash_read(data_end, 4)?, 0) {
+        Ok(Status::Valid { length })
+    } else {
+        Ok(Status::Corrupted)
+    }
+}
+
+/// Read part of the stored dump, starting at `offset` bytes from the start of the partition
+///
+/// The dump (including the header) can be saved to a file for `espcoredump.py`.
+pub fn read(offset: u32, buffer: &mut [u8]) -> Result<(), Error> {
+    let partition = find_partition()?;
+    if offset as usize + buffer.len() > partition.size as usize {
+        return Err(Error::OutOfRange);
+    }
+
+    // flash reads are word aligned
+    let mut address = partition.offset + offset;
+    let mut position = 0;
+    while position < buffer.len() {
+        let skip = (address % 4) as usize;
+        let chunk = (buffer.len() - position).min(BUFFER_SIZE - skip);
+        let data = flash_read(address - skip as u32, (skip + chunk + 3) & !3)?;
+        buffer[position..position + chunk].copy_from_slice(&data[skip..skip + chunk]);
+
+        position += chunk;
+        address += chunk as u32;
+    }
+    Ok(())
+}
+
+/// Erase the stored dump
+pub fn erase() -> Result<(), Error> {
+    let partition = find_partition()?;
+    flash_erase(partition.offset, SECTOR_SIZE)
+}
@@ -0,0 +1,278 @@
+//! CRC protected crash log buffer
+//!
+//! The buffer is a plain `#[repr(C)]` structure, so it can be placed in memory that is not
+//! initialized at boot. Its validity is checked via a magic value and a CRC over the contents.
+//!
+//! *Note: this module does not depend on the hardware, so it is also compiled and tested on the
+//! host (see the `host-tests` directory).*
+
+use core::fmt;
+
+use crate::ring_buffer::RingBuffer;
+
+/// Size of the log ring buffer in bytes
+pub const LOG_SIZE: usize = 1024;
+/// Maximum length of the stored panic message in bytes
+pub const PANIC_MESSAGE_SIZE: usize = 256;
+/// Maximum length of a formatted record in bytes, longer records are truncated
+pub const RECORD_SIZE: usize = 256;
+
+const MAGIC: u32 = 0x4352_4c47;
+
+/// CRC-32 (IEEE 802.3) lookup table
+const CRC_TABLE: [u32; 256] = crc_table();
+
+const fn crc_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut index = 0;
+    while index < 256 {
+        let mut crc = index as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[index] = crc;
+        index += 1;
+    }
+    table
+}
+
+/// Continue a CRC-32 calculation
+///
+/// Start with a `crc` of 0; the result of one call can be passed to the next to calculate the
+/// CRC over multiple slices.
+pub fn crc32_update(crc: u32, data: &[u8]) -> u32 {
+    !data.iter().fold(!crc, |crc, byte| {
+        CRC_TABLE[((crc ^ *byte as u32) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
+
+/// Calculate the CRC-32 (IEEE 802.3) of the data
+pub fn crc32(data: &[u8]) -> u32 {
+    crc32_update(0, data)
+}
+
+/// Crash log contents
+///
+/// Log data is kept in a ring buffer: when it is full, the oldest lines are removed, so the log
+/// always starts at the beginning of a line.
+#[repr(C)]
+#[derive(Clone)]
+pub struct CrashLog {
+    magic: u32,
+    crc: u32,
+    boot_count: u32,
+    panic_length: u32,
+    log: RingBuffer<[u8; LOG_SIZE]>,
+    panic_message: [u8; PANIC_MESSAGE_SIZE],
+}
+
+impl CrashLog {
+    /// Create a new, empty crash log
+    pub fn new() -> Self {
+        let mut log = CrashLog {
+            magic: MAGIC,
+            crc: 0,
+            boot_count: 0,
+            panic_length: 0,
+            log: RingBuffer::new([0; LOG_SIZE]),
+            panic_message: [0; PANIC_MESSAGE_SIZE],
+        };
+        log.update_crc();
+        log
+    }
+
+    /// Invalid (all zero) crash log, for initializing statics in uninitialized memory
+    pub(crate) const fn zeroed() -> Self {
+        CrashLog {
+            magic: 0,
+            crc: 0,
+            boot_count: 0,
+            panic_length: 0,
+            log: RingBuffer::new([0; LOG_SIZE]),
+            panic_message: [0; PANIC_MESSAGE_SIZE],
+        }
+    }
+
+    /// Check the magic value, the CRC and the internal consistency
+    ///
+    /// Memory which is not initialized at boot is very unlikely to pass this check.
+    pub fn is_valid(&self) -> bool {
+        self.magic == MAGIC
+            && self.log.is_consistent()
+            && (self.panic_length as usize) <= PANIC_MESSAGE_SIZE
+            && self.crc == self.calculate_crc()
+    }
+
+    /// Clear the log and the panic message
+    ///
+    /// The boot count is kept.
+    pub fn clear(&mut self) {
+        self.magic = MAGIC;
+        self.log.clear();
+        self.panic_length = 0;
+        self.update_crc();
+    }
+
+    /// Number of boots (sessions) this log has been carried over
+    pub fn boot_count(&self) -> u32 {
+        self.boot_count
+    }
+
+    /// Set the boot count
+    pub fn set_boot_count(&mut self, boot_count: u32) {
+        self.boot_count = boot_count;
+        self.update_crc();
+    }
+
+    /// Number of bytes in the log
+    pub fn len(&self) -> usize {
+        self.log.len()
+    }
+
+    /// Returns true if the log is empty
+    pub fn is_empty(&self) -> bool {
+        self.log.is_empty()
+    }
+
+    /// Append a record to the log, removing the oldest lines if needed
+    ///
+    /// Of a record larger than the buffer only the last part is kept.
+    pub fn write(&mut self, data: &[u8]) {
+        let data = &data[data.len().saturating_sub(LOG_SIZE)..];
+
+        while LOG_SIZE - self.log.len() < data.len() {
+            while let Some(byte) = self.log.pop() {
+                if byte == b'\n' {
+                    break;
+                }
+            }
+        }
+        for byte in data {
+            self.log.push(*byte);
+        }
+
+        self.update_crc();
+    }
+
+    /// Log contents, oldest data first, as two slices
+    ///
+    /// The second slice is only non-empty when the ring buffer has wrapped.
+    pub fn as_slices(&self) -> (&[u8], &[u8]) {
+        self.log.as_slices()
+    }
+
+    /// Copy the log contents (oldest data first) from `offset` into `data`
+    ///
+    /// Returns the number of bytes copied.
+    pub fn read(&self, offset: usize, data: &mut [u8]) -> usize {
+        let (first, second) = self.as_slices();
+        let mut count = 0;
+
+        for (source, byte) in first
+            .iter()
+            .chain(second.iter())
+            .skip(offset)
+            .zip(data.iter_mut())
+        {
+            *byte = *source;
+            count += 1;
+        }
+
+        count
+    }
+
+    /// Store the panic message, truncating it if needed
+    pub fn set_panic_message(&mut self, message: &[u8]) {
+        let length = message.len().min(PANIC_MESSAGE_SIZE);
+        self.panic_message[..length].copy_from_slice(&message[..length]);
+        self.panic_length = length as u32;
+        self.update_crc();
+    }
+
+    /// The stored panic message, if any
+    ///
+    /// A message truncated in the middle of a character is cut before that character.
+    pub fn panic_message(&self) -> Option<&str> {
+        if self.panic_length == 0 {
+            return None;
+        }
+
+        let message = &self.panic_message[..self.panic_length as usize];
+        Some(match core::str::from_utf8(message) {
+            Ok(message) => message,
+            Err(error) => core::str::from_utf8(&message[..error.valid_up_to()]).unwrap_or_default(),
+        })
+    }
+
+    fn calculate_crc(&self) -> u32 {
+        let mut crc = crc32_update(0, &self.boot_count.to_le_bytes());
+        crc = crc32_update(crc, &(self.log.len() as u32).to_le_bytes());
+        crc = crc32_update(crc, &self.panic_length.to_le_bytes());
+        let (first, second) = self.log.as_slices();
+        crc = crc32_update(crc, first);
+        crc = crc32_update(crc, second);
+        crc32_update(crc, &self.panic_message)
+    }
+
+    fn update_crc(&mut self) {
+        self.crc = self.calculate_crc();
+    }
+}
+
+impl Default for CrashLog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Every call to [write_str](fmt::Write::write_str) or [write_fmt](fmt::Write::write_fmt) is a
+/// single record
+impl fmt::Write for CrashLog {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.write(s.as_bytes());
+        Ok(())
+    }
+
+    /// Formats the record into a buffer of [RECORD_SIZE] bytes first, so the CRC is only
+    /// calculated once
+    fn write_fmt(&mut self, args: fmt::Arguments) -> fmt::Result {
+        let mut buffer = [0u8; RECORD_SIZE];
+        let mut writer = TruncatingWriter::new(&mut buffer);
+        // the truncating writer never fails
+        let _ = fmt::write(&mut writer, args);
+
+        self.write(writer.as_bytes());
+        Ok(())
+    }
+}
+
+/// Writer which truncates instead of failing when the buffer is full
+pub(crate) struct TruncatingWriter<'a> {
+    buffer: &'a mut [u8],
+    length: usize,
+}
+
+impl<'a> TruncatingWriter<'a> {
+    pub(crate) fn new(buffer: &'a mut [u8]) -> Self {
+        TruncatingWriter { buffer, length: 0 }
+    }
+
+    pub(crate) fn as_bytes(&self) -> &[u8] {
+        &self.buffer[..self.length]
+    }
+}
+
+impl fmt::Write for TruncatingWriter<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let count = s.len().min(self.buffer.len() - self.length);
+        self.buffer[self.length..self.length + count].copy_from_slice(&s.as_bytes()[..count]);
+        self.length += count;
+        Ok(())
+    }
+}
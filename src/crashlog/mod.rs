@@ -0,0 +1,183 @@
+//! Persistent crash log
+//!
+//! Keeps a ring buffer of log lines and the last panic message in RTC slow memory, which is not
+//! initialized at boot. The contents survive software resets, watchdog resets and deep sleep (as
+//! long as the RTC slow memory stays powered), but not power loss or brown-outs.
+//!
+//! At boot [init] validates the crash log of the previous session, makes it available via
+//! [previous] and starts a new, empty crash log.
+//!
+//! # Example
+//! ```
+//! if crashlog::init() {
+//!     crashlog::previous(|log| {
+//!         if let Some(message) = log.and_then(|log| log.panic_message()) {
+//!             writeln!(uart0, "Previous session panicked: {}", message).unwrap();
+//!         }
+//!     });
+//! }
+//!
+//! writeln!(CrashLogWriter, "Connecting to {}", address).unwrap();
+//! ```
+//!
+//! Each write is a record, which is formatted first and truncated to [RECORD_SIZE] bytes. When
+//! the log is full, the oldest lines are removed.
+//!
+//! *Note: every record recalculates the CRC over the complete crash log, which takes in the
+//! order of 10us.*
+
+use core::fmt::{self, Write};
+
+use crate::prelude::*;
+
+pub mod buffer;
+
+use buffer::TruncatingWriter;
+pub use buffer::{CrashLog, LOG_SIZE, PANIC_MESSAGE_SIZE, RECORD_SIZE};
+
+#[crate::ram(rtc_slow, uninitialized)]
+static mut CRASH_LOG: CrashLog = CrashLog::zeroed();
+
+static mut PREVIOUS: Option<CrashLog> = None;
+
+/// Protects CRASH_LOG and PREVIOUS, contains true once initialized
+static CRASH_LOG_MUTEX: CriticalSectionSpinLockMutex<bool> =
+    CriticalSectionSpinLockMutex::new(false);
+
+/// Initialize the crash log
+///
+/// Moves a valid crash log of the previous session to [previous] and starts a new crash log.
+/// Must be called once at boot, before the crash log is written.
+///
+/// Returns true if the previous session left a valid crash log.
+pub fn init() -> bool {
+    (&CRASH_LOG_MUTEX).lock(|initialized| unsafe {
+        if *initialized {
+            return PREVIOUS.is_some();
+        }
+        *initialized = true;
+
+        let boot_count = if CRASH_LOG.is_valid() {
+            let boot_count = CRASH_LOG.boot_count().wrapping_add(1);
+            PREVIOUS = Some(CRASH_LOG.clone());
+            boot_count
+        } else {
+            0
+        };
+
+        CRASH_LOG = CrashLog::new();
+        CRASH_LOG.set_boot_count(boot_count);
+
+        PREVIOUS.is_some()
+    })
+}
+
+/// Access the crash log of the previous session
+///
+/// `f` receives None if there was no valid crash log or [init] has not been called.
+///
+/// *Note: this is called with interrupts disabled, so `f` should be short.*
+pub fn previous<R>(f: impl FnOnce(Option<&CrashLog>) -> R) -> R {
+    (&CRASH_LOG_MUTEX).lock(|_| unsafe { f(PREVIOUS.as_ref()) })
+}
+
+/// Access the crash log of the current session
+///
+/// `f` receives None if [init] has not been called.
+///
+/// *Note: this is called with interrupts disabled, so `f` should be short.*
+pub fn current<R>(f: impl FnOnce(Option<&mut CrashLog>) -> R) -> R {
+    (&CRASH_LOG_MUTEX).lock(|initialized| unsafe {
+        if *initialized {
+            f(Some(&mut CRASH_LOG))
+        } else {
+            f(None)
+        }
+    })
+}
+
+/// Append a record to the crash log
+pub fn write(data: &[u8]) {
+    current(|log| {
+        if let Some(log) = log {
+            log.write(data);
+        }
+    });
+}
+
+/// Append formatted arguments to the crash log as a single record
+///
+/// The record is formatted before locking the crash log and truncated to [RECORD_SIZE] bytes.
+pub fn write_fmt(args: fmt::Arguments) -> fmt::Result {
+    let mut buffer = [0u8; RECORD_SIZE];
+    let mut writer = TruncatingWriter::new(&mut buffer);
+    // the truncating writer never fails
+    let _ = writer.write_fmt(args);
+
+    write(writer.as_bytes());
+    Ok(())
+}
+
+/// Store the panic message, truncated to [PANIC_MESSAGE_SIZE] bytes
+///
+/// *Note: when called while the crash log is locked on the same core (e.g. from a panic while
+/// writing the crash log) this deadlocks.*
+pub fn record_panic(message: &dyn fmt::Display) {
+    let mut buffer = [0u8; PANIC_MESSAGE_SIZE];
+    let mut writer = TruncatingWriter::new(&mut buffer);
+    // the truncating writer never fails
+    let _ = write!(writer, "{}", message);
+
+    current(|log| {
+        if let Some(log) = log {
+            log.set_panic_message(writer.as_bytes());
+        }
+    });
+}
+
+/// Store the panic message without taking the crash log lock
+///
+/// Also records the message if [init] has not been called in this session, as long as the crash
+/// log contents are valid.
+///
+/// # Safety
+/// Only to be used when the lock may never be released, e.g. when panicking after the other core
+/// has been stalled.
+pub(crate) unsafe fn record_panic_unlocked(message: &dyn fmt::Display) {
+    let mut buffer = [0u8; PANIC_MESSAGE_SIZE];
+    let mut writer = TruncatingWriter::new(&mut buffer);
+    let _ = write!(writer, "{}", message);
+
+    if CRASH_LOG.is_valid() {
+        CRASH_LOG.set_panic_message(writer.as_bytes());
+    }
+}
+
+/// Clear the crash log of the current session
+pub fn clear() {
+    current(|log| {
+        if let Some(log) = log {
+            log.clear();
+        }
+    });
+}
+
+/// Handle writing each message to the crash log atomically
+///
+/// Can be used where an owned writer is needed, e.g. for the [logger](crate::logger).
+#[derive(Copy, Clone, Debug, Default)]
+pub struct CrashLogWriter;
+
+impl Write for CrashLogWriter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        write(s.as_bytes());
+        Ok(())
+    }
+
+    fn write_fmt(&mut self, args: fmt::Arguments) -> fmt::Result {
+        write_fmt(args)
+    }
+}
+
+#[cfg(feature = "log")]
+impl crate::logger::Sink for CrashLogWriter {}
@@ -0,0 +1,217 @@
+//! DMA descriptors
+//!
+//! The DMA engines of the ESP32 peripherals (UHCI, SPI and I2S) use linked lists of descriptors.
+//! Each descriptor points to a part (of at most 4095 bytes) of a buffer and to the next
+//! descriptor.
+//!
+//! The DMA engines can only access internal data RAM, so both the descriptors and the buffers
+//! must be located there (not in flash or external RAM). Typically they are statically allocated:
+//!
+//! ```
+//! static mut DESCRIPTORS: [Descriptor; 4] = [Descriptor::new(); 4];
+//! static mut BUFFER: [u8; 8192] = [0; 8192];
+//! ```
+
+use core::ptr::{read_volatile, write_volatile};
+
+/// Maximum number of bytes per descriptor (word aligned)
+pub const MAX_DESCRIPTOR_LENGTH: usize = 4092;
+
+/// Start of the DMA capable internal data RAM
+const DMA_RAM_START: usize = 0x3FFA_E000;
+/// End of the DMA capable internal data RAM
+const DMA_RAM_END: usize = 0x4000_0000;
+
+const SIZE_SHIFT: u32 = 0;
+const LENGTH_SHIFT: u32 = 12;
+const FIELD_MASK: u32 = 0xfff;
+const EOF_BIT: u32 = 1 << 30;
+const OWNER_DMA_BIT: u32 = 1 << 31;
+
+/// DMA errors
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum Error {
+    /// Buffer or descriptors are not located in internal data RAM
+    NotDmaCapable,
+    /// Receive buffer is not word aligned or its length is not a multiple of 4
+    Alignment,
+    /// Buffer is empty
+    EmptyBuffer,
+    /// Not enough descriptors for the buffer
+    TooFewDescriptors,
+    /// Received data does not fit in the buffer
+    BufferTooSmall,
+    /// Invalid descriptor encountered by the DMA engine
+    DescriptorError,
+}
+
+/// DMA linked list descriptor
+#[repr(C, align(4))]
+#[derive(Copy, Clone, Debug)]
+pub struct Descriptor {
+    /// size (bits 0-11), length (bits 12-23), end of frame (bit 30) and owner (bit 31)
+    flags: u32,
+    buffer: u32,
+    next: u32,
+}
+
+impl Descriptor {
+    /// Create a new (unlinked) descriptor
+    pub const fn new() -> Self {
+        Descriptor {
+            flags: 0,
+            buffer: 0,
+            next: 0,
+        }
+    }
+
+    fn flags(&self) -> u32 {
+        unsafe { read_volatile(&self.flags) }
+    }
+
+    fn set_flags(&mut self, flags: u32) {
+        unsafe { write_volatile(&mut self.flags, flags) }
+    }
+
+    /// Number of bytes in the buffer of the descriptor
+    pub(crate) fn size(&self) -> usize {
+        ((self.flags() >> SIZE_SHIFT) & FIELD_MASK) as usize
+    }
+
+    /// Number of valid bytes (to be sent or received)
+    pub(crate) fn length(&self) -> usize {
+        ((self.flags() >> LENGTH_SHIFT) & FIELD_MASK) as usize
+    }
+
+    /// Returns true if this is the last descriptor of a frame
+    pub(crate) fn is_eof(&self) -> bool {
+        self.flags() & EOF_BIT != 0
+    }
+
+    /// Returns true if the descriptor is still owned by the DMA engine
+    pub(crate) fn is_owned_by_dma(&self) -> bool {
+        self.flags() & OWNER_DMA_BIT != 0
+    }
+
+    /// Hand an (emptied) receive descriptor back to the DMA engine
+    pub(crate) fn release_to_dma(&mut self) {
+        let size = self.size() as u32;
+        self.set_flags(OWNER_DMA_BIT | (size << SIZE_SHIFT));
+    }
+
+    /// Address of the descriptor as used by the DMA engine
+    pub(crate) fn address(&self) -> u32 {
+        self as *const _ as u32
+    }
+
+    fn set(&mut self, buffer: u32, size: usize, length: usize, eof: bool, next: u32) {
+        unsafe {
+            write_volatile(&mut self.buffer, buffer);
+            write_volatile(&mut self.next, next);
+        }
+        self.set_flags(
+            OWNER_DMA_BIT
+                | if eof { EOF_BIT } else { 0 }
+                | ((length as u32 & FIELD_MASK) << LENGTH_SHIFT)
+                | ((size as u32 & FIELD_MASK) << SIZE_SHIFT),
+        );
+    }
+}
+
+/// Number of descriptors needed to transmit a buffer of the given length
+pub const fn descriptors_needed(length: usize) -> usize {
+    (length + MAX_DESCRIPTOR_LENGTH - 1) / MAX_DESCRIPTOR_LENGTH
+}
+
+/// Returns true if the memory range is located in DMA capable (internal data) RAM
+pub fn is_dma_capable(address: usize, length: usize) -> bool {
+    address >= DMA_RAM_START && address + length <= DMA_RAM_END
+}
+
+fn check_dma_capable(descriptors: &[Descriptor], buffer: &[u8]) -> Result<(), Error> {
+    if buffer.is_empty() {
+        return Err(Error::EmptyBuffer);
+    }
+
+    if !is_dma_capable(buffer.as_ptr() as usize, buffer.len())
+        || !is_dma_capable(
+            descriptors.as_ptr() as usize,
+            descriptors.len() * core::mem::size_of::<Descriptor>(),
+        )
+    {
+        return Err(Error::NotDmaCapable);
+    }
+
+    Ok(())
+}
+
+/// Link the descriptors to transmit the buffer
+///
+/// Returns the number of descriptors used. The last descriptor is marked as end of frame.
+pub(crate) fn link_tx(descriptors: &mut [Descriptor], buffer: &[u8]) -> Result<usize, Error> {
+    check_dma_capable(descriptors, buffer)?;
+
+    let count = descriptors_needed(buffer.len());
+    if count > descriptors.len() {
+        return Err(Error::TooFewDescriptors);
+    }
+
+    for (index, chunk) in buffer.chunks(MAX_DESCRIPTOR_LENGTH).enumerate() {
+        let last = index == count - 1;
+        let next = if last {
+            0
+        } else {
+            descriptors[index + 1].address()
+        };
+        descriptors[index].set(chunk.as_ptr() as u32, chunk.len(), chunk.len(), last, next);
+    }
+
+    Ok(count)
+}
+
+/// Link the descriptors to receive into the buffer
+///
+/// The buffer is divided evenly over all descriptors. When `circular` is true the last descriptor
+/// links back to the first one.
+///
+/// The DMA engine writes whole words, so the buffer must be word aligned and its length a multiple
+/// of 4. Otherwise up to 3 bytes after the end of the buffer would be overwritten.
+///
+/// Returns the number of descriptors used and the number of bytes per descriptor.
+pub(crate) fn link_rx(
+    descriptors: &mut [Descriptor],
+    buffer: &mut [u8],
+    circular: bool,
+) -> Result<(usize, usize), Error> {
+    check_dma_capable(descriptors, buffer)?;
+
+    if buffer.as_ptr() as usize % 4 != 0 || buffer.len() % 4 != 0 {
+        return Err(Error::Alignment);
+    }
+    if descriptors.is_empty() {
+        return Err(Error::TooFewDescriptors);
+    }
+
+    // word aligned part of the buffer per descriptor
+    let chunk_size = (buffer.len() + descriptors.len() - 1) / descriptors.len();
+    let chunk_size = (chunk_size + 3) & !3;
+    if chunk_size > MAX_DESCRIPTOR_LENGTH {
+        return Err(Error::TooFewDescriptors);
+    }
+
+    let count = (buffer.len() + chunk_size - 1) / chunk_size;
+    let first = descriptors[0].address();
+
+    for (index, chunk) in buffer.chunks_mut(chunk_size).enumerate() {
+        let next = if index < count - 1 {
+            descriptors[index + 1].address()
+        } else if circular {
+            first
+        } else {
+            0
+        };
+        descriptors[index].set(chunk.as_mut_ptr() as u32, chunk.len(), 0, false, next);
+    }
+
+    Ok((count, chunk_size))
+}
@@ -251,3 +251,24 @@ pub fn reset_peripheral(peripheral: Peripheral) {
         }
     });
 }
+
+/// Connect a SPI controller (1-3) to a SPI DMA channel (1 or 2, 0 to disconnect)
+///
+/// Returns false if the DMA channel is already connected to another SPI controller.
+pub(crate) fn select_spi_dma_channel(spi: u32, channel: u32) -> bool {
+    let dport = unsafe { &(*DPORT::ptr()) };
+    let shift = (spi - 1) * 2;
+    (&PERIPHERAL_MUTEX).lock(|_| unsafe {
+        let selection = dport.spi_dma_chan_sel.read().bits();
+        let in_use = channel != 0
+            && (1..=3)
+                .filter(|other| *other != spi)
+                .any(|other| (selection >> ((other - 1) * 2)) & 3 == channel);
+        if !in_use {
+            dport
+                .spi_dma_chan_sel
+                .write(|w| w.bits(selection & !(3 << shift) | channel << shift));
+        }
+        !in_use
+    })
+}
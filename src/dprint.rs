@@ -1,10 +1,26 @@
 //! Print debug information to UART0
 //!
-//! Directly writes to the UART0 TX uart queue.
-//! This is unsafe! It is asynchronous with normal UART0 usage and
-//! interrupts are not disabled.
+//! Directly writes to the UART0 TX FIFO, independent of the configuration of UART0.
+//!
+//! Each message is written in parts: a part is placed in the FIFO while holding a spin lock
+//! with interrupts disabled, but the lock is released while waiting for space in the FIFO. The
+//! core writing a message owns the output until the message is complete, so messages from both
+//! cores are not interleaved. A message from an interrupt handler which interrupted a message on
+//! the same core is written between two parts of that message. The transmitter of an owned UART0
+//! [Serial](crate::serial::Serial) driver uses the same mechanism, so both can share the console.
+
+use core::fmt::{self, Write};
 
+use crate::prelude::*;
 use crate::target::UART0;
+use crate::Core;
+
+/// Size of the UART0 TX FIFO
+const FIFO_SIZE: u8 = 128;
+
+/// Core writing the current message
+static MESSAGE_OWNER: CriticalSectionSpinLockMutex<Option<Core>> =
+    CriticalSectionSpinLockMutex::new(None);
 
 pub struct DebugLog {}
 
@@ -12,16 +28,17 @@ pub enum Error {}
 
 impl DebugLog {
     pub fn count(&mut self) -> u8 {
-        unsafe { (*UART0::ptr()).status.read().txfifo_cnt().bits() }
+        fifo_count()
     }
 
     pub fn is_idle(&mut self) -> bool {
-        unsafe { (*UART0::ptr()).status.read().st_utx_out().is_tx_idle() }
+        is_idle()
     }
 
+    /// Write a single byte, returns [nb::Error::WouldBlock] while the FIFO is full or the other
+    /// core is writing a message
     pub fn write(&mut self, byte: u8) -> nb::Result<(), Error> {
-        if self.count() < 128 {
-            unsafe { (*UART0::ptr()).tx_fifo.write_with_zero(|w| w.bits(byte)) }
+        if try_write_byte(byte) {
             Ok(())
         } else {
             Err(nb::Error::WouldBlock)
@@ -29,69 +46,176 @@ impl DebugLog {
     }
 }
 
-impl core::fmt::Write for DebugLog {
-    fn write_str(&mut self, s: &str) -> core::fmt::Result {
-        s.as_bytes()
-            .iter()
-            .try_for_each(|c| nb::block!(self.write(*c)))
-            .map_err(|_| core::fmt::Error)
+/// Every call to [write_str](Write::write_str) or [write_fmt](Write::write_fmt) is a single
+/// message
+impl Write for DebugLog {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        write_str(s)
+    }
+
+    fn write_fmt(&mut self, args: fmt::Arguments) -> fmt::Result {
+        write_fmt(args)
     }
 }
 
 pub static mut DEBUG_LOG: DebugLog = DebugLog {};
 
-/// Macro for sending a formatted string to UART0 for debugging
-#[macro_export]
-macro_rules! dprint {
-    ($s:expr) => {
-        #[allow(unused_unsafe)]
-        unsafe {
-            use core::fmt::Write;
-            $crate::dprint::DEBUG_LOG.write_str($s).unwrap();
+/// Writer of the parts of a message, see [write_message]
+pub(crate) struct MessageWriter {
+    _private: (),
+}
+
+impl Write for MessageWriter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let mut bytes = s.as_bytes();
+        while !bytes.is_empty() {
+            // interrupts are only disabled while filling the FIFO
+            let count = (&MESSAGE_OWNER).lock(|_| unsafe { fill_fifo(bytes) });
+            bytes = &bytes[count..];
         }
-    };
-    ($($arg:tt)*) => {
-        #[allow(unused_unsafe)]
-        unsafe {
-            use core::fmt::Write;
-            $crate::dprint::DEBUG_LOG.write_fmt(format_args!($($arg)*)).unwrap();
+        Ok(())
+    }
+}
+
+/// Write a message via `f`, waiting until a message of the other core is complete
+pub(crate) fn write_message<R>(f: impl FnOnce(&mut MessageWriter) -> R) -> R {
+    let core = crate::get_core();
+
+    let owner = loop {
+        let started = (&MESSAGE_OWNER).lock(|owner| match *owner {
+            None => {
+                *owner = Some(core);
+                Some(true)
+            }
+            // interrupted a message on this core, which can not continue until this one is done
+            Some(owner) if owner == core => Some(false),
+            Some(_) => None,
+        });
+        if let Some(owner) = started {
+            break owner;
         }
     };
+
+    let result = f(&mut MessageWriter { _private: () });
+
+    if owner {
+        (&MESSAGE_OWNER).lock(|owner| *owner = None);
+    }
+    result
+}
+
+/// Write a single byte unless the FIFO is full or the other core is writing a message
+pub(crate) fn try_write_byte(byte: u8) -> bool {
+    let core = crate::get_core();
+    (&MESSAGE_OWNER).lock(|owner| match *owner {
+        Some(owner) if owner != core => false,
+        _ => unsafe { fill_fifo(&[byte]) == 1 },
+    })
+}
+
+/// Writer bypassing the message ownership and the lock
+///
+/// # Safety
+/// Only to be used when the lock may never be released, e.g. when panicking after the other core
+/// has been stalled. Output may be interleaved with other writers.
+pub(crate) unsafe fn unlocked() -> UnlockedDebugLog {
+    UnlockedDebugLog { _private: () }
+}
+
+/// Writer returned by [unlocked]
+pub(crate) struct UnlockedDebugLog {
+    _private: (),
+}
+
+impl Write for UnlockedDebugLog {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let mut bytes = s.as_bytes();
+        while !bytes.is_empty() {
+            bytes = &bytes[unsafe { fill_fifo(bytes) }..];
+        }
+        Ok(())
+    }
+}
+
+/// Place as many bytes in the FIFO as there is space for, returning the number of bytes written
+///
+/// # Safety
+/// The caller must prevent concurrent writes to the FIFO.
+unsafe fn fill_fifo(bytes: &[u8]) -> usize {
+    let count = bytes
+        .len()
+        .min(FIFO_SIZE.saturating_sub(fifo_count()) as usize);
+    for byte in &bytes[..count] {
+        (*UART0::ptr()).tx_fifo.write_with_zero(|w| w.bits(*byte))
+    }
+    count
+}
+
+fn fifo_count() -> u8 {
+    unsafe { (*UART0::ptr()).status.read().txfifo_cnt().bits() }
+}
+
+/// Write a string as a single message
+pub fn write_str(s: &str) -> fmt::Result {
+    write_message(|writer| writer.write_str(s))
+}
+
+/// Write formatted arguments as a single message
+pub fn write_fmt(args: fmt::Arguments) -> fmt::Result {
+    write_message(|writer| writer.write_fmt(args))
+}
+/// Returns true if UART0 has transmitted all data
+pub fn is_idle() -> bool {
+    unsafe { (*UART0::ptr()).status.read().st_utx_out().is_tx_idle() }
+}
+
+/// Handle writing each message to the debug log atomically
+///
+/// Every call to [write_str](Write::write_str) or [write_fmt](Write::write_fmt) is a single
+/// message. Can be used where an owned writer is needed, e.g. for the
+/// [logger](crate::logger).
+#[derive(Copy, Clone, Debug, Default)]
+pub struct DebugLogWriter;
+
+impl Write for DebugLogWriter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        write_str(s)
+    }
+
+    fn write_fmt(&mut self, args: fmt::Arguments) -> fmt::Result {
+        write_fmt(args)
+    }
+}
+
+/// Macro for sending a formatted string to UART0 for debugging
+#[macro_export]
+macro_rules! dprint {
+    ($s:expr) => {{
+        $crate::dprint::write_str($s).ok();
+    }};
+    ($($arg:tt)*) => {{
+        $crate::dprint::write_fmt(format_args!($($arg)*)).ok();
+    }};
 }
 
 /// Macro for sending a formatted string to UART0 for debugging, with a newline.
 #[macro_export]
 macro_rules! dprintln {
-    () => {
-        #[allow(unused_unsafe)]
-        unsafe {
-            use core::fmt::Write;
-            $crate::dprint::DEBUG_LOG.write_str("\n").unwrap();
-        }
-    };
-    ($fmt:expr) => {
-        #[allow(unused_unsafe)]
-        unsafe {
-            use core::fmt::Write;
-            $crate::dprint::DEBUG_LOG.write_str(concat!($fmt, "\n")).unwrap();
-        }
-    };
-    ($fmt:expr, $($arg:tt)*) => {
-        #[allow(unused_unsafe)]
-        unsafe {
-            use core::fmt::Write;
-            $crate::dprint::DEBUG_LOG.write_fmt(format_args!(concat!($fmt, "\n"), $($arg)*)).unwrap();
-        }
-    };
+    () => {{
+        $crate::dprint::write_str("\n").ok();
+    }};
+    ($fmt:expr) => {{
+        $crate::dprint::write_str(concat!($fmt, "\n")).ok();
+    }};
+    ($fmt:expr, $($arg:tt)*) => {{
+        $crate::dprint::write_fmt(format_args!(concat!($fmt, "\n"), $($arg)*)).ok();
+    }};
 }
 
 /// Macro for flushing the UART0 TX buffer
 #[macro_export]
 macro_rules! dflush {
-    () => {
-        #[allow(unused_unsafe)]
-        unsafe {
-            while !$crate::dprint::DEBUG_LOG.is_idle() {}
-        }
-    };
+    () => {{
+        while !$crate::dprint::is_idle() {}
+    }};
 }
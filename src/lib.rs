@@ -13,12 +13,19 @@
 //! - `mem`
 //!     - Include customized memcpy, memset, etc. which use word (4-byte) sized and aligned
 //!         instructions to support IRAM usage and as optimization
+//! - `log`
+//!     - Enables the [logger] backend for the `log` crate
+//! - `panic_handler`
+//!     - Installs a panic handler which prints the panic information, stores it in the
+//!         [crash log](crashlog) and resets the chip
 
 #![no_std]
 #![cfg_attr(feature = "alloc", feature(allocator_api))]
 #![cfg_attr(feature = "alloc", feature(alloc_layout_extra))]
 #![cfg_attr(feature = "alloc", feature(nonnull_slice_from_raw_parts))]
 #![cfg_attr(feature = "alloc", feature(const_fn_trait_bound))]
+#![feature(asm)]
+#![feature(global_asm)]
 
 pub use embedded_hal as hal;
 pub use esp32 as target;
@@ -28,8 +35,13 @@ pub use proc_macros::interrupt;
 pub use proc_macros::ram;
 
 pub mod analog;
+pub mod backtrace;
 pub mod clock_control;
+pub mod console;
+pub mod coredump;
+pub mod crashlog;
 pub mod delay;
+pub mod dma;
 pub mod dport;
 pub mod efuse;
 #[cfg(feature = "external_ram")]
@@ -39,7 +51,12 @@ pub mod i2c;
 #[cfg(feature = "rt")]
 pub mod interrupt;
 pub mod ledc;
+#[cfg(feature = "log")]
+pub mod logger;
 pub mod prelude;
+mod registers;
+pub mod reset;
+mod ring_buffer;
 pub mod serial;
 pub mod spi;
 pub mod timer;
@@ -54,6 +71,9 @@ pub mod dprint;
 #[cfg(feature = "mem")]
 pub mod mem;
 
+#[cfg(feature = "panic_handler")]
+pub mod panic_handler;
+
 /// Function initializes ESP32 specific memories (RTC slow and fast) and
 /// then calls original Reset function
 ///
@@ -0,0 +1,372 @@
+//! Backend for the [log](https://docs.rs/log) crate
+//!
+//! Log records are written to a [Sink]: the [DebugLogWriter], an owned
+//! serial transmitter ([Tx](crate::serial::Tx)) or a [RingBuffer] in memory.
+//!
+//! Each record is formatted and written while holding a spin lock with interrupts disabled, so
+//! records from both cores and from interrupt handlers are never interleaved. Because of this,
+//! logging from an interrupt handler delays other interrupts until the record is written. Sinks
+//! writing to UART0 start a [debug log](crate::dprint) message before taking the lock, so they
+//! never wait for a message of the other core while holding it.
+//!
+//! # Example
+//! ```
+//! static LOGGER: Logger<Tx<esp32::UART0>> = Logger::new();
+//!
+//! static MODULE_LEVELS: &[(&str, LevelFilter)] = &[("esp32_hal::serial", LevelFilter::Warn)];
+//!
+//! let (tx, _rx) = serial.split();
+//! LOGGER
+//!     .init(
+//!         tx,
+//!         logger::Config::default()
+//!             .level(LevelFilter::Debug)
+//!             .module_levels(MODULE_LEVELS)
+//!             .colours(true),
+//!     )
+//!     .unwrap();
+//!
+//! log::info!("Hello {}", "world");
+//! ```
+//!
+//! *Note: timestamps are taken from [rtc_nanoseconds](ClockControlConfig::rtc_nanoseconds),
+//! so they require the clock control to be initialized. Disable them via
+//! [Config::timestamps] when logging before that.*
+
+use core::fmt::{self, Write};
+
+use crate::clock_control::ClockControlConfig;
+use crate::dprint::DebugLogWriter;
+use crate::prelude::*;
+use log::{Level, LevelFilter, Log, Metadata, Record, SetLoggerError};
+
+const COLOUR_RESET: &str = "\x1b[0m";
+
+/// Destination of log records
+pub trait Sink: Write + Send {
+    /// Wait until all written data has been output
+    fn flush(&mut self) {}
+
+    /// Run `f`, which takes the lock of the logger and writes a record
+    ///
+    /// Sinks writing to UART0 start a [debug log](crate::dprint) message before the lock is taken.
+    /// Waiting for a message of the other core while holding the lock would never finish when an
+    /// interrupt handler in the middle of that message logs as well.
+    fn with_message<R>(f: impl FnOnce() -> R) -> R
+    where
+        Self: Sized,
+    {
+        f()
+    }
+}
+
+impl Sink for DebugLogWriter {
+    fn flush(&mut self) {
+        while !crate::dprint::is_idle() {}
+    }
+
+    fn with_message<R>(f: impl FnOnce() -> R) -> R {
+        crate::dprint::write_message(|_| f())
+    }
+}
+
+/// Logger configuration
+#[derive(Copy, Clone, Debug)]
+pub struct Config {
+    /// Level for modules without a specific level
+    pub level: LevelFilter,
+    /// Levels of specific modules (including their submodules)
+    pub module_levels: &'static [(&'static str, LevelFilter)],
+    /// Colour records by level using ANSI escape codes
+    pub colours: bool,
+    /// Prefix records with the time since boot
+    pub timestamps: bool,
+}
+
+impl Config {
+    /// Set the level for modules without a specific level
+    pub fn level(mut self, level: LevelFilter) -> Self {
+        self.level = level;
+        self
+    }
+
+    /// Set the levels of specific modules
+    ///
+    /// When multiple entries match, the longest (most specific) module path is used.
+    pub fn module_levels(mut self, module_levels: &'static [(&'static str, LevelFilter)]) -> Self {
+        self.module_levels = module_levels;
+        self
+    }
+
+    /// Enable or disable ANSI colours
+    pub fn colours(mut self, colours: bool) -> Self {
+        self.colours = colours;
+        self
+    }
+
+    /// Enable or disable timestamps
+    pub fn timestamps(mut self, timestamps: bool) -> Self {
+        self.timestamps = timestamps;
+        self
+    }
+
+    /// Level for the given module path
+    pub fn level_for(&self, module_path: &str) -> LevelFilter {
+        let mut level = self.level;
+        let mut best_match = 0;
+
+        for (path, path_level) in self.module_levels {
+            let matches = module_path.starts_with(path)
+                && (module_path.len() == path.len() || module_path[path.len()..].starts_with("::"));
+
+            if matches && (path.len() >= best_match) {
+                level = *path_level;
+                best_match = path.len();
+            }
+        }
+
+        level
+    }
+
+    /// Highest level of all modules
+    fn max_level(&self) -> LevelFilter {
+        self.module_levels
+            .iter()
+            .map(|(_, level)| *level)
+            .fold(self.level, core::cmp::max)
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            level: LevelFilter::Info,
+            module_levels: &[],
+            colours: false,
+            timestamps: true,
+        }
+    }
+}
+
+/// Logger writing to a sink of type `S`
+///
+/// The logger has to be placed in a static, as the log crate requires a static reference.
+pub struct Logger<S> {
+    inner: CriticalSectionSpinLockMutex<Option<Inner<S>>>,
+}
+
+struct Inner<S> {
+    sink: S,
+    config: Config,
+}
+
+impl<S> Logger<S> {
+    /// Create a new logger
+    ///
+    /// The logger does not output anything until it is initialized via [Logger::init].
+    pub const fn new() -> Self {
+        Logger {
+            inner: CriticalSectionSpinLockMutex::new(None),
+        }
+    }
+}
+
+impl<S: Sink> Logger<S> {
+    /// Set the sink and configuration and install this as the global logger
+    ///
+    /// Fails if another logger has already been installed.
+    pub fn init(&'static self, sink: S, config: Config) -> Result<(), SetLoggerError>
+    where
+        S: 'static,
+    {
+        log::set_logger(self)?;
+        self.change_sink(sink);
+        self.change_config(config);
+        Ok(())
+    }
+
+    /// Replace the sink, returning the previous one
+    pub fn change_sink(&self, sink: S) -> Option<S> {
+        (&self.inner).lock(|inner| match inner {
+            Some(inner) => Some(core::mem::replace(&mut inner.sink, sink)),
+            None => {
+                *inner = Some(Inner {
+                    sink,
+                    config: Config::default(),
+                });
+                None
+            }
+        })
+    }
+
+    /// Change the configuration
+    ///
+    /// Has no effect before a sink has been set.
+    pub fn change_config(&self, config: Config) {
+        (&self.inner).lock(|inner| {
+            if let Some(inner) = inner {
+                inner.config = config;
+            }
+        });
+        log::set_max_level(config.max_level());
+    }
+
+    /// Remove and return the sink
+    ///
+    /// Records are discarded until a new sink is set.
+    pub fn release(&self) -> Option<S> {
+        (&self.inner).lock(|inner| inner.take().map(|inner| inner.sink))
+    }
+
+    /// Access the sink (e.g. to read the [RingBuffer])
+    ///
+    /// *Note: this is called with interrupts disabled, so `f` should be short.*
+    pub fn with_sink<R>(&self, f: impl FnOnce(&mut S) -> R) -> Option<R> {
+        (&self.inner).lock(|inner| inner.as_mut().map(|inner| f(&mut inner.sink)))
+    }
+}
+
+impl<S: Sink> Log for Logger<S> {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        (&self.inner).lock(|inner| match inner {
+            Some(inner) => metadata.level() <= inner.config.level_for(metadata.target()),
+            None => false,
+        })
+    }
+
+    fn log(&self, record: &Record) {
+        // obtained before taking the lock, as this blocks for up to one slow RTC clock cycle
+        let timestamp = if (&self.inner).lock(|inner| match inner {
+            Some(inner) => inner.config.timestamps,
+            None => false,
+        }) {
+            Some(u64::from(ClockControlConfig {}.rtc_nanoseconds()))
+        } else {
+            None
+        };
+        let core = crate::get_core();
+
+        S::with_message(|| {
+            (&self.inner).lock(|inner| {
+                if let Some(inner) = inner {
+                    if record.level() <= inner.config.level_for(record.target()) {
+                        // errors can not be reported anywhere
+                        let _ =
+                            write_record(&mut inner.sink, &inner.config, timestamp, core, record);
+                    }
+                }
+            })
+        });
+    }
+
+    fn flush(&self) {
+        (&self.inner).lock(|inner| {
+            if let Some(inner) = inner {
+                inner.sink.flush();
+            }
+        });
+    }
+}
+
+fn level_colour(level: Level) -> Option<&'static str> {
+    match level {
+        Level::Error => Some("\x1b[31m"),
+        Level::Warn => Some("\x1b[33m"),
+        Level::Info => Some("\x1b[32m"),
+        Level::Debug | Level::Trace => None,
+    }
+}
+
+/// Formats the optional timestamp prefix
+struct Timestamp(Option<u64>);
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            Some(nanoseconds) => write!(
+                f,
+                "[{:>5}.{:06}] ",
+                nanoseconds / 1_000_000_000,
+                (nanoseconds % 1_000_000_000) / 1_000
+            ),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Write the record with a single `write_fmt`, so sinks can write it as one message
+fn write_record(
+    sink: &mut dyn Write,
+    config: &Config,
+    timestamp: Option<u64>,
+    core: crate::Core,
+    record: &Record,
+) -> fmt::Result {
+    let colour = if config.colours {
+        level_colour(record.level())
+    } else {
+        None
+    };
+
+    write!(
+        sink,
+        "{}{}{:?} {:<5} {}: {}{}\n",
+        colour.unwrap_or(""),
+        Timestamp(timestamp),
+        core,
+        record.level(),
+        record.target(),
+        record.args(),
+        if colour.is_some() { COLOUR_RESET } else { "" }
+    )
+}
+
+/// Ring buffer sink
+///
+/// When the buffer is full, the oldest data is overwritten.
+pub struct RingBuffer {
+    buffer: crate::ring_buffer::RingBuffer<&'static mut [u8]>,
+}
+
+impl RingBuffer {
+    /// Create a new ring buffer sink using the given (static) buffer
+    pub fn new(buffer: &'static mut [u8]) -> Self {
+        RingBuffer {
+            buffer: crate::ring_buffer::RingBuffer::new(buffer),
+        }
+    }
+
+    /// Number of bytes in the buffer
+    pub fn len(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Returns true if the buffer is empty
+    pub fn is_empty(&self) -> bool {
+        self.buffer.is_empty()
+    }
+
+    /// Discard all data
+    pub fn clear(&mut self) {
+        self.buffer.clear();
+    }
+
+    /// Move the oldest data into `data`, returning the number of bytes read
+    pub fn read(&mut self, data: &mut [u8]) -> usize {
+        self.buffer.read(data)
+    }
+
+    /// Release the buffer
+    pub fn release(self) -> &'static mut [u8] {
+        self.buffer.release()
+    }
+}
+
+impl Write for RingBuffer {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        s.bytes().for_each(|byte| self.buffer.push_overwrite(byte));
+        Ok(())
+    }
+}
+
+impl Sink for RingBuffer {}
@@ -0,0 +1,110 @@
+//! Panic handler (enabled via the `panic_handler` feature)
+//!
+//! On a panic the handler:
+//! - stalls the other core
+//! - prints the panic information, the panicking core, the exception registers and a
+//!   [backtrace](crate::backtrace) to UART0
+//! - stores the panic message in the [crash log](crate::crashlog)
+//! - writes a [core dump](crate::coredump) if enabled via
+//!   [write_on_panic](crate::coredump::write_on_panic)
+//! - resets the chip, using the method selected via [set_reset_method]
+//!
+//! As the other core may be stalled while holding a lock, the UART0 and crash log locks are
+//! bypassed.
+//!
+//! *Note: when this feature is enabled, applications must not define their own panic handler
+//! (e.g. via the `panic-halt` crate).*
+
+use core::fmt::Write;
+use core::panic::PanicInfo;
+use core::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+
+use crate::clock_control::{cpu, watchdog};
+use crate::registers::ExceptionRegisters;
+
+/// Number of slow RTC clock cycles before the watchdog resets the chip
+///
+/// Between about 1ms (150kHz RTC clock) and 6ms (32kHz crystal).
+const WATCHDOG_RESET_TICKS: u32 = 200;
+
+/// How to reset the chip after a panic
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum ResetMethod {
+    /// Software system reset
+    Software = 0,
+    /// System reset by the RTC watchdog
+    Watchdog = 1,
+    /// Do not reset, halt the core (e.g. for attaching a debugger)
+    Halt = 2,
+}
+
+static RESET_METHOD: AtomicU8 = AtomicU8::new(ResetMethod::Software as u8);
+static PANICKING: AtomicBool = AtomicBool::new(false);
+
+/// Select how to reset the chip after a panic
+pub fn set_reset_method(method: ResetMethod) {
+    RESET_METHOD.store(method as u8, Ordering::SeqCst);
+}
+
+/// Get the method used to reset the chip after a panic
+pub fn reset_method() -> ResetMethod {
+    match RESET_METHOD.load(Ordering::SeqCst) {
+        1 => ResetMethod::Watchdog,
+        2 => ResetMethod::Halt,
+        _ => ResetMethod::Software,
+    }
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    xtensa_lx::interrupt::disable();
+
+    // a panic while handling a panic: only reset
+    if PANICKING.swap(true, Ordering::SeqCst) {
+        reset(reset_method());
+    }
+
+    let core = crate::get_core();
+    unsafe { cpu::park_core(crate::get_other_core()) };
+
+    let mut output = unsafe { crate::dprint::unlocked() };
+    let _ = writeln!(output, "\n\n*** PANIC on core {:?}: {}", core, info);
+    let exception = ExceptionRegisters::read();
+    let _ = writeln!(
+        output,
+        "EXCCAUSE: {:#010x} EPC1: {:#010x} EXCVADDR: {:#010x}",
+        exception.exccause, exception.epc1, exception.excvaddr
+    );
+
+    let _ = writeln!(output, "{}", crate::backtrace::backtrace());
+
+    // before the core dump, which takes a while and may fail
+    unsafe { crate::crashlog::record_panic_unlocked(info) };
+
+    if let Some(regions) = crate::coredump::panic_regions() {
+        match crate::coredump::write(regions) {
+            Ok(()) => {
+                let _ = writeln!(output, "Core dump written");
+            }
+            Err(error) => {
+                let _ = writeln!(output, "Core dump failed: {:?}", error);
+            }
+        }
+    }
+
+    let method = reset_method();
+    let _ = writeln!(output, "Reset method: {:?}", method);
+    while !crate::dprint::is_idle() {}
+
+    reset(method);
+}
+
+fn reset(method: ResetMethod) -> ! {
+    match method {
+        ResetMethod::Software => crate::reset::system_reset(),
+        ResetMethod::Watchdog => watchdog::system_reset_after(WATCHDOG_RESET_TICKS),
+        ResetMethod::Halt => {}
+    }
+
+    loop {}
+}
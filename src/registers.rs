@@ -0,0 +1,78 @@
+//! Special registers of the current core
+//!
+//! Shared by the [backtrace](crate::backtrace), the [core dump](crate::coredump) and the panic
+//! handler.
+
+/// Exception registers, describing the last (level 1) exception
+#[derive(Copy, Clone, Debug, Default)]
+pub(crate) struct ExceptionRegisters {
+    /// Exception cause
+    pub exccause: u32,
+    /// Program counter of the exception
+    pub epc1: u32,
+    /// Virtual address causing a memory exception
+    pub excvaddr: u32,
+}
+
+impl ExceptionRegisters {
+    /// Read the exception registers of the current core
+    pub fn read() -> Self {
+        let mut registers = ExceptionRegisters::default();
+        unsafe {
+            asm!(
+                "rsr.exccause {exccause}",
+                "rsr.epc1 {epc1}",
+                "rsr.excvaddr {excvaddr}",
+                exccause = out(reg) registers.exccause,
+                epc1 = out(reg) registers.epc1,
+                excvaddr = out(reg) registers.excvaddr,
+            );
+        }
+        registers
+    }
+}
+
+/// Registers of a stack frame
+#[derive(Copy, Clone, Debug, Default)]
+pub(crate) struct Frame {
+    pub pc: u32,
+    /// Stack pointer (A1)
+    pub sp: u32,
+    /// Return address (A0)
+    pub return_address: u32,
+    pub ps: u32,
+    pub sar: u32,
+    pub lbeg: u32,
+    pub lend: u32,
+    pub lcount: u32,
+}
+
+/// Registers of the frame of the calling function
+///
+/// Always inlined, so the program counter and stack pointer are those of the caller.
+#[inline(always)]
+pub(crate) fn current_frame() -> Frame {
+    let mut frame = Frame::default();
+    unsafe {
+        asm!(
+            "movi {pc}, 1f",
+            "1:",
+            "mov {sp}, a1",
+            "mov {ra}, a0",
+            "rsr.ps {ps}",
+            "rsr.sar {sar}",
+            "rsr.lbeg {lbeg}",
+            "rsr.lend {lend}",
+            "rsr.lcount {lcount}",
+            pc = out(reg) frame.pc,
+            sp = out(reg) frame.sp,
+            ra = out(reg) frame.return_address,
+            ps = out(reg) frame.ps,
+            sar = out(reg) frame.sar,
+            lbeg = out(reg) frame.lbeg,
+            lend = out(reg) frame.lend,
+            lcount = out(reg) frame.lcount,
+        );
+    }
+    frame
+}
@@ -0,0 +1,308 @@
+//! Reset reasons and software restart
+//!
+//! The reset reason is latched per core by the RTC controller and stays valid until the next
+//! reset. After a wake-up from deep sleep the reason is [ResetReason::DeepSleep], the wake-up
+//! source is available via [wakeup_cause](crate::clock_control::wakeup::wakeup_cause).
+//!
+//! The restart functions can store a reason in RTC slow memory, which can be retrieved after
+//! the restart via [take_restart_reason].
+
+use crate::clock_control::cpu;
+use crate::crashlog::buffer::crc32;
+use crate::target::RTCCNTL;
+use crate::Core;
+
+/// Reason for the last reset of a core
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum ResetReason {
+    /// Power-on reset
+    PowerOn,
+    /// Software system reset
+    Software,
+    /// Legacy watchdog system reset
+    LegacyWatchdog,
+    /// Wake-up from deep sleep
+    DeepSleep,
+    /// SDIO system reset
+    Sdio,
+    /// Timer group 0 watchdog system reset
+    Timer0Watchdog,
+    /// Timer group 1 watchdog system reset
+    Timer1Watchdog,
+    /// RTC watchdog system reset
+    RtcWatchdog,
+    /// Intrusion test reset
+    Intrusion,
+    /// Timer group watchdog core reset
+    TimerWatchdogCore,
+    /// Software core reset
+    SoftwareCore,
+    /// RTC watchdog core reset
+    RtcWatchdogCore,
+    /// APP core reset by the PRO core
+    ExternalCore,
+    /// Brown-out reset (RTC watchdog or brown-out detector)
+    BrownOut,
+    /// RTC watchdog reset of the digital and RTC domain
+    RtcWatchdogRtc,
+    /// Unknown reset reason code
+    Unknown(u8),
+}
+
+impl ResetReason {
+    /// Decode the reset reason code from the RTC controller
+    pub fn from_code(code: u8) -> Self {
+        match code {
+            1 => ResetReason::PowerOn,
+            3 => ResetReason::Software,
+            4 => ResetReason::LegacyWatchdog,
+            5 => ResetReason::DeepSleep,
+            6 => ResetReason::Sdio,
+            7 => ResetReason::Timer0Watchdog,
+            8 => ResetReason::Timer1Watchdog,
+            9 => ResetReason::RtcWatchdog,
+            10 => ResetReason::Intrusion,
+            11 => ResetReason::TimerWatchdogCore,
+            12 => ResetReason::SoftwareCore,
+            13 => ResetReason::RtcWatchdogCore,
+            14 => ResetReason::ExternalCore,
+            15 => ResetReason::BrownOut,
+            16 => ResetReason::RtcWatchdogRtc,
+            code => ResetReason::Unknown(code),
+        }
+    }
+
+    /// Returns true if the reset was caused by one of the watchdogs
+    pub fn is_watchdog(&self) -> bool {
+        matches!(
+            self,
+            ResetReason::LegacyWatchdog
+                | ResetReason::Timer0Watchdog
+                | ResetReason::Timer1Watchdog
+                | ResetReason::RtcWatchdog
+                | ResetReason::TimerWatchdogCore
+                | ResetReason::RtcWatchdogCore
+                | ResetReason::RtcWatchdogRtc
+        )
+    }
+
+    /// Returns true if the reset was requested by software
+    pub fn is_software(&self) -> bool {
+        matches!(
+            self,
+            ResetReason::Software | ResetReason::SoftwareCore | ResetReason::ExternalCore
+        )
+    }
+}
+
+/// Get the reason for the last reset of the core
+pub fn reset_reason(core: Core) -> ResetReason {
+    let reset_state = unsafe { (*RTCCNTL::ptr()).reset_state.read() };
+
+    ResetReason::from_code(match core {
+        Core::PRO => reset_state.reset_cause_procpu().bits(),
+        Core::APP => reset_state.reset_cause_appcpu().bits(),
+    })
+}
+
+/// Maximum length of the restart reason in bytes
+pub const MAX_RESTART_REASON_LENGTH: usize = 64;
+
+const RESTART_REASON_MAGIC: u32 = 0x5253_5452;
+
+/// Reset errors
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum Error {
+    /// Operation is not supported by the chip
+    NotSupported,
+}
+
+/// Restart reason provided by the application
+#[derive(Copy, Clone)]
+pub struct RestartReason {
+    length: usize,
+    data: [u8; MAX_RESTART_REASON_LENGTH],
+}
+
+impl RestartReason {
+    /// The reason as string
+    pub fn as_str(&self) -> &str {
+        let data = &self.data[..self.length];
+        match core::str::from_utf8(data) {
+            Ok(reason) => reason,
+            Err(error) => core::str::from_utf8(&data[..error.valid_up_to()]).unwrap_or_default(),
+        }
+    }
+}
+
+impl core::fmt::Debug for RestartReason {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        core::fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl core::fmt::Display for RestartReason {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[repr(C)]
+struct StoredRestartReason {
+    magic: u32,
+    crc: u32,
+    reason: RestartReason,
+}
+
+impl StoredRestartReason {
+    fn crc(&self) -> u32 {
+        crc32(&self.reason.data[..self.reason.length.min(MAX_RESTART_REASON_LENGTH)])
+    }
+}
+
+#[crate::ram(rtc_slow, uninitialized)]
+static mut RESTART_REASON: StoredRestartReason = StoredRestartReason {
+    magic: 0,
+    crc: 0,
+    reason: RestartReason {
+        length: 0,
+        data: [0; MAX_RESTART_REASON_LENGTH],
+    },
+};
+
+/// Get and clear the reason stored by the restart before the last reset
+///
+/// Returns None if no reason was given or the last reset was not caused by a restart (e.g. a
+/// watchdog reset or power-on).
+pub fn take_restart_reason() -> Option<RestartReason> {
+    xtensa_lx::interrupt::free(|_| unsafe {
+        let stored = &mut RESTART_REASON;
+        let valid = stored.magic == RESTART_REASON_MAGIC
+            && stored.reason.length <= MAX_RESTART_REASON_LENGTH
+            && stored.crc == stored.crc();
+        stored.magic = 0;
+
+        if valid {
+            Some(stored.reason)
+        } else {
+            None
+        }
+    })
+}
+
+fn store_restart_reason(reason: Option<&str>) {
+    unsafe {
+        let stored = &mut RESTART_REASON;
+        match reason {
+            Some(reason) => {
+                // truncate, the partial character at the end is dropped when reading
+                let length = reason.len().min(MAX_RESTART_REASON_LENGTH);
+                stored.reason.data[..length].copy_from_slice(&reason.as_bytes()[..length]);
+                stored.reason.length = length;
+                stored.crc = stored.crc();
+                stored.magic = RESTART_REASON_MAGIC;
+            }
+            None => stored.magic = 0,
+        }
+    }
+}
+
+/// Prepare for a restart: disable interrupts, store the reason and wait until UART0 has sent
+/// all data
+fn prepare_restart(reason: Option<&str>) {
+    xtensa_lx::interrupt::disable();
+    store_restart_reason(reason);
+
+    // limit the wait, in case UART0 is blocked by flow control
+    for _ in 0..UART_FLUSH_POLLS {
+        if crate::dprint::is_idle() {
+            break;
+        }
+    }
+}
+
+/// Maximum number of polls while waiting for UART0 to be idle (some 10s of ms)
+const UART_FLUSH_POLLS: u32 = 1_000_000;
+
+/// Software system reset
+///
+/// Resets both cores and all digital peripherals, but not the RTC domain.
+pub(crate) fn system_reset() -> ! {
+    unsafe {
+        (*RTCCNTL::ptr())
+            .options0
+            .modify(|_, w| w.sw_sys_rst().set_bit());
+    }
+    loop {}
+}
+
+/// Restart the chip
+///
+/// The other core is stopped before the system reset. The optional reason can be retrieved after
+/// the restart via [take_restart_reason].
+pub fn restart(reason: Option<&str>) -> ! {
+    prepare_restart(reason);
+    unsafe { cpu::park_core(crate::get_other_core()) };
+    system_reset();
+}
+
+/// Restart a single core
+///
+/// Only the core is reset, the other core and the peripherals keep running. When resetting the
+/// current core this function does not return. After a reset of the APP core from the PRO core,
+/// the APP core has to be started again via
+/// [start_app_core](crate::clock_control::ClockControl::start_app_core).
+///
+/// The optional reason can be retrieved after the restart via [take_restart_reason].
+pub fn restart_core(core: Core, reason: Option<&str>) {
+    let rtc_control = unsafe { &*RTCCNTL::ptr() };
+    let current_core = core == crate::get_core();
+
+    if current_core {
+        prepare_restart(reason);
+    } else {
+        store_restart_reason(reason);
+    }
+
+    // keep the APP core stopped after the reset until it is started again
+    if core == Core::APP && !current_core {
+        unsafe { cpu::park_core(core) };
+    }
+
+    match core {
+        Core::PRO => {
+            rtc_control
+                .options0
+                .modify(|_, w| w.sw_procpu_rst().set_bit());
+            rtc_control
+                .options0
+                .modify(|_, w| w.sw_procpu_rst().clear_bit());
+        }
+        Core::APP => {
+            rtc_control
+                .options0
+                .modify(|_, w| w.sw_appcpu_rst().set_bit());
+            rtc_control
+                .options0
+                .modify(|_, w| w.sw_appcpu_rst().clear_bit());
+        }
+    }
+
+    if current_core {
+        loop {}
+    }
+
+    // stop the clock, so the APP core can be started again
+    if core == Core::APP {
+        unsafe { cpu::stop_app_core() };
+    }
+}
+
+/// Restart into the serial download mode of the ROM bootloader
+///
+/// The ESP32 selects the boot mode only via the strapping pins (GPIO0), so this always returns
+/// [Error::NotSupported]. Use the DTR/RTS lines of the serial adapter (as done by esptool)
+/// instead.
+pub fn restart_to_download_mode(_reason: Option<&str>) -> Result<(), Error> {
+    Err(Error::NotSupported)
+}
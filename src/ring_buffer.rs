@@ -0,0 +1,143 @@
+//! Fixed size byte ring buffer
+//!
+//! Shared by the [buffered serial](crate::serial::buffered), the [logger](crate::logger) and
+//! the [crash log](crate::crashlog). The storage can be a statically allocated slice or an
+//! array, the latter allowing the ring buffer to be part of a `#[repr(C)]` structure in memory
+//! which is not initialized at boot.
+//!
+//! *Note: this module does not depend on the hardware, so it is also compiled and tested on the
+//! host (see the `host-tests` directory).*
+
+/// Fixed size byte ring buffer
+#[repr(C)]
+#[derive(Clone)]
+pub struct RingBuffer<S> {
+    start: usize,
+    length: usize,
+    buffer: S,
+}
+
+impl<S> RingBuffer<S> {
+    /// Create a new, empty ring buffer using the given storage
+    pub const fn new(buffer: S) -> Self {
+        RingBuffer {
+            start: 0,
+            length: 0,
+            buffer,
+        }
+    }
+
+    /// Release the storage
+    pub fn release(self) -> S {
+        self.buffer
+    }
+}
+
+impl<S: AsRef<[u8]> + AsMut<[u8]>> RingBuffer<S> {
+    /// Total number of bytes the buffer can hold
+    pub fn capacity(&self) -> usize {
+        self.buffer.as_ref().len()
+    }
+
+    /// Number of bytes in the buffer
+    pub fn len(&self) -> usize {
+        self.length
+    }
+
+    /// Returns true if the buffer is empty
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    /// Returns true if the buffer is full
+    pub fn is_full(&self) -> bool {
+        self.length == self.capacity()
+    }
+
+    /// Returns true if the start and length are within the storage
+    ///
+    /// Used to validate a ring buffer in memory which is not initialized at boot.
+    pub fn is_consistent(&self) -> bool {
+        let capacity = self.capacity();
+        self.length <= capacity && (self.start < capacity || self.start == 0)
+    }
+
+    /// Discard all data
+    pub fn clear(&mut self) {
+        self.start = 0;
+        self.length = 0;
+    }
+
+    /// Add a byte to the end of the buffer, returns false if the buffer is full
+    pub fn push(&mut self, byte: u8) -> bool {
+        if self.is_full() {
+            return false;
+        }
+        let index = (self.start + self.length) % self.capacity();
+        self.buffer.as_mut()[index] = byte;
+        self.length += 1;
+        true
+    }
+
+    /// Add a byte to the end of the buffer, overwriting the oldest byte if the buffer is full
+    pub fn push_overwrite(&mut self, byte: u8) {
+        let capacity = self.capacity();
+        if capacity == 0 {
+            return;
+        }
+
+        self.buffer.as_mut()[(self.start + self.length) % capacity] = byte;
+        if self.length < capacity {
+            self.length += 1;
+        } else {
+            self.start = (self.start + 1) % capacity;
+        }
+    }
+
+    /// Remove a byte from the start of the buffer
+    pub fn pop(&mut self) -> Option<u8> {
+        if self.is_empty() {
+            return None;
+        }
+        let byte = self.buffer.as_ref()[self.start];
+        self.start = (self.start + 1) % self.capacity();
+        self.length -= 1;
+        Some(byte)
+    }
+
+    /// Move the oldest data into `data`, returning the number of bytes read
+    pub fn read(&mut self, data: &mut [u8]) -> usize {
+        let count = data.len().min(self.length);
+        let (first, second) = self.as_slices();
+        let split = count.min(first.len());
+
+        data[..split].copy_from_slice(&first[..split]);
+        data[split..count].copy_from_slice(&second[..count - split]);
+        self.discard(count);
+
+        count
+    }
+
+    /// Remove up to `count` bytes from the start of the buffer
+    pub fn discard(&mut self, count: usize) {
+        let count = count.min(self.length);
+        if count > 0 {
+            self.start = (self.start + count) % self.capacity();
+            self.length -= count;
+        }
+    }
+
+    /// The contents of the buffer, oldest data first
+    ///
+    /// The second slice is only non-empty if the data wraps around the end of the storage.
+    pub fn as_slices(&self) -> (&[u8], &[u8]) {
+        let buffer = self.buffer.as_ref();
+        let end = self.start + self.length;
+
+        if end <= buffer.len() {
+            (&buffer[self.start..end], &[])
+        } else {
+            (&buffer[self.start..], &buffer[..end - buffer.len()])
+        }
+    }
+}
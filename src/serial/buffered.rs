@@ -0,0 +1,453 @@
+//! Interrupt driven UART with software ring buffers
+//!
+//! The hardware FIFOs of the UART are only 128 bytes deep. The buffered receiver and transmitter
+//! add (larger) statically allocated ring buffers, which are filled and drained from the UART
+//! interrupt.
+//!
+//! The buffered serial is shared between the application and the interrupt handler, typically
+//! via a static [CriticalSectionSpinLockMutex].
+//!
+//! # Example
+//! ```
+//! static SERIAL: CriticalSectionSpinLockMutex<Option<BufferedSerial<esp32::UART0>>> =
+//!     CriticalSectionSpinLockMutex::new(None);
+//!
+//! static mut TX_BUFFER: [u8; 1024] = [0; 1024];
+//! static mut RX_BUFFER: [u8; 1024] = [0; 1024];
+//!
+//! let (tx, rx) = serial.split();
+//! let serial = BufferedSerial::new(tx, rx, unsafe { &mut TX_BUFFER }, unsafe { &mut RX_BUFFER });
+//! (&SERIAL).lock(|data| *data = Some(serial));
+//!
+//! interrupt::enable(Interrupt::UART0_INTR).unwrap();
+//!
+//! #[interrupt]
+//! fn UART0_INTR() {
+//!     (&SERIAL).lock(|serial| serial.as_mut().unwrap().handle_interrupt());
+//! }
+//! ```
+//!
+//! When listening to the [PatternDetected](Event::PatternDetected) event, the buffered receiver
+//! records the positions of the detected patterns, which can be used to read complete lines or
+//! responses (see [BufferedRx::pattern_position]).
+//!
+//! *Note: bytes are only moved between the FIFOs and the ring buffers when the interrupt is
+//! handled or when the buffered receiver or transmitter is accessed, so the interrupt handler
+//! has to run at least once every 128 byte times to prevent loss of data.*
+
+use super::{private::Instance, Error, Event, Rx, Tx, UART_FIFO_SIZE};
+use crate::ring_buffer::RingBuffer;
+use embedded_hal::serial::{self, Read, Write};
+
+/// Events handled by the buffered receiver
+const RX_EVENTS: [Event; 7] = [
+    Event::Rxne,
+    Event::Idle,
+    Event::Overflow,
+    Event::Break,
+    Event::FramingError,
+    Event::ParityError,
+    Event::Collision,
+];
+
+/// Maximum number of pattern positions recorded by the buffered receiver
+pub const PATTERN_QUEUE_SIZE: usize = 8;
+
+/// Queue of pattern positions
+///
+/// Positions are stored as the total number of bytes received before the pattern, so they do
+/// not change when reading from the buffer.
+struct PatternQueue {
+    positions: [usize; PATTERN_QUEUE_SIZE],
+    start: usize,
+    len: usize,
+}
+
+impl PatternQueue {
+    fn new() -> Self {
+        PatternQueue {
+            positions: [0; PATTERN_QUEUE_SIZE],
+            start: 0,
+            len: 0,
+        }
+    }
+
+    /// Add a position to the end of the queue, returns false if the queue is full
+    fn push(&mut self, position: usize) -> bool {
+        if self.len == PATTERN_QUEUE_SIZE {
+            return false;
+        }
+        self.positions[(self.start + self.len) % PATTERN_QUEUE_SIZE] = position;
+        self.len += 1;
+        true
+    }
+
+    fn peek(&self) -> Option<usize> {
+        if self.len == 0 {
+            None
+        } else {
+            Some(self.positions[self.start])
+        }
+    }
+
+    fn pop(&mut self) -> Option<usize> {
+        let position = self.peek()?;
+        self.start = (self.start + 1) % PATTERN_QUEUE_SIZE;
+        self.len -= 1;
+        Some(position)
+    }
+}
+
+/// Buffered serial receiver
+pub struct BufferedRx<UART: Instance> {
+    rx: Rx<UART>,
+    buffer: RingBuffer<&'static mut [u8]>,
+    buffer_overrun: bool,
+    line_error: Option<Error>,
+    patterns: PatternQueue,
+    /// Total number of bytes stored in the buffer (wrapping)
+    received: usize,
+    /// Total number of bytes read from the buffer (wrapping)
+    read: usize,
+}
+
+impl<UART: Instance> BufferedRx<UART> {
+    /// Create a new buffered receiver
+    ///
+    /// Listens to the [Rxne](Event::Rxne), [Idle](Event::Idle) and line error events.
+    /// The UART interrupt still needs to be enabled.
+    pub fn new(mut rx: Rx<UART>, buffer: &'static mut [u8]) -> Self {
+        for &event in RX_EVENTS.iter() {
+            rx.listen(event);
+        }
+
+        BufferedRx {
+            rx,
+            buffer: RingBuffer::new(buffer),
+            buffer_overrun: false,
+            line_error: None,
+            patterns: PatternQueue::new(),
+            received: 0,
+            read: 0,
+        }
+    }
+
+    /// Get count of bytes in the receive buffer
+    pub fn count(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Move received bytes from the FIFO to the ring buffer
+    ///
+    /// To be called from the UART interrupt handler.
+    pub fn handle_interrupt(&mut self) {
+        // the pattern position is relative to the data in the FIFO
+        if self.rx.is_interrupt_pending(Event::PatternDetected) {
+            if let Some(position) = self.rx.pattern_position() {
+                // positions are dropped when the queue is full
+                self.patterns
+                    .push(self.received.wrapping_add(position as usize));
+            }
+            self.rx.clear_interrupt(Event::PatternDetected);
+        }
+
+        // line errors are cleared by reading
+        self.fill_buffer();
+
+        self.rx.clear_interrupt(Event::Rxne);
+        self.rx.clear_interrupt(Event::Idle);
+    }
+
+    /// Read as many bytes as available (up to the length of data)
+    ///
+    /// Returns the number of bytes read or an error if data has been lost since the last read.
+    pub fn read_slice(&mut self, data: &mut [u8]) -> Result<usize, Error> {
+        self.take_error()?;
+        self.fill_buffer();
+
+        let mut count = 0;
+        while count < data.len() {
+            match self.pop() {
+                Some(byte) => data[count] = byte,
+                None => break,
+            }
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// Get the position of the next detected pattern relative to the next byte to be read
+    ///
+    /// Returns None if no pattern has been detected (or the pattern has already been read). The
+    /// pattern itself is included in the data, e.g. with a position of 5 the pattern starts at
+    /// the 6th byte read.
+    ///
+    /// Requires listening to the [PatternDetected](Event::PatternDetected) event. At most
+    /// [PATTERN_QUEUE_SIZE] positions are recorded, later patterns are dropped until positions
+    /// are popped.
+    pub fn pattern_position(&mut self) -> Option<usize> {
+        self.discard_read_patterns();
+        self.patterns
+            .peek()
+            .map(|position| position.wrapping_sub(self.read))
+    }
+
+    /// Get and remove the position of the next detected pattern (see
+    /// [pattern_position](Self::pattern_position))
+    pub fn pop_pattern_position(&mut self) -> Option<usize> {
+        self.discard_read_patterns();
+        self.patterns
+            .pop()
+            .map(|position| position.wrapping_sub(self.read))
+    }
+
+    /// Stop listening to the interrupt events and release the receiver
+    pub fn release(mut self) -> Rx<UART> {
+        for &event in RX_EVENTS.iter() {
+            self.rx.unlisten(event);
+        }
+        self.rx
+    }
+
+    fn fill_buffer(&mut self) {
+        loop {
+            match self.rx.read() {
+                Ok(byte) => {
+                    if self.buffer.push(byte) {
+                        self.received = self.received.wrapping_add(1);
+                    } else {
+                        self.buffer_overrun = true;
+                    }
+                }
+                Err(nb::Error::Other(error)) => {
+                    // keep the first error until it is reported
+                    if self.line_error.is_none() {
+                        self.line_error = Some(error);
+                    }
+                }
+                Err(nb::Error::WouldBlock) => break,
+            }
+        }
+    }
+
+    fn pop(&mut self) -> Option<u8> {
+        let byte = self.buffer.pop()?;
+        self.read = self.read.wrapping_add(1);
+        Some(byte)
+    }
+
+    /// Remove the positions of patterns which have (partly) been read
+    fn discard_read_patterns(&mut self) {
+        while let Some(position) = self.patterns.peek() {
+            // positions before the read position wrap to large values
+            if position.wrapping_sub(self.read) <= self.buffer.len() {
+                break;
+            }
+            self.patterns.pop();
+        }
+    }
+
+    /// Report (and reset) errors: line errors (including hardware FIFO overflow) take
+    /// precedence over software buffer overrun
+    fn take_error(&mut self) -> Result<(), Error> {
+        if let Some(error) = self.line_error.take() {
+            Err(error)
+        } else if self.buffer_overrun {
+            self.buffer_overrun = false;
+            Err(Error::BufferOverrun)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Reading returns the line errors of the receiver (see [Rx]), including [Error::Overrun] when
+/// the hardware FIFO overflowed, and [Error::BufferOverrun] when the software buffer overran
+/// since the last read. Reading can continue after the error is returned.
+///
+/// *Note: errors are reported when detected, which can be before older data still waiting in
+/// the buffer is read.*
+impl<UART: Instance> serial::Read<u8> for BufferedRx<UART> {
+    type Error = Error;
+
+    fn read(&mut self) -> nb::Result<u8, Self::Error> {
+        self.take_error()?;
+
+        if self.buffer.is_empty() {
+            self.fill_buffer();
+        }
+
+        self.pop().ok_or(nb::Error::WouldBlock)
+    }
+}
+
+/// Buffered serial transmitter
+pub struct BufferedTx<UART: Instance> {
+    tx: Tx<UART>,
+    buffer: RingBuffer<&'static mut [u8]>,
+    listening: bool,
+}
+
+impl<UART: Instance> BufferedTx<UART> {
+    /// Create a new buffered transmitter
+    ///
+    /// Listens to the [Txe](Event::Txe) event while data is waiting in the buffer.
+    /// The UART interrupt still needs to be enabled.
+    pub fn new(tx: Tx<UART>, buffer: &'static mut [u8]) -> Self {
+        BufferedTx {
+            tx,
+            buffer: RingBuffer::new(buffer),
+            listening: false,
+        }
+    }
+
+    /// Get count of bytes waiting in the transmit buffer
+    pub fn count(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Move bytes waiting in the ring buffer to the FIFO
+    ///
+    /// To be called from the UART interrupt handler.
+    pub fn handle_interrupt(&mut self) {
+        if self.tx.is_interrupt_pending(Event::Txe) {
+            self.fill_fifo();
+            self.tx.clear_interrupt(Event::Txe);
+        }
+    }
+
+    /// Stop listening to the interrupt events and release the transmitter
+    ///
+    /// Data still waiting in the buffer is discarded.
+    pub fn release(mut self) -> Tx<UART> {
+        self.tx.unlisten(Event::Txe);
+        self.tx
+    }
+
+    fn fill_fifo(&mut self) {
+        while self.tx.count() < UART_FIFO_SIZE {
+            let byte = match self.buffer.as_slices().0.first() {
+                Some(&byte) => byte,
+                None => break,
+            };
+            // UART0 is shared with the debug log: keep the byte while the other core is writing
+            // a message
+            if self.tx.write(byte).is_err() {
+                break;
+            }
+            self.buffer.discard(1);
+        }
+
+        // only listen while there is data waiting
+        if self.buffer.is_empty() == self.listening {
+            self.listening = !self.listening;
+            if self.listening {
+                self.tx.listen(Event::Txe);
+            } else {
+                self.tx.unlisten(Event::Txe);
+            }
+        }
+    }
+}
+
+impl<UART: Instance> serial::Write<u8> for BufferedTx<UART> {
+    type Error = Error;
+
+    fn flush(&mut self) -> nb::Result<(), Self::Error> {
+        self.fill_fifo();
+
+        // flushing the transmitter also disables the RS-485 driver or IrDA transmitter
+        if self.buffer.is_empty() && self.tx.flush().is_ok() {
+            Ok(())
+        } else {
+            Err(nb::Error::WouldBlock)
+        }
+    }
+
+    fn write(&mut self, byte: u8) -> nb::Result<(), Self::Error> {
+        if self.buffer.is_full() {
+            self.fill_fifo();
+        }
+
+        if self.buffer.push(byte) {
+            self.fill_fifo();
+            Ok(())
+        } else {
+            Err(nb::Error::WouldBlock)
+        }
+    }
+}
+
+impl<UART: Instance> core::fmt::Write for BufferedTx<UART> {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        s.as_bytes()
+            .iter()
+            .try_for_each(|c| nb::block!(self.write(*c)))
+            .map_err(|_| core::fmt::Error)
+    }
+}
+
+/// Buffered serial abstraction
+pub struct BufferedSerial<UART: Instance> {
+    tx: BufferedTx<UART>,
+    rx: BufferedRx<UART>,
+}
+
+impl<UART: Instance> BufferedSerial<UART> {
+    /// Create a new buffered serial driver from the transmitter and receiver
+    pub fn new(
+        tx: Tx<UART>,
+        rx: Rx<UART>,
+        tx_buffer: &'static mut [u8],
+        rx_buffer: &'static mut [u8],
+    ) -> Self {
+        BufferedSerial {
+            tx: BufferedTx::new(tx, tx_buffer),
+            rx: BufferedRx::new(rx, rx_buffer),
+        }
+    }
+
+    /// Move data between the FIFOs and the ring buffers
+    ///
+    /// To be called from the UART interrupt handler.
+    pub fn handle_interrupt(&mut self) {
+        self.rx.handle_interrupt();
+        self.tx.handle_interrupt();
+    }
+
+    /// Split the buffered serial driver in separate TX and RX drivers
+    pub fn split(self) -> (BufferedTx<UART>, BufferedRx<UART>) {
+        (self.tx, self.rx)
+    }
+
+    /// Release the transmitter and receiver
+    pub fn release(self) -> (Tx<UART>, Rx<UART>) {
+        (self.tx.release(), self.rx.release())
+    }
+}
+
+impl<UART: Instance> serial::Read<u8> for BufferedSerial<UART> {
+    type Error = Error;
+
+    fn read(&mut self) -> nb::Result<u8, Self::Error> {
+        self.rx.read()
+    }
+}
+
+impl<UART: Instance> serial::Write<u8> for BufferedSerial<UART> {
+    type Error = Error;
+
+    fn flush(&mut self) -> nb::Result<(), Self::Error> {
+        self.tx.flush()
+    }
+
+    fn write(&mut self, byte: u8) -> nb::Result<(), Self::Error> {
+        self.tx.write(byte)
+    }
+}
+
+impl<UART: Instance> core::fmt::Write for BufferedSerial<UART> {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        core::fmt::Write::write_str(&mut self.tx, s)
+    }
+}
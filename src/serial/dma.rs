@@ -0,0 +1,576 @@
+//! UART DMA transfers via UHCI
+//!
+//! The UHCI peripherals (UHCI0 and UHCI1) connect a UART to a DMA engine, which moves data
+//! between memory and the UART FIFOs without involvement of the CPU. This allows continuous
+//! transfers at high baudrates.
+//!
+//! Transmitting sends a complete `&'static` buffer in the background, returning a
+//! [TxTransfer] handle which can be polled or waited for. Receiving is done circularly into a
+//! `&'static` buffer: the DMA engine keeps filling the buffer, while the received data is read
+//! via the returned [CircularRx] handle.
+//!
+//! The buffers and [descriptors](crate::dma::Descriptor) must be located in internal data RAM,
+//! see the [dma](crate::dma) module.
+//!
+//! # Framing
+//!
+//! Without framing, the received data is handed over when a descriptor is full or when the
+//! receiver is idle for longer than the RX timeout of the [Serial](super::Serial).
+//!
+//! With [Framing] enabled, the UHCI performs SLIP style framing in hardware: each transmitted
+//! buffer is sent as a frame enclosed in separator characters, with separator and escape
+//! characters in the data replaced by escape sequences. Received frames are unescaped and can be
+//! read one by one via [CircularRx::read_frame].
+//!
+//! # Example
+//! ```
+//! static mut TX_DESCRIPTORS: [Descriptor; 2] = [Descriptor::new(); 2];
+//! static mut RX_DESCRIPTORS: [Descriptor; 8] = [Descriptor::new(); 8];
+//! static mut TX_BUFFER: [u8; 8000] = [0; 8000];
+//! static mut RX_BUFFER: [u8; 1024] = [0; 1024];
+//!
+//! let (tx, rx) = serial.split();
+//! let uhci = Uhci::new(
+//!     dp.UHCI0,
+//!     tx,
+//!     rx,
+//!     unsafe { &mut TX_DESCRIPTORS },
+//!     unsafe { &mut RX_DESCRIPTORS },
+//!     Some(Framing::default()),
+//! )
+//! .unwrap();
+//! let (uhci_tx, uhci_rx) = uhci.split();
+//!
+//! let mut transfer = uhci_tx.write(unsafe { &TX_BUFFER }).unwrap();
+//! transfer.wait().unwrap();
+//! let uhci_tx = transfer.release().0;
+//!
+//! let mut receiver = uhci_rx.read_circular(unsafe { &mut RX_BUFFER }).unwrap();
+//! let mut frame = [0u8; 256];
+//! let length = nb::block!(receiver.read_frame(&mut frame)).unwrap();
+//! ```
+
+use super::{private::Instance, Rx, Tx};
+use crate::dma::{self, Descriptor, Error};
+use core::marker::PhantomData;
+use core::sync::atomic::{compiler_fence, Ordering};
+
+pub use private::UhciInstance;
+
+// bit positions in the UHCI interrupt registers
+const IN_SUC_EOF: u32 = 1 << 5;
+const IN_DSCR_ERR: u32 = 1 << 9;
+const OUT_DSCR_ERR: u32 = 1 << 10;
+const IN_DSCR_EMPTY: u32 = 1 << 11;
+const OUT_TOTAL_EOF: u32 = 1 << 13;
+
+/// SLIP style separator framing
+///
+/// The default is SLIP (RFC 1055): separator 0xC0, escape sequences 0xDB 0xDC for the separator
+/// and 0xDB 0xDD for the escape character.
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub struct Framing {
+    /// Character separating the frames
+    pub separator: u8,
+    /// Escape character, first character of the escape sequences
+    pub escape: u8,
+    /// Second character of the escape sequence replacing the separator
+    pub escaped_separator: u8,
+    /// Second character of the escape sequence replacing the escape character
+    pub escaped_escape: u8,
+}
+
+impl Default for Framing {
+    fn default() -> Framing {
+        Framing {
+            separator: 0xC0,
+            escape: 0xDB,
+            escaped_separator: 0xDC,
+            escaped_escape: 0xDD,
+        }
+    }
+}
+
+/// UART DMA driver
+pub struct Uhci<UHCI: UhciInstance, UART: Instance> {
+    uhci: UHCI,
+    tx: UhciTx<UHCI, UART>,
+    rx: UhciRx<UHCI, UART>,
+}
+
+/// UART DMA transmitter
+pub struct UhciTx<UHCI: UhciInstance, UART: Instance> {
+    _uhci: PhantomData<UHCI>,
+    tx: Tx<UART>,
+    descriptors: &'static mut [Descriptor],
+}
+
+/// UART DMA receiver
+pub struct UhciRx<UHCI: UhciInstance, UART: Instance> {
+    _uhci: PhantomData<UHCI>,
+    rx: Rx<UART>,
+    descriptors: &'static mut [Descriptor],
+}
+
+impl<UHCI: UhciInstance, UART: Instance> Uhci<UHCI, UART> {
+    /// Create a new UART DMA driver
+    ///
+    /// The descriptors determine the maximum transmit buffer size
+    /// (see [descriptors_needed](crate::dma::descriptors_needed)) and the granularity of the
+    /// receive buffer: the receive buffer is divided evenly over the receive descriptors and
+    /// each frame occupies at least one descriptor.
+    ///
+    /// *Note: the receiver and transmitter are used by the DMA engine, they should not be used
+    /// directly anymore.*
+    pub fn new(
+        mut uhci: UHCI,
+        tx: Tx<UART>,
+        rx: Rx<UART>,
+        tx_descriptors: &'static mut [Descriptor],
+        rx_descriptors: &'static mut [Descriptor],
+        framing: Option<Framing>,
+    ) -> Result<Self, Error> {
+        for descriptors in [&tx_descriptors[..], &rx_descriptors[..]].iter() {
+            if descriptors.is_empty() {
+                return Err(Error::TooFewDescriptors);
+            }
+            if !dma::is_dma_capable(
+                descriptors.as_ptr() as usize,
+                descriptors.len() * core::mem::size_of::<Descriptor>(),
+            ) {
+                return Err(Error::NotDmaCapable);
+            }
+        }
+
+        uhci.reset().enable();
+
+        uhci.conf0.modify(|_, w| {
+            w.in_rst()
+                .set_bit()
+                .out_rst()
+                .set_bit()
+                .ahbm_rst()
+                .set_bit()
+                .ahbm_fifo_rst()
+                .set_bit()
+        });
+        uhci.conf0.modify(|_, w| {
+            w.in_rst()
+                .clear_bit()
+                .out_rst()
+                .clear_bit()
+                .ahbm_rst()
+                .clear_bit()
+                .ahbm_fifo_rst()
+                .clear_bit()
+        });
+
+        uhci.conf0.write(|w| {
+            let w = w
+                .clk_en()
+                .set_bit()
+                .head_en()
+                .clear_bit()
+                .crc_rec_en()
+                .clear_bit()
+                .encode_crc_en()
+                .clear_bit()
+                .len_eof_en()
+                .clear_bit()
+                .seper_en()
+                .bit(framing.is_some())
+                // without framing hand over the received data when the receiver is idle
+                .uart_idle_eof_en()
+                .bit(framing.is_none());
+
+            match UART::index() {
+                0 => w.uart0_ce().set_bit(),
+                1 => w.uart1_ce().set_bit(),
+                _ => w.uart2_ce().set_bit(),
+            }
+        });
+
+        // stop at descriptors not yet released by the CPU instead of overwriting data
+        uhci.conf1.modify(|_, w| {
+            w.check_owner()
+                .set_bit()
+                .check_sum_en()
+                .clear_bit()
+                .check_seq_en()
+                .clear_bit()
+                .tx_check_sum_re()
+                .clear_bit()
+                .tx_ack_num_re()
+                .clear_bit()
+        });
+
+        if let Some(framing) = framing {
+            uhci.esc_conf0.write(|w| unsafe {
+                w.seper_char()
+                    .bits(framing.separator)
+                    .seper_esc_char0()
+                    .bits(framing.escape)
+                    .seper_esc_char1()
+                    .bits(framing.escaped_separator)
+            });
+            uhci.esc_conf1.write(|w| unsafe {
+                w.esc_seq0()
+                    .bits(framing.escape)
+                    .esc_seq0_char0()
+                    .bits(framing.escape)
+                    .esc_seq0_char1()
+                    .bits(framing.escaped_escape)
+            });
+            uhci.escape_conf.write(|w| {
+                w.tx_c0_esc_en()
+                    .set_bit()
+                    .tx_db_esc_en()
+                    .set_bit()
+                    .rx_c0_esc_en()
+                    .set_bit()
+                    .rx_db_esc_en()
+                    .set_bit()
+            });
+        } else {
+            uhci.escape_conf.write(|w| unsafe { w.bits(0) });
+        }
+
+        uhci.int_ena.write(|w| unsafe { w.bits(0) });
+        uhci.int_clr.write(|w| unsafe { w.bits(0xffff_ffff) });
+
+        Ok(Uhci {
+            uhci,
+            tx: UhciTx {
+                _uhci: PhantomData,
+                tx,
+                descriptors: tx_descriptors,
+            },
+            rx: UhciRx {
+                _uhci: PhantomData,
+                rx,
+                descriptors: rx_descriptors,
+            },
+        })
+    }
+
+    /// Split the driver in separate TX and RX drivers
+    pub fn split(self) -> (UhciTx<UHCI, UART>, UhciRx<UHCI, UART>) {
+        (self.tx, self.rx)
+    }
+
+    /// Disable the UHCI and release the resources
+    pub fn release(
+        mut self,
+    ) -> (
+        UHCI,
+        Tx<UART>,
+        Rx<UART>,
+        &'static mut [Descriptor],
+        &'static mut [Descriptor],
+    ) {
+        self.uhci.disable();
+        (
+            self.uhci,
+            self.tx.tx,
+            self.rx.rx,
+            self.tx.descriptors,
+            self.rx.descriptors,
+        )
+    }
+}
+
+impl<UHCI: UhciInstance, UART: Instance> UhciTx<UHCI, UART> {
+    /// Start sending the buffer in the background
+    ///
+    /// With framing enabled, the buffer is sent as a single frame.
+    pub fn write(self, buffer: &'static [u8]) -> Result<TxTransfer<UHCI, UART>, Error> {
+        dma::link_tx(self.descriptors, buffer)?;
+
+        let uhci = unsafe { &*UHCI::ptr() };
+
+        uhci.conf0.modify(|_, w| w.out_rst().set_bit());
+        uhci.conf0.modify(|_, w| w.out_rst().clear_bit());
+        uhci.int_clr
+            .write(|w| unsafe { w.bits(OUT_TOTAL_EOF | OUT_DSCR_ERR) });
+
+        let address = self.descriptors[0].address();
+        uhci.dma_out_link.modify(|_, w| unsafe {
+            w.outlink_addr()
+                .bits(address & 0xfffff)
+                .outlink_start()
+                .set_bit()
+        });
+
+        Ok(TxTransfer { tx: self, buffer })
+    }
+}
+
+/// Background transmit transfer
+pub struct TxTransfer<UHCI: UhciInstance, UART: Instance> {
+    tx: UhciTx<UHCI, UART>,
+    buffer: &'static [u8],
+}
+
+impl<UHCI: UhciInstance, UART: Instance> TxTransfer<UHCI, UART> {
+    /// Check if the transfer has finished
+    ///
+    /// The transfer has finished when all data has been sent by the UART.
+    pub fn poll(&mut self) -> nb::Result<(), Error> {
+        let raw = unsafe { (*UHCI::ptr()).int_raw.read().bits() };
+
+        if raw & OUT_DSCR_ERR != 0 {
+            return Err(nb::Error::Other(Error::DescriptorError));
+        }
+
+        if raw & OUT_TOTAL_EOF != 0 && self.tx.tx.count() == 0 && self.tx.tx.is_idle() {
+            Ok(())
+        } else {
+            Err(nb::Error::WouldBlock)
+        }
+    }
+
+    /// Returns true if the transfer has finished (or failed)
+    pub fn is_done(&mut self) -> bool {
+        match self.poll() {
+            Err(nb::Error::WouldBlock) => false,
+            _ => true,
+        }
+    }
+
+    /// Wait for the transfer to finish
+    pub fn wait(&mut self) -> Result<(), Error> {
+        nb::block!(self.poll())
+    }
+
+    /// Release the transmitter and buffer, aborting the transfer if it is still running
+    pub fn release(self) -> (UhciTx<UHCI, UART>, &'static [u8]) {
+        unsafe {
+            (*UHCI::ptr())
+                .dma_out_link
+                .modify(|_, w| w.outlink_stop().set_bit())
+        };
+        (self.tx, self.buffer)
+    }
+}
+
+impl<UHCI: UhciInstance, UART: Instance> UhciRx<UHCI, UART> {
+    /// Start receiving circularly into the buffer
+    ///
+    /// The buffer must be word aligned and its length a multiple of 4.
+    pub fn read_circular(self, buffer: &'static mut [u8]) -> Result<CircularRx<UHCI, UART>, Error> {
+        let (count, chunk_size) = dma::link_rx(self.descriptors, buffer, true)?;
+
+        let uhci = unsafe { &*UHCI::ptr() };
+
+        uhci.conf0.modify(|_, w| w.in_rst().set_bit());
+        uhci.conf0.modify(|_, w| w.in_rst().clear_bit());
+        uhci.int_clr
+            .write(|w| unsafe { w.bits(IN_SUC_EOF | IN_DSCR_ERR | IN_DSCR_EMPTY) });
+
+        let address = self.descriptors[0].address();
+        uhci.dma_in_link.modify(|_, w| unsafe {
+            w.inlink_addr()
+                .bits(address & 0xfffff)
+                .inlink_start()
+                .set_bit()
+        });
+
+        Ok(CircularRx {
+            rx: self,
+            buffer,
+            count,
+            chunk_size,
+            index: 0,
+            offset: 0,
+        })
+    }
+}
+
+/// Circular receive transfer
+///
+/// When the buffer is full (all descriptors contain data which has not been read) the DMA engine
+/// pauses until data is read. Data is then buffered in the UART RX FIFO, which can overflow
+/// (see [Error::Overrun](super::Error::Overrun)).
+pub struct CircularRx<UHCI: UhciInstance, UART: Instance> {
+    rx: UhciRx<UHCI, UART>,
+    buffer: &'static mut [u8],
+    /// Number of descriptors in use
+    count: usize,
+    /// Number of bytes per descriptor
+    chunk_size: usize,
+    /// Descriptor to read next
+    index: usize,
+    /// Number of bytes already read from the current descriptor
+    offset: usize,
+}
+
+impl<UHCI: UhciInstance, UART: Instance> CircularRx<UHCI, UART> {
+    /// Get the number of received bytes available for reading
+    pub fn available(&self) -> usize {
+        let mut available = 0;
+        let mut offset = self.offset;
+        for index in (self.index..self.count).chain(0..self.index) {
+            let descriptor = &self.rx.descriptors[index];
+            if descriptor.is_owned_by_dma() {
+                break;
+            }
+            available += descriptor.length() - offset;
+            offset = 0;
+        }
+        available
+    }
+
+    /// Read as many bytes as available (up to the length of data)
+    ///
+    /// Returns the number of bytes read.
+    pub fn read(&mut self, data: &mut [u8]) -> usize {
+        let mut count = 0;
+
+        while count < data.len() {
+            let descriptor = &self.rx.descriptors[self.index];
+            if descriptor.is_owned_by_dma() {
+                break;
+            }
+            // do not read the buffer before the descriptor has been handed over
+            compiler_fence(Ordering::SeqCst);
+
+            let length = descriptor.length();
+            let size = core::cmp::min(length - self.offset, data.len() - count);
+            let start = self.index * self.chunk_size + self.offset;
+
+            data[count..count + size].copy_from_slice(&self.buffer[start..start + size]);
+            count += size;
+            self.offset += size;
+
+            if self.offset == length {
+                self.release_descriptor();
+            }
+        }
+
+        count
+    }
+
+    /// Read a complete frame (when framing is enabled)
+    ///
+    /// Returns the length of the frame. When the frame does not fit in data, it is discarded and
+    /// [Error::BufferTooSmall] is returned.
+    pub fn read_frame(&mut self, data: &mut [u8]) -> nb::Result<usize, Error> {
+        let mut length = 0;
+        let mut offset = self.offset;
+        let mut index = self.index;
+
+        // find the end of the frame
+        loop {
+            let descriptor = &self.rx.descriptors[index];
+            if descriptor.is_owned_by_dma() {
+                return Err(nb::Error::WouldBlock);
+            }
+            length += descriptor.length() - offset;
+            offset = 0;
+
+            if descriptor.is_eof() {
+                break;
+            }
+
+            index = (index + 1) % self.count;
+            if index == self.index {
+                // the complete buffer is filled with a single frame
+                self.discard(length);
+                return Err(nb::Error::Other(Error::BufferTooSmall));
+            }
+        }
+
+        if length > data.len() {
+            self.discard(length);
+            return Err(nb::Error::Other(Error::BufferTooSmall));
+        }
+
+        Ok(self.read(&mut data[..length]))
+    }
+
+    /// Stop receiving and release the receiver and buffer
+    pub fn stop(self) -> (UhciRx<UHCI, UART>, &'static mut [u8]) {
+        unsafe {
+            (*UHCI::ptr())
+                .dma_in_link
+                .modify(|_, w| w.inlink_stop().set_bit())
+        };
+        (self.rx, self.buffer)
+    }
+
+    /// Discard received data
+    fn discard(&mut self, mut length: usize) {
+        while length > 0 {
+            let descriptor = &self.rx.descriptors[self.index];
+            let size = core::cmp::min(descriptor.length() - self.offset, length);
+            length -= size;
+            self.offset += size;
+            if self.offset == descriptor.length() {
+                self.release_descriptor();
+            }
+        }
+    }
+
+    /// Hand the current descriptor back to the DMA engine
+    fn release_descriptor(&mut self) {
+        self.rx.descriptors[self.index].release_to_dma();
+        self.index = (self.index + 1) % self.count;
+        self.offset = 0;
+
+        // restart the DMA engine if it paused because the buffer was full
+        let uhci = unsafe { &*UHCI::ptr() };
+        if uhci.int_raw.read().bits() & (IN_DSCR_EMPTY | IN_DSCR_ERR) != 0 {
+            uhci.int_clr
+                .write(|w| unsafe { w.bits(IN_DSCR_EMPTY | IN_DSCR_ERR) });
+            uhci.dma_in_link.modify(|_, w| w.inlink_restart().set_bit());
+        }
+    }
+}
+
+mod private {
+    use crate::dport::{self, Peripheral};
+    use crate::target::{uhci0, UHCI0, UHCI1};
+    use core::ops::Deref;
+
+    pub trait UhciInstance: Deref<Target = uhci0::RegisterBlock> {
+        fn ptr() -> *const uhci0::RegisterBlock;
+        /// Enable peripheral
+        fn enable(&mut self) -> &mut Self;
+        /// Disable peripheral
+        fn disable(&mut self) -> &mut Self;
+        /// Reset peripheral
+        fn reset(&mut self) -> &mut Self;
+    }
+
+    macro_rules! halUhci {
+        ($($UHCIX:ident,)+) => {
+            $(
+                impl UhciInstance for $UHCIX {
+                    fn ptr() -> *const uhci0::RegisterBlock {
+                        $UHCIX::ptr()
+                    }
+
+                    fn enable(&mut self) -> &mut Self {
+                        dport::enable_peripheral(Peripheral::$UHCIX);
+                        self
+                    }
+
+                    fn disable(&mut self) -> &mut Self {
+                        dport::disable_peripheral(Peripheral::$UHCIX);
+                        self
+                    }
+
+                    fn reset(&mut self) -> &mut Self {
+                        dport::reset_peripheral(Peripheral::$UHCIX);
+                        self
+                    }
+                }
+            )+
+        }
+    }
+
+    halUhci! {
+        UHCI0,
+        UHCI1,
+    }
+}
@@ -0,0 +1,105 @@
+//! LIN frame format: protected identifiers and checksums
+//!
+//! *Note: this module does not depend on the hardware, so it is also compiled and tested on the
+//! host (see the `host-tests` directory).*
+
+/// Value of the sync field
+pub const SYNC: u8 = 0x55;
+
+/// Length of the BREAK sent by the master in bit times
+pub const BREAK_BITS: u8 = 13;
+
+/// Maximum number of data bytes in a frame
+pub const MAX_DATA_LENGTH: usize = 8;
+
+/// LIN errors
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum Error {
+    /// Error of the underlying serial interface
+    Serial(crate::serial::Error),
+    /// Invalid identifier (must be 0-63)
+    InvalidId,
+    /// Invalid data length (must be 1-8 bytes)
+    InvalidLength,
+    /// Parity error in the protected identifier
+    IdParity,
+    /// Invalid or missing sync field
+    Sync,
+    /// Checksum mismatch
+    Checksum,
+    /// Received identifier differs from the transmitted identifier
+    IdMismatch,
+    /// No (complete) response within the timeout
+    Timeout,
+}
+
+impl From<crate::serial::Error> for Error {
+    fn from(error: crate::serial::Error) -> Self {
+        Error::Serial(error)
+    }
+}
+
+/// Checksum model
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum ChecksumType {
+    /// LIN 1.x checksum over the data bytes only
+    Classic,
+    /// LIN 2.x checksum over the protected identifier and the data bytes
+    ///
+    /// *Note: diagnostic frames (identifiers 0x3c and 0x3d) always use the classic checksum.*
+    Enhanced,
+}
+
+/// Calculate the protected identifier (identifier with parity bits) of an identifier
+pub fn protected_id(id: u8) -> Result<u8, Error> {
+    if id > 0x3f {
+        return Err(Error::InvalidId);
+    }
+
+    let bit = |n: u8| (id >> n) & 1;
+    let p0 = bit(0) ^ bit(1) ^ bit(2) ^ bit(4);
+    let p1 = !(bit(1) ^ bit(3) ^ bit(4) ^ bit(5)) & 1;
+
+    Ok(id | (p0 << 6) | (p1 << 7))
+}
+
+/// Extract the identifier from a protected identifier, checking the parity bits
+pub fn id_from_protected_id(pid: u8) -> Result<u8, Error> {
+    let id = pid & 0x3f;
+    if protected_id(id)? == pid {
+        Ok(id)
+    } else {
+        Err(Error::IdParity)
+    }
+}
+
+/// Inverted sum with carry of the data, starting from `initial`
+fn inverted_sum_with_carry(initial: u8, data: &[u8]) -> u8 {
+    let sum = data.iter().fold(initial as u16, |sum, &byte| {
+        let sum = sum + byte as u16;
+        if sum > 0xff {
+            sum - 0xff
+        } else {
+            sum
+        }
+    });
+    !(sum as u8)
+}
+
+/// Calculate the classic checksum (LIN 1.x) of the data
+pub fn classic_checksum(data: &[u8]) -> u8 {
+    inverted_sum_with_carry(0, data)
+}
+
+/// Calculate the enhanced checksum (LIN 2.x) of the protected identifier and the data
+pub fn enhanced_checksum(pid: u8, data: &[u8]) -> u8 {
+    inverted_sum_with_carry(pid, data)
+}
+
+/// Calculate the checksum of a frame
+pub fn checksum(checksum_type: ChecksumType, pid: u8, data: &[u8]) -> u8 {
+    match checksum_type {
+        ChecksumType::Classic => classic_checksum(data),
+        ChecksumType::Enhanced => enhanced_checksum(pid, data),
+    }
+}
@@ -0,0 +1,249 @@
+//! LIN bus frame layer
+//!
+//! Implements LIN master and slave frames on top of the UART: BREAK, sync field, protected
+//! identifier, data and checksum. The baudrate (typically 9600 or 19200 baud, 8N1) is
+//! configured on the [Serial](super::Serial) before splitting it.
+//!
+//! The transceiver is expected to echo the transmitted data to the receiver (as all single-wire
+//! LIN transceivers do). The echo is discarded.
+//!
+//! # Example
+//! ```
+//! let (tx, rx) = serial.split();
+//! let mut lin = Lin::new(tx, rx, 10.ms());
+//!
+//! // master request: read 2 bytes from the slave responding to id 0x10
+//! let mut data = [0u8; 2];
+//! lin.read_frame(0x10, &mut data, ChecksumType::Enhanced).unwrap();
+//! ```
+
+use super::{private::Instance, Rx, Tx};
+use crate::prelude::*;
+use embedded_hal::serial::{Read, Write};
+
+mod frame;
+
+pub use frame::{
+    checksum, classic_checksum, enhanced_checksum, id_from_protected_id, protected_id,
+    ChecksumType, Error, BREAK_BITS, MAX_DATA_LENGTH, SYNC,
+};
+
+/// Polling interval while waiting for data
+const POLL_INTERVAL: MicroSeconds = MicroSeconds(10);
+
+/// Maximum number of bytes skipped while waiting for the sync field
+const MAX_SKIPPED_BYTES: usize = 4;
+
+/// Minimum time a received byte takes, at the maximum LIN baudrate of 20kbaud
+const MIN_BYTE_TIME: MicroSeconds = MicroSeconds(500);
+
+/// LIN interface
+pub struct Lin<UART: Instance> {
+    tx: Tx<UART>,
+    rx: Rx<UART>,
+    timeout: MicroSeconds,
+}
+
+impl<UART: Instance> Lin<UART> {
+    /// Create a new LIN interface
+    ///
+    /// The timeout is the maximum time to wait for a header or a response.
+    pub fn new<T: Into<MicroSeconds>>(tx: Tx<UART>, rx: Rx<UART>, timeout: T) -> Self {
+        Lin {
+            tx,
+            rx,
+            timeout: timeout.into(),
+        }
+    }
+
+    /// Release the transmitter and receiver
+    pub fn release(self) -> (Tx<UART>, Rx<UART>) {
+        (self.tx, self.rx)
+    }
+
+    /// Master: send a frame with data to the slaves
+    pub fn write_frame(
+        &mut self,
+        id: u8,
+        data: &[u8],
+        checksum_type: ChecksumType,
+    ) -> Result<(), Error> {
+        check_length(data.len())?;
+        let pid = self.write_header(id)?;
+        self.write_response_internal(pid, data, checksum_type)
+    }
+
+    /// Master: request a frame with data from a slave
+    pub fn read_frame(
+        &mut self,
+        id: u8,
+        data: &mut [u8],
+        checksum_type: ChecksumType,
+    ) -> Result<(), Error> {
+        check_length(data.len())?;
+        let pid = self.write_header(id)?;
+        self.read_response_internal(pid, data, checksum_type)
+    }
+
+    /// Slave: wait for a header and return the identifier
+    ///
+    /// Returns [Error::Timeout] if no complete header is received within the timeout, also
+    /// when other data keeps arriving.
+    pub fn read_header(&mut self) -> Result<u8, Error> {
+        let mut remaining = u32::from(self.timeout);
+
+        // wait for the BREAK
+        loop {
+            match self.read_byte_within(&mut remaining) {
+                Err(Error::Serial(super::Error::Break)) => break,
+                Err(Error::Timeout) => return Err(Error::Timeout),
+                _ => {}
+            }
+        }
+
+        self.read_sync(&mut remaining)?;
+        id_from_protected_id(self.read_byte_within(&mut remaining)?)
+    }
+
+    /// Slave: send the response to a header
+    pub fn write_response(
+        &mut self,
+        id: u8,
+        data: &[u8],
+        checksum_type: ChecksumType,
+    ) -> Result<(), Error> {
+        check_length(data.len())?;
+        self.write_response_internal(protected_id(id)?, data, checksum_type)
+    }
+
+    /// Slave: receive the data following a header
+    pub fn read_response(
+        &mut self,
+        id: u8,
+        data: &mut [u8],
+        checksum_type: ChecksumType,
+    ) -> Result<(), Error> {
+        check_length(data.len())?;
+        self.read_response_internal(protected_id(id)?, data, checksum_type)
+    }
+
+    /// Send the header (BREAK, sync and protected identifier) and discard its echo
+    fn write_header(&mut self, id: u8) -> Result<u8, Error> {
+        let pid = protected_id(id)?;
+
+        // discard stale data and errors
+        loop {
+            if let Err(nb::Error::WouldBlock) = self.rx.read() {
+                break;
+            }
+        }
+
+        self.tx.send_break(BREAK_BITS, self.timeout)?;
+        self.write_bytes(&[SYNC, pid])?;
+
+        // the BREAK is echoed as BREAK/framing error and a 0 byte
+        let mut remaining = u32::from(self.timeout);
+        self.read_sync(&mut remaining)?;
+        if self.read_byte()? != pid {
+            return Err(Error::IdMismatch);
+        }
+
+        Ok(pid)
+    }
+
+    fn write_response_internal(
+        &mut self,
+        pid: u8,
+        data: &[u8],
+        checksum_type: ChecksumType,
+    ) -> Result<(), Error> {
+        self.write_bytes(data)?;
+        self.write_bytes(&[checksum(checksum_type, pid, data)])?;
+
+        // discard the echo
+        for _ in 0..=data.len() {
+            self.read_byte()?;
+        }
+
+        Ok(())
+    }
+
+    fn read_response_internal(
+        &mut self,
+        pid: u8,
+        data: &mut [u8],
+        checksum_type: ChecksumType,
+    ) -> Result<(), Error> {
+        for byte in data.iter_mut() {
+            *byte = self.read_byte()?;
+        }
+
+        if self.read_byte()? != checksum(checksum_type, pid, data) {
+            return Err(Error::Checksum);
+        }
+
+        Ok(())
+    }
+
+    fn write_bytes(&mut self, data: &[u8]) -> Result<(), Error> {
+        for &byte in data {
+            nb::block!(self.tx.write(byte)).ok();
+        }
+        nb::block!(self.tx.flush()).ok();
+        Ok(())
+    }
+
+    /// Skip the 0 byte and errors caused by the BREAK and read the sync field
+    fn read_sync(&mut self, remaining: &mut u32) -> Result<(), Error> {
+        for _ in 0..MAX_SKIPPED_BYTES {
+            match self.read_byte_within(remaining) {
+                Ok(SYNC) => return Ok(()),
+                Ok(0) => {}
+                Err(Error::Serial(super::Error::Break))
+                | Err(Error::Serial(super::Error::Framing)) => {}
+                Ok(_) => return Err(Error::Sync),
+                Err(error) => return Err(error),
+            }
+        }
+        Err(Error::Sync)
+    }
+
+    /// Read a byte, waiting at most the timeout
+    fn read_byte(&mut self) -> Result<u8, Error> {
+        let mut remaining = u32::from(self.timeout);
+        self.read_byte_within(&mut remaining)
+    }
+
+    /// Read a byte, waiting at most `remaining` microseconds
+    ///
+    /// `remaining` is reduced by the time waited and by the minimum time of the received byte,
+    /// so a sequence of reads ends when data keeps arriving.
+    fn read_byte_within(&mut self, remaining: &mut u32) -> Result<u8, Error> {
+        loop {
+            if *remaining == 0 {
+                return Err(Error::Timeout);
+            }
+
+            let result = match self.rx.read() {
+                Ok(byte) => Ok(byte),
+                Err(nb::Error::Other(error)) => Err(error.into()),
+                Err(nb::Error::WouldBlock) => {
+                    crate::clock_control::sleep(POLL_INTERVAL);
+                    *remaining = remaining.saturating_sub(u32::from(POLL_INTERVAL));
+                    continue;
+                }
+            };
+
+            *remaining = remaining.saturating_sub(u32::from(MIN_BYTE_TIME));
+            return result;
+        }
+    }
+}
+
+fn check_length(length: usize) -> Result<(), Error> {
+    if length == 0 || length > MAX_DATA_LENGTH {
+        Err(Error::InvalidLength)
+    } else {
+        Ok(())
+    }
+}
@@ -0,0 +1,1643 @@
+//! UART peripheral control
+//!
+//! Controls the 3 uart peripherals (UART0, UART1, UART2).
+//!
+//! # Example
+//!
+//! Creation of the serial peripheral and writing formatted info.
+//! ```
+//! let serial: Serial<_, _, _> = Serial::new(
+//!     dp.UART0,
+//!     esp32_hal::serial::Pins {
+//!         tx: gpios.gpio1,
+//!         rx: gpios.gpio3,
+//!         cts: None,
+//!         rts: None,
+//!     },
+//!     config,
+//!     clkcntrl_config,
+//!     &mut dport,
+//!     )
+//!     .unwrap();
+//!
+//! writeln!(serial, "Serial output").unwrap();
+//! ```
+//!
+//! For interrupt driven communication with software buffers see [BufferedSerial].
+//!
+//! # TODO
+//! - Add all extra features esp32 supports
+//! - Free APB lock when TX is idle (and no RX used)
+//! - Address errata 3.17: UART fifo_cnt is inconsistent with FIFO pointer
+
+use core::{convert::Infallible, marker::PhantomData};
+
+use crate::clock_control::wakeup::{self, WakeupSource};
+use crate::gpio::{InputPin, OutputPin};
+use crate::prelude::*;
+
+use embedded_hal::serial;
+
+pub mod buffered;
+pub mod dma;
+pub mod lin;
+
+pub use buffered::{BufferedRx, BufferedSerial, BufferedTx};
+
+const UART_FIFO_SIZE: u8 = 128;
+
+/// Number of edges measured for automatic baudrate detection
+const AUTOBAUD_EDGE_COUNT: u16 = 10;
+/// Polling interval during automatic baudrate detection
+const AUTOBAUD_POLL_INTERVAL: MicroSeconds = MicroSeconds(100);
+/// Polling interval while sending a BREAK
+const BREAK_POLL_INTERVAL: MicroSeconds = MicroSeconds(10);
+
+/// Minimum number of RX edges to wake from light sleep
+const WAKEUP_THRESHOLD_MIN: u16 = 3;
+/// Maximum number of RX edges to wake from light sleep
+const WAKEUP_THRESHOLD_MAX: u16 = 0x3ff + 2;
+
+/// Serial error
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum Error {
+    /// Framing error
+    Framing,
+    /// Noise error
+    Noise,
+    /// RX FIFO overrun
+    Overrun,
+    /// Parity check error
+    Parity,
+    /// BREAK condition received
+    Break,
+    /// RS-485 collision detected
+    Collision,
+    /// Software receive buffer overrun
+    BufferOverrun,
+    /// Baudrate too low
+    BaudrateTooLow,
+    /// Baudrate too high
+    BaudrateTooHigh,
+    /// Timeout (e.g. during baudrate detection)
+    Timeout,
+    /// Wake-up from light sleep is only supported by UART0 and UART1
+    WakeupNotSupported,
+    /// Invalid wake-up threshold
+    InvalidWakeupThreshold,
+}
+
+/// Interrupt event
+///
+/// The value of each event is its bit position in the UART interrupt registers.
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum Event {
+    /// New data has been received (RX FIFO above the RX FIFO full threshold)
+    Rxne = 0,
+    /// New data can be sent (TX FIFO below the TX FIFO empty threshold)
+    Txe = 1,
+    /// Parity error detected
+    ParityError = 2,
+    /// Framing error detected
+    FramingError = 3,
+    /// RX FIFO overflow
+    Overflow = 4,
+    /// BREAK condition detected
+    Break = 7,
+    /// Idle line state detected (receiver idle for longer than the RX timeout)
+    Idle = 8,
+    /// XON character received (software flow control)
+    XonReceived = 9,
+    /// XOFF character received (software flow control)
+    XoffReceived = 10,
+    /// Transmitter done sending a BREAK
+    TxBreakDone = 12,
+    /// Transmitter done sending all data in the TX FIFO
+    TxDone = 14,
+    /// RS-485 collision between the transmitted and received data detected
+    Collision = 17,
+    /// Pattern detected (see [PatternDetection](config::PatternDetection))
+    PatternDetected = 18,
+}
+
+impl Event {
+    fn mask(self) -> u32 {
+        1 << self as u32
+    }
+}
+
+/// UART configuration
+pub mod config {
+    use crate::units::*;
+
+    /// Number of data bits
+    #[derive(PartialEq, Eq, Copy, Clone, Debug)]
+    pub enum DataBits {
+        DataBits5,
+        DataBits6,
+        DataBits7,
+        DataBits8,
+    }
+
+    /// Parity check
+    #[derive(PartialEq, Eq, Copy, Clone, Debug)]
+    pub enum Parity {
+        ParityNone,
+        ParityEven,
+        ParityOdd,
+    }
+
+    /// Number of stop bits
+    #[derive(PartialEq, Eq, Copy, Clone, Debug)]
+    pub enum StopBits {
+        /// 1 stop bit
+        STOP1,
+        /// 1.5 stop bits
+        STOP1P5,
+        /// 2 stop bits
+        STOP2,
+    }
+
+    /// Hardware flow control
+    #[derive(PartialEq, Eq, Copy, Clone, Debug)]
+    pub enum FlowControl {
+        /// No hardware flow control
+        None,
+        /// RTS is deasserted when the RX FIFO reaches the RX flow threshold
+        Rts,
+        /// Transmission is paused while CTS is deasserted
+        Cts,
+        /// Both RTS and CTS flow control
+        RtsCts,
+    }
+
+    /// Software (XON/XOFF) flow control configuration
+    ///
+    /// The transmitter pauses when XOFF is received and continues when XON is received.
+    /// The XON and XOFF characters are removed from the received data.
+    #[derive(PartialEq, Eq, Copy, Clone, Debug)]
+    pub struct SoftwareFlowControl {
+        /// Character sent to resume transmission by the other side
+        pub xon_char: u8,
+        /// Character sent to pause transmission by the other side
+        pub xoff_char: u8,
+        /// XON is sent when the RX FIFO drops below this number of bytes (at most 127)
+        pub xon_threshold: u8,
+        /// XOFF is sent when the RX FIFO exceeds this number of bytes (at most 127)
+        pub xoff_threshold: u8,
+    }
+
+    impl Default for SoftwareFlowControl {
+        fn default() -> SoftwareFlowControl {
+            SoftwareFlowControl {
+                xon_char: 0x11,
+                xoff_char: 0x13,
+                xon_threshold: 32,
+                xoff_threshold: 96,
+            }
+        }
+    }
+
+    /// RS-485 half-duplex configuration
+    ///
+    /// The RTS pin is used as driver enable (DE) of the transceiver: it is driven high while
+    /// transmitting and low while receiving.
+    ///
+    /// *Note: the ESP32 does not switch DE in hardware; the delays below only apply to the
+    /// transmitted signal. DE is driven by the driver and stays high after writing single bytes
+    /// until a successful [flush](embedded_hal::serial::Write::flush).*
+    #[derive(PartialEq, Eq, Copy, Clone, Debug)]
+    pub struct Rs485 {
+        /// Add a delay of one bit time between enabling the driver and the start bit
+        pub delay_before_tx: bool,
+        /// Add a delay of one bit time after the stop bit(s) before switching back to receive
+        pub delay_after_tx: bool,
+        /// Delay of the internal transmit signal (0-15 clock cycles)
+        pub tx_signal_delay: u8,
+        /// Delay the internal receive signal by one clock cycle
+        pub rx_signal_delay: bool,
+        /// Do not start transmitting while the receiver is busy
+        pub collision_avoidance: bool,
+        /// Report a [Collision](super::Error::Collision) error when the received data differs
+        /// from the transmitted data.
+        ///
+        /// *Note: this requires the receiver to receive the transmitted data, so the echo is
+        /// not suppressed when collision detection is enabled.*
+        pub collision_detection: bool,
+        /// Do not receive the transmitted data
+        pub echo_suppression: bool,
+    }
+
+    impl Default for Rs485 {
+        fn default() -> Rs485 {
+            Rs485 {
+                delay_before_tx: false,
+                delay_after_tx: false,
+                tx_signal_delay: 0,
+                rx_signal_delay: false,
+                collision_avoidance: true,
+                collision_detection: false,
+                echo_suppression: true,
+            }
+        }
+    }
+
+    /// Signal inversion, e.g. for inverting RS-232 level shifters
+    #[derive(PartialEq, Eq, Copy, Clone, Debug, Default)]
+    pub struct Inversion {
+        /// Invert the TX signal
+        pub tx: bool,
+        /// Invert the RX signal
+        pub rx: bool,
+        /// Invert the RTS signal
+        pub rts: bool,
+        /// Invert the CTS signal
+        pub cts: bool,
+    }
+
+    /// IrDA (SIR) configuration
+    ///
+    /// In IrDA mode each zero bit is sent as a pulse of 3/16 bit time. IrDA SIR supports
+    /// baudrates up to 115200 baud.
+    #[derive(PartialEq, Eq, Copy, Clone, Debug)]
+    pub struct IrDA {
+        /// Keep the IrDA transmitter enabled continuously, otherwise the transmitter is only
+        /// enabled while sending
+        ///
+        /// *Note: most transceivers echo the transmitted pulses to the receiver.*
+        pub duplex: bool,
+        /// Invert the IrDA transmit pulses
+        pub tx_inverted: bool,
+        /// Invert the IrDA receive pulses
+        pub rx_inverted: bool,
+    }
+
+    impl Default for IrDA {
+        fn default() -> IrDA {
+            IrDA {
+                duplex: false,
+                tx_inverted: false,
+                rx_inverted: false,
+            }
+        }
+    }
+
+    /// Pattern (AT command) detection configuration
+    ///
+    /// A pattern is a number of consecutive identical characters, e.g. `+++` to switch a modem
+    /// to command mode or a single `\n` to detect the end of a line. When the pattern is
+    /// received the [PatternDetected](super::Event::PatternDetected) event fires.
+    #[derive(PartialEq, Eq, Copy, Clone, Debug)]
+    pub struct PatternDetection {
+        /// Pattern character
+        pub character: u8,
+        /// Number of consecutive pattern characters
+        pub count: u8,
+        /// Maximum time between the pattern characters in bit times
+        pub gap_timeout: u16,
+        /// Minimum idle time before the first pattern character in bit times
+        pub pre_idle: u16,
+        /// Minimum idle time after the last pattern character in bit times
+        pub post_idle: u16,
+    }
+
+    impl Default for PatternDetection {
+        fn default() -> PatternDetection {
+            PatternDetection {
+                character: b'+',
+                count: 3,
+                gap_timeout: 9,
+                pre_idle: 0,
+                post_idle: 0,
+            }
+        }
+    }
+
+    /// UART configuration
+    #[derive(Debug, Copy, Clone)]
+    pub struct Config {
+        pub baudrate: Hertz,
+        pub data_bits: DataBits,
+        pub parity: Parity,
+        pub stop_bits: StopBits,
+        /// Number of bytes in the RX FIFO above which the [Rxne](super::Event::Rxne) event fires
+        pub rx_fifo_full_threshold: u8,
+        /// Number of bytes in the TX FIFO below which the [Txe](super::Event::Txe) event fires
+        pub tx_fifo_empty_threshold: u8,
+        /// Number of symbol times the receiver has to be idle before the
+        /// [Idle](super::Event::Idle) event fires (None to disable)
+        pub rx_timeout: Option<u8>,
+        /// RS-485 half-duplex mode (None for normal mode)
+        pub rs485: Option<Rs485>,
+        /// IrDA mode (None for normal mode)
+        pub irda: Option<IrDA>,
+        /// Signal inversion
+        pub inversion: Inversion,
+        /// Hardware flow control via the RTS and CTS pins
+        pub flow_control: FlowControl,
+        /// Number of bytes in the RX FIFO at which RTS is deasserted
+        pub rx_flow_threshold: u8,
+        /// Software flow control (None to disable)
+        pub software_flow_control: Option<SoftwareFlowControl>,
+        /// Pattern detection (None to disable)
+        pub pattern_detection: Option<PatternDetection>,
+    }
+
+    impl Config {
+        pub fn baudrate(mut self, baudrate: Hertz) -> Self {
+            self.baudrate = baudrate;
+            self
+        }
+
+        pub fn parity_none(mut self) -> Self {
+            self.parity = Parity::ParityNone;
+            self
+        }
+
+        pub fn parity_even(mut self) -> Self {
+            self.parity = Parity::ParityEven;
+            self
+        }
+
+        pub fn parity_odd(mut self) -> Self {
+            self.parity = Parity::ParityOdd;
+            self
+        }
+
+        pub fn data_bits(mut self, data_bits: DataBits) -> Self {
+            self.data_bits = data_bits;
+            self
+        }
+
+        pub fn stop_bits(mut self, stop_bits: StopBits) -> Self {
+            self.stop_bits = stop_bits;
+            self
+        }
+
+        pub fn rx_fifo_full_threshold(mut self, threshold: u8) -> Self {
+            self.rx_fifo_full_threshold = threshold;
+            self
+        }
+
+        pub fn tx_fifo_empty_threshold(mut self, threshold: u8) -> Self {
+            self.tx_fifo_empty_threshold = threshold;
+            self
+        }
+
+        pub fn rx_timeout(mut self, symbols: Option<u8>) -> Self {
+            self.rx_timeout = symbols;
+            self
+        }
+
+        pub fn rs485(mut self, rs485: Option<Rs485>) -> Self {
+            self.rs485 = rs485;
+            self
+        }
+
+        pub fn irda(mut self, irda: Option<IrDA>) -> Self {
+            self.irda = irda;
+            self
+        }
+
+        pub fn inversion(mut self, inversion: Inversion) -> Self {
+            self.inversion = inversion;
+            self
+        }
+
+        pub fn flow_control(mut self, flow_control: FlowControl) -> Self {
+            self.flow_control = flow_control;
+            self
+        }
+
+        pub fn rx_flow_threshold(mut self, threshold: u8) -> Self {
+            self.rx_flow_threshold = threshold;
+            self
+        }
+
+        pub fn software_flow_control(
+            mut self,
+            software_flow_control: Option<SoftwareFlowControl>,
+        ) -> Self {
+            self.software_flow_control = software_flow_control;
+            self
+        }
+
+        pub fn pattern_detection(mut self, pattern_detection: Option<PatternDetection>) -> Self {
+            self.pattern_detection = pattern_detection;
+            self
+        }
+    }
+
+    impl Default for Config {
+        fn default() -> Config {
+            Config {
+                baudrate: Hertz(19_200),
+                data_bits: DataBits::DataBits8,
+                parity: Parity::ParityNone,
+                stop_bits: StopBits::STOP1,
+                rx_fifo_full_threshold: 120,
+                tx_fifo_empty_threshold: 10,
+                rx_timeout: Some(10),
+                rs485: None,
+                irda: None,
+                inversion: Inversion::default(),
+                flow_control: FlowControl::None,
+                rx_flow_threshold: 100,
+                software_flow_control: None,
+                pattern_detection: None,
+            }
+        }
+    }
+}
+
+/// Pins used by the UART interface
+///
+/// Note that any two pins may be used
+///
+/// In RS-485 mode the RTS pin is used as driver enable (DE) of the transceiver.
+pub struct Pins<
+    TX: OutputPin,
+    RX: InputPin,
+    // default pins to allow type inference
+    CTS: InputPin = crate::gpio::Gpio19<crate::gpio::Input<crate::gpio::Floating>>,
+    RTS: OutputPin = crate::gpio::Gpio22<crate::gpio::Output<crate::gpio::PushPull>>,
+> {
+    pub tx: TX,
+    pub rx: RX,
+    pub cts: Option<CTS>,
+    pub rts: Option<RTS>,
+}
+
+use private::Instance;
+
+/// Serial abstraction
+///
+pub struct Serial<
+    UART: Instance,
+    TX: OutputPin,
+    RX: InputPin,
+    // default pins to allow type inference
+    CTS: InputPin = crate::gpio::Gpio19<crate::gpio::Input<crate::gpio::Floating>>,
+    RTS: OutputPin = crate::gpio::Gpio22<crate::gpio::Output<crate::gpio::PushPull>>,
+> {
+    uart: UART,
+    pins: Pins<TX, RX, CTS, RTS>,
+    clock_control: crate::clock_control::ClockControlConfig,
+    rx_timeout: Option<u8>,
+    stop_bits: config::StopBits,
+    flow_control: config::FlowControl,
+    rs485: Option<config::Rs485>,
+    irda: Option<config::IrDA>,
+    pattern_detection: Option<config::PatternDetection>,
+    rx: Rx<UART>,
+    tx: Tx<UART>,
+}
+
+/// Serial receiver
+pub struct Rx<UART: Instance> {
+    _uart: PhantomData<UART>,
+    apb_lock: Option<crate::clock_control::dfs::LockAPB>,
+}
+
+/// Serial transmitter
+pub struct Tx<UART: Instance> {
+    _uart: PhantomData<UART>,
+    apb_lock: Option<crate::clock_control::dfs::LockAPB>,
+}
+
+impl<UART: Instance, TX: OutputPin, RX: InputPin, CTS: InputPin, RTS: OutputPin>
+    Serial<UART, TX, RX, CTS, RTS>
+{
+    /// Create a new serial driver
+    pub fn new(
+        uart: UART,
+        pins: Pins<TX, RX, CTS, RTS>,
+        config: config::Config,
+        clock_control: crate::clock_control::ClockControlConfig,
+    ) -> Result<Self, Error> {
+        let mut serial = Serial {
+            uart,
+            pins,
+            clock_control,
+            rx_timeout: config.rx_timeout,
+            stop_bits: config.stop_bits,
+            flow_control: config.flow_control,
+            rs485: config.rs485,
+            irda: config.irda,
+            // applied when setting the baudrate
+            pattern_detection: config.pattern_detection,
+            rx: Rx {
+                _uart: PhantomData,
+                apb_lock: None,
+            },
+            tx: Tx {
+                _uart: PhantomData,
+                apb_lock: None,
+            },
+        };
+
+        serial.uart.init_pins(&mut serial.pins);
+        serial.uart.reset().enable();
+        serial.reset_rx_fifo();
+        serial.reset_tx_fifo();
+        serial
+            .change_stop_bits(config.stop_bits)
+            .change_rx_flow_threshold(config.rx_flow_threshold)
+            .change_flow_control(config.flow_control)
+            .change_software_flow_control(config.software_flow_control)
+            .change_rs485(config.rs485)?
+            .change_irda(config.irda)?
+            .change_inversion(config.inversion)
+            .change_data_bits(config.data_bits)
+            .change_parity(config.parity)
+            .change_rx_fifo_full_threshold(config.rx_fifo_full_threshold)
+            .change_tx_fifo_empty_threshold(config.tx_fifo_empty_threshold)
+            .change_baudrate(config.baudrate)?;
+        Ok(serial)
+    }
+
+    /// Change the number of stop bits
+    pub fn change_stop_bits(&mut self, stop_bits: config::StopBits) -> &mut Self {
+        self.stop_bits = stop_bits;
+        let dl1_en = self.rs485_dl1_en();
+
+        //workaround for hardware issue, when UART stop bit set as 2-bit mode.
+        self.uart.rs485_conf.modify(|_, w| w.dl1_en().bit(dl1_en));
+
+        self.uart.conf0.modify(|_, w| match stop_bits {
+            config::StopBits::STOP1 => w.stop_bit_num().stop_bits_1(),
+            config::StopBits::STOP1P5 => w.stop_bit_num().stop_bits_1p5(),
+            //workaround for hardware issue, when UART stop bit set as 2-bit mode.
+            config::StopBits::STOP2 => w.stop_bit_num().stop_bits_1(),
+        });
+
+        self
+    }
+
+    /// Change to RS-485 half-duplex mode (or back to normal mode when None)
+    ///
+    /// Enabling RS-485 mode disables IrDA mode.
+    ///
+    /// The RTS pin is driven high (driver enabled) by writing and driven low (receiving) by
+    /// a successful [flush](embedded_hal::serial::Write::flush) after all data has been sent.
+    /// Formatted writes ([core::fmt::Write]) flush at the end of each message, but after
+    /// writing single bytes (e.g. via [embedded_hal::serial::Write::write] or the buffered
+    /// transmitter) the caller must flush, otherwise the bus stays blocked. When sending from
+    /// an interrupt handler, listen to [Event::TxDone] and call flush from the handler.
+    ///
+    /// Fails if IrDA mode is disabled and the baudrate cannot be re-applied.
+    pub fn change_rs485(&mut self, rs485: Option<config::Rs485>) -> Result<&mut Self, Error> {
+        self.rs485 = rs485;
+        let dl1_en = self.rs485_dl1_en();
+
+        // RTS cannot be used for flow control in RS-485 mode, restore it otherwise
+        self.change_flow_control(self.flow_control);
+
+        match rs485 {
+            Some(rs485) => {
+                if self.irda.is_some() {
+                    self.change_irda(None)?;
+                }
+                self.uart.rs485_conf.modify(|_, w| unsafe {
+                    w.rs485_en()
+                        .set_bit()
+                        .dl0_en()
+                        .bit(rs485.delay_before_tx)
+                        .dl1_en()
+                        .bit(dl1_en)
+                        .rs485tx_rx_en()
+                        .bit(rs485.collision_detection || !rs485.echo_suppression)
+                        .rs485rxby_tx_en()
+                        .bit(!rs485.collision_avoidance)
+                        .rs485_tx_dly_num()
+                        .bits(core::cmp::min(rs485.tx_signal_delay, 0xf))
+                        .rs485_rx_dly_num()
+                        .bit(rs485.rx_signal_delay)
+                });
+                // driver disabled: RTS low
+                self.uart.conf0.modify(|_, w| w.sw_rts().set_bit());
+            }
+            None => {
+                self.uart.rs485_conf.modify(|_, w| {
+                    w.rs485_en()
+                        .clear_bit()
+                        .dl0_en()
+                        .clear_bit()
+                        .dl1_en()
+                        .bit(dl1_en)
+                        .rs485tx_rx_en()
+                        .clear_bit()
+                        .rs485rxby_tx_en()
+                        .clear_bit()
+                });
+                self.uart.conf0.modify(|_, w| w.sw_rts().clear_bit());
+            }
+        }
+
+        Ok(self)
+    }
+
+    /// Change to IrDA mode (or back to normal mode when None)
+    ///
+    /// Enabling IrDA mode disables RS-485 mode. In IrDA mode the APB clock is used, as the
+    /// IrDA pulses are too short for the reference clock, so the baudrate is re-applied.
+    ///
+    /// Unless in duplex mode, the IrDA transmitter is enabled by writing and disabled by a
+    /// successful [flush](embedded_hal::serial::Write::flush) after all data has been sent.
+    ///
+    /// Fails if the baudrate cannot be re-applied with the clock source of the new mode.
+    pub fn change_irda(&mut self, irda: Option<config::IrDA>) -> Result<&mut Self, Error> {
+        self.irda = irda;
+
+        match irda {
+            Some(irda) => {
+                if self.rs485.is_some() {
+                    self.change_rs485(None)?;
+                }
+                self.uart.conf0.modify(|_, w| {
+                    w.irda_en()
+                        .set_bit()
+                        .irda_dplx()
+                        .bit(irda.duplex)
+                        .irda_tx_en()
+                        .bit(irda.duplex)
+                        .irda_tx_inv()
+                        .bit(irda.tx_inverted)
+                        .irda_rx_inv()
+                        .bit(irda.rx_inverted)
+                        .irda_wctl()
+                        .clear_bit()
+                });
+            }
+            None => {
+                self.uart.conf0.modify(|_, w| {
+                    w.irda_en()
+                        .clear_bit()
+                        .irda_dplx()
+                        .clear_bit()
+                        .irda_tx_en()
+                        .clear_bit()
+                        .irda_tx_inv()
+                        .clear_bit()
+                        .irda_rx_inv()
+                        .clear_bit()
+                });
+            }
+        }
+
+        // the clock source depends on the IrDA mode
+        let baudrate = self.baudrate();
+        self.change_baudrate(baudrate)
+    }
+
+    /// Change the inversion of the TX, RX, RTS and CTS signals
+    pub fn change_inversion(&mut self, inversion: config::Inversion) -> &mut Self {
+        self.uart.conf0.modify(|_, w| {
+            w.txd_inv()
+                .bit(inversion.tx)
+                .rxd_inv()
+                .bit(inversion.rx)
+                .rts_inv()
+                .bit(inversion.rts)
+                .cts_inv()
+                .bit(inversion.cts)
+        });
+
+        self
+    }
+
+    /// Change the hardware flow control
+    ///
+    /// *Note: RTS flow control cannot be combined with RS-485 mode, as RTS is then used as
+    /// driver enable. It is applied when RS-485 mode is disabled. When CTS flow control is used
+    /// without a CTS pin, transmission may be blocked.*
+    pub fn change_flow_control(&mut self, flow_control: config::FlowControl) -> &mut Self {
+        self.flow_control = flow_control;
+
+        let (rts, cts) = match flow_control {
+            config::FlowControl::None => (false, false),
+            config::FlowControl::Rts => (true, false),
+            config::FlowControl::Cts => (false, true),
+            config::FlowControl::RtsCts => (true, true),
+        };
+
+        let rts = rts && self.rs485.is_none();
+
+        (&UART_MUTEX).lock(|_| {
+            self.uart.conf1.modify(|_, w| w.rx_flow_en().bit(rts));
+        });
+        self.uart.conf0.modify(|_, w| w.tx_flow_en().bit(cts));
+
+        self
+    }
+
+    /// Change the number of bytes in the RX FIFO at which RTS is deasserted
+    ///
+    /// The threshold is limited to 127 bytes.
+    pub fn change_rx_flow_threshold(&mut self, threshold: u8) -> &mut Self {
+        (&UART_MUTEX).lock(|_| {
+            self.uart
+                .conf1
+                .modify(|_, w| unsafe { w.rx_flow_thrhd().bits(core::cmp::min(threshold, 0x7f)) })
+        });
+        self
+    }
+
+    /// Change the software (XON/XOFF) flow control (None to disable)
+    ///
+    /// The thresholds are limited to 127 bytes, so they can be reached before the RX FIFO
+    /// is full.
+    pub fn change_software_flow_control(
+        &mut self,
+        software_flow_control: Option<config::SoftwareFlowControl>,
+    ) -> &mut Self {
+        match software_flow_control {
+            Some(config) => {
+                self.uart.swfc_conf.write(|w| unsafe {
+                    w.xon_char()
+                        .bits(config.xon_char)
+                        .xoff_char()
+                        .bits(config.xoff_char)
+                        .xon_threshold()
+                        .bits(core::cmp::min(config.xon_threshold, UART_FIFO_SIZE - 1))
+                        .xoff_threshold()
+                        .bits(core::cmp::min(config.xoff_threshold, UART_FIFO_SIZE - 1))
+                });
+                self.uart
+                    .flow_conf
+                    .modify(|_, w| w.sw_flow_con_en().set_bit().xonoff_del().set_bit());
+            }
+            None => {
+                self.uart
+                    .flow_conf
+                    .modify(|_, w| w.sw_flow_con_en().clear_bit().xonoff_del().clear_bit());
+            }
+        }
+        self
+    }
+
+    /// The one bit delay after the stop bit is used for both RS-485 and as workaround for 2 stop
+    /// bits.
+    fn rs485_dl1_en(&self) -> bool {
+        self.stop_bits == config::StopBits::STOP2
+            || self.rs485.map_or(false, |rs485| rs485.delay_after_tx)
+    }
+
+    /// Change the number of data bits
+    pub fn change_data_bits(&mut self, data_bits: config::DataBits) -> &mut Self {
+        self.uart.conf0.modify(|_, w| match data_bits {
+            config::DataBits::DataBits5 => w.bit_num().data_bits_5(),
+            config::DataBits::DataBits6 => w.bit_num().data_bits_6(),
+            config::DataBits::DataBits7 => w.bit_num().data_bits_7(),
+            config::DataBits::DataBits8 => w.bit_num().data_bits_8(),
+        });
+
+        self
+    }
+
+    /// Change the type of parity checking
+    pub fn change_parity(&mut self, parity: config::Parity) -> &mut Self {
+        self.uart.conf0.modify(|_, w| match parity {
+            config::Parity::ParityNone => w.parity_en().clear_bit(),
+            config::Parity::ParityEven => w.parity_en().set_bit().parity().clear_bit(),
+            config::Parity::ParityOdd => w.parity_en().set_bit().parity().set_bit(),
+        });
+
+        self
+    }
+
+    /// Change the number of bytes in the RX FIFO above which the [Event::Rxne] event fires
+    ///
+    /// The threshold is limited to 127 bytes.
+    pub fn change_rx_fifo_full_threshold(&mut self, threshold: u8) -> &mut Self {
+        self.rx.change_fifo_full_threshold(threshold);
+        self
+    }
+
+    /// Change the number of bytes in the TX FIFO below which the [Event::Txe] event fires
+    ///
+    /// The threshold is limited to 127 bytes.
+    pub fn change_tx_fifo_empty_threshold(&mut self, threshold: u8) -> &mut Self {
+        self.tx.change_fifo_empty_threshold(threshold);
+        self
+    }
+
+    /// Change the number of symbol times the receiver has to be idle before the [Event::Idle]
+    /// event fires. None disables the RX timeout.
+    ///
+    /// The timeout is limited to 127 symbol times when using the APB clock and to 15 symbol
+    /// times when using the reference clock.
+    pub fn change_rx_timeout(&mut self, symbols: Option<u8>) -> &mut Self {
+        self.rx_timeout = symbols;
+
+        // Hardware issue: when using the reference clock, the timeout is counted in units
+        // of 1/8 symbol time
+        let factor = if self.is_clock_apb() { 1 } else { 8 };
+        let threshold = symbols.map(|symbols| core::cmp::min(symbols as u32 * factor, 0x7f));
+
+        (&UART_MUTEX).lock(|_| {
+            self.uart.conf1.modify(|_, w| unsafe {
+                match threshold {
+                    Some(threshold) if threshold > 0 => w
+                        .rx_tout_thrhd()
+                        .bits(threshold as u8)
+                        .rx_tout_en()
+                        .set_bit(),
+                    _ => w.rx_tout_en().clear_bit(),
+                }
+            })
+        });
+
+        self
+    }
+
+    /// Change the pattern detection
+    ///
+    /// None stops listening to the [Event::PatternDetected] event. (The hardware cannot be
+    /// disabled and detects `+++` by default.)
+    pub fn change_pattern_detection(
+        &mut self,
+        pattern_detection: Option<config::PatternDetection>,
+    ) -> &mut Self {
+        self.pattern_detection = pattern_detection;
+
+        match pattern_detection {
+            Some(pattern) => {
+                // the idle and gap times are counted in clock cycles
+                let cycles_per_bit = self.uart.clkdiv.read().clkdiv().bits();
+                let cycles = |bits: u16| core::cmp::min(bits as u32 * cycles_per_bit, 0xff_ffff);
+
+                self.uart.at_cmd_char.write(|w| unsafe {
+                    w.at_cmd_char()
+                        .bits(pattern.character)
+                        .char_num()
+                        .bits(pattern.count)
+                });
+                self.uart
+                    .at_cmd_gaptout
+                    .write(|w| unsafe { w.rx_gap_tout().bits(cycles(pattern.gap_timeout)) });
+                self.uart
+                    .at_cmd_precnt
+                    .write(|w| unsafe { w.pre_idle_num().bits(cycles(pattern.pre_idle)) });
+                self.uart
+                    .at_cmd_postcnt
+                    .write(|w| unsafe { w.post_idle_num().bits(cycles(pattern.post_idle)) });
+            }
+            None => listen::<UART>(Event::PatternDetected, false),
+        }
+
+        self
+    }
+
+    /// Change the baudrate.
+    ///
+    /// Will automatically select the clock source. When possible the reference clock (1MHz) will
+    /// be used, because this is constant when the clock source/frequency changes.
+    /// However if one of the clock frequencies is below 10MHz or if the baudrate is above
+    /// the reference clock or if the baudrate cannot be set within 1.5% or in IrDA mode
+    /// then use the APB clock.
+    pub fn change_baudrate<T: Into<Hertz> + Copy>(
+        &mut self,
+        baudrate: T,
+    ) -> Result<&mut Self, Error> {
+        let mut use_apb_frequency = false;
+
+        // if APB frequency is <10MHz the ref clock is no longer accurate
+        // or if the baudrate > Ref frequency then use the APB frequency
+        // IrDA pulses (3/16 bit time) are too short for the reference clock
+        if !self.clock_control.is_ref_clock_stable()
+            || baudrate.into() > self.clock_control.ref_frequency()
+            || self.irda.is_some()
+        {
+            use_apb_frequency = true;
+        } else if baudrate.into() < self.clock_control.apb_frequency_apb_locked() / (1 << 20 - 1) {
+            // if baudrate is lower then can be achieved via the APB frequency
+            use_apb_frequency = false;
+        } else {
+            let clk_div =
+                (self.clock_control.ref_frequency() * 16 + baudrate.into() / 2) / baudrate.into();
+            // if baudrate too high use APB clock
+            if clk_div == 0 {
+                use_apb_frequency = true
+            } else {
+                // if baudrate cannot be reached within 1.5% use APB frequency
+                // use 203 as multiplier (2*101.5), because 1Mhz * 16 * 203 still fits in 2^32
+                let calc_baudrate = (self.clock_control.ref_frequency() * 16 * 200) / clk_div;
+                if calc_baudrate > baudrate.into() * 203 || calc_baudrate < baudrate.into() * 197 {
+                    use_apb_frequency = true;
+                }
+            }
+        }
+
+        self.change_baudrate_force_clock(baudrate, use_apb_frequency)
+    }
+
+    /// Change the baudrate choosing the reference or APB clock manually
+    pub fn change_baudrate_force_clock<T: Into<Hertz> + Copy>(
+        &mut self,
+        baudrate: T,
+        use_apb_frequency: bool,
+    ) -> Result<&mut Self, Error> {
+        if self.rx.apb_lock.is_none() {
+            if use_apb_frequency {
+                self.rx.apb_lock = Some(self.clock_control.lock_apb_frequency());
+                self.tx.apb_lock = Some(self.clock_control.lock_apb_frequency());
+            }
+        } else if !use_apb_frequency {
+            self.rx.apb_lock = None;
+            self.tx.apb_lock = None;
+        }
+
+        // set clock source
+        self.uart
+            .conf0
+            .modify(|_, w| w.tick_ref_always_on().bit(use_apb_frequency));
+
+        // the RX timeout depends on the clock source
+        self.change_rx_timeout(self.rx_timeout);
+
+        let sclk_freq = if use_apb_frequency {
+            self.clock_control.apb_frequency_apb_locked()
+        } else {
+            self.clock_control.ref_frequency()
+        };
+
+        // calculate nearest divider
+        let clk_div = (sclk_freq * 16 + baudrate.into() / 2) / baudrate.into();
+
+        if clk_div == 0 {
+            return Err(Error::BaudrateTooHigh);
+        }
+        if clk_div > (1 << 24) - 1 {
+            return Err(Error::BaudrateTooLow);
+        }
+
+        unsafe {
+            self.uart.clkdiv.modify(|_, w| {
+                w.clkdiv()
+                    .bits(clk_div >> 4)
+                    .clkdiv_frag()
+                    .bits((clk_div & 0xf) as u8)
+            })
+        };
+
+        // the pattern detection timing depends on the baudrate
+        self.change_pattern_detection(self.pattern_detection);
+
+        Ok(self)
+    }
+
+    /// Detect the baudrate of the received data
+    ///
+    /// The hardware measures the shortest low and high pulses on the RX line. The other side
+    /// should therefore send characters with single bit pulses, e.g. 0x55 ('U'), until
+    /// detection has finished. The received data is discarded.
+    ///
+    /// When `apply` is true, the detected baudrate is set, otherwise the original
+    /// baudrate is restored.
+    pub fn detect_baudrate<T: Into<MicroSeconds>>(
+        &mut self,
+        timeout: T,
+        apply: bool,
+    ) -> Result<Hertz, Error> {
+        let original_baudrate = self.baudrate();
+        let timeout: MicroSeconds = timeout.into();
+        let timeout = u32::from(timeout);
+
+        // measure with the APB clock for best accuracy
+        self.change_baudrate_force_clock(original_baudrate, true)?;
+        let sclk_freq = self.clock_control.apb_frequency_apb_locked();
+
+        // restart the detector
+        self.uart.autobaud.modify(|_, w| w.en().clear_bit());
+        self.uart.autobaud.modify(|_, w| w.en().set_bit());
+
+        let mut elapsed = 0;
+        while self.uart.rxd_cnt.read().edge_cnt().bits() < AUTOBAUD_EDGE_COUNT {
+            if elapsed >= timeout {
+                self.uart.autobaud.modify(|_, w| w.en().clear_bit());
+                self.change_baudrate(original_baudrate)?;
+                return Err(Error::Timeout);
+            }
+            crate::clock_control::sleep(AUTOBAUD_POLL_INTERVAL);
+            elapsed += u32::from(AUTOBAUD_POLL_INTERVAL);
+        }
+
+        // minimum pulse widths in clock cycles, both should be a single bit
+        let low = self.uart.lowpulse.read().min_cnt().bits();
+        let high = self.uart.highpulse.read().min_cnt().bits();
+
+        self.uart.autobaud.modify(|_, w| w.en().clear_bit());
+        self.reset_rx_fifo();
+
+        let baudrate = sclk_freq / ((low + high + 2) / 2);
+
+        if apply {
+            self.change_baudrate(baudrate)?;
+        } else {
+            self.change_baudrate(original_baudrate)?;
+        }
+
+        Ok(baudrate)
+    }
+
+    /// Enable wake-up from light sleep
+    ///
+    /// The chip wakes up when the number of positive edges on the RX line reaches the threshold
+    /// (3-1025), see [light_sleep](crate::clock_control::ClockControlConfig::light_sleep).
+    /// E.g. the character 0x55 ('U') contains 5 positive edges (including the stop bit).
+    ///
+    /// *Note: the characters received during sleep, including the character that wakes the chip,
+    /// are lost. Characters received before the clocks are running again (typically below 1ms)
+    /// can be corrupted, so the other side should send one or more wake-up characters and wait
+    /// before sending the actual data.*
+    pub fn enable_wakeup(&mut self, threshold_edges: u16) -> Result<&mut Self, Error> {
+        let source = match UART::index() {
+            0 => WakeupSource::Uart0,
+            1 => WakeupSource::Uart1,
+            _ => return Err(Error::WakeupNotSupported),
+        };
+
+        if threshold_edges < WAKEUP_THRESHOLD_MIN || threshold_edges > WAKEUP_THRESHOLD_MAX {
+            return Err(Error::InvalidWakeupThreshold);
+        }
+
+        // the hardware adds 2 edges to the threshold
+        self.uart
+            .sleep_conf
+            .write(|w| unsafe { w.active_threshold().bits(threshold_edges - 2) });
+        wakeup::enable_wakeup_source(source, true);
+
+        Ok(self)
+    }
+
+    /// Disable wake-up from light sleep
+    pub fn disable_wakeup(&mut self) -> &mut Self {
+        match UART::index() {
+            0 => wakeup::enable_wakeup_source(WakeupSource::Uart0, false),
+            1 => wakeup::enable_wakeup_source(WakeupSource::Uart1, false),
+            _ => {}
+        }
+        self
+    }
+
+    /// Returns if the reference or APB clock is used
+    pub fn is_clock_apb(&self) -> bool {
+        self.uart.conf0.read().tick_ref_always_on().bit_is_set()
+    }
+
+    /// Returns the current baudrate
+    pub fn baudrate(&self) -> Hertz {
+        let use_apb_frequency = self.uart.conf0.read().tick_ref_always_on().bit_is_set();
+        let sclk_freq = if use_apb_frequency {
+            self.clock_control.apb_frequency()
+        } else {
+            self.clock_control.ref_frequency()
+        };
+        let div = self.uart.clkdiv.read().clkdiv().bits() << 4
+            | (self.uart.clkdiv.read().clkdiv_frag().bits() as u32);
+
+        // round to nearest integer baudrate
+        (sclk_freq * 16 + Hertz(div / 2)) / div
+    }
+
+    /// Starts listening for an interrupt event
+    ///
+    /// The interrupt still needs to be enabled via [interrupt::enable][crate::interrupt::enable]
+    /// or similar functions.
+    pub fn listen(&mut self, event: Event) {
+        listen::<UART>(event, true);
+    }
+
+    /// Stop listening for an interrupt event
+    pub fn unlisten(&mut self, event: Event) {
+        listen::<UART>(event, false);
+    }
+
+    /// Clear a pending interrupt event
+    ///
+    /// *Note: the [Event::Rxne] and [Event::Txe] events will be raised again immediately if the
+    /// FIFO level is still beyond the threshold, so read or write the FIFO before clearing.*
+    pub fn clear_interrupt(&mut self, event: Event) {
+        clear_interrupt::<UART>(event);
+    }
+
+    /// Check if an interrupt event is pending
+    pub fn is_interrupt_pending(&self, event: Event) -> bool {
+        is_interrupt_pending::<UART>(event)
+    }
+
+    /// Return true if the receiver is idle
+    pub fn is_rx_idle(&self) -> bool {
+        self.uart.status.read().st_urx_out().is_rx_idle()
+    }
+
+    /// Return true if the transmitter is idle
+    pub fn is_tx_idle(&self) -> bool {
+        self.uart.status.read().st_utx_out().is_tx_idle()
+    }
+
+    /// Split the serial driver in separate TX and RX drivers
+    pub fn split(self) -> (Tx<UART>, Rx<UART>) {
+        (self.tx, self.rx)
+    }
+
+    /// Release the UART and GPIO resources
+    pub fn release(self) -> (UART, Pins<TX, RX, CTS, RTS>) {
+        (self.uart, self.pins)
+    }
+
+    pub fn reset_rx_fifo(&mut self) {
+        // Hardware issue: rxfifo_rst does not work properly;
+        while self.uart.status.read().rxfifo_cnt().bits() != 0
+            || (self.uart.mem_rx_status.read().mem_rx_rd_addr().bits()
+                != self.uart.mem_rx_status.read().mem_rx_wr_addr().bits())
+        {
+            self.uart.rx_fifo.read().bits();
+        }
+
+        // discard line errors of the removed data
+        self.uart
+            .int_clr
+            .write(|w| unsafe { w.bits(LINE_ERROR_EVENTS_MASK) });
+    }
+
+    pub fn reset_tx_fifo(&self) {
+        self.uart.conf0.modify(|_, w| w.txfifo_rst().set_bit());
+        self.uart.conf0.modify(|_, w| w.txfifo_rst().clear_bit());
+    }
+}
+
+impl<UART: Instance, TX: OutputPin, RX: InputPin, CTS: InputPin, RTS: OutputPin> serial::Read<u8>
+    for Serial<UART, TX, RX, CTS, RTS>
+{
+    type Error = Error;
+
+    fn read(&mut self) -> nb::Result<u8, Self::Error> {
+        self.rx.read()
+    }
+}
+
+impl<UART: Instance, TX: OutputPin, RX: InputPin, CTS: InputPin, RTS: OutputPin> serial::Write<u8>
+    for Serial<UART, TX, RX, CTS, RTS>
+{
+    type Error = Infallible;
+
+    fn flush(&mut self) -> nb::Result<(), Self::Error> {
+        self.tx.flush()
+    }
+
+    fn write(&mut self, byte: u8) -> nb::Result<(), Self::Error> {
+        self.tx.write(byte)
+    }
+}
+
+impl<UART: Instance, TX: OutputPin, RX: InputPin, CTS: InputPin, RTS: OutputPin> core::fmt::Write
+    for Serial<UART, TX, RX, CTS, RTS>
+{
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        self.tx.write_str(s)
+    }
+
+    fn write_fmt(&mut self, args: core::fmt::Arguments) -> core::fmt::Result {
+        self.tx.write_fmt(args)
+    }
+}
+
+// The interrupt enable and configuration registers are shared by the receiver and transmitter,
+// which may be used from different cores or interrupts.
+static UART_MUTEX: CriticalSectionSpinLockMutex<()> = CriticalSectionSpinLockMutex::new(());
+
+fn listen<UART: Instance>(event: Event, enable: bool) {
+    (&UART_MUTEX).lock(|_| unsafe {
+        (*UART::ptr()).int_ena.modify(|r, w| {
+            if enable {
+                w.bits(r.bits() | event.mask())
+            } else {
+                w.bits(r.bits() & !event.mask())
+            }
+        })
+    });
+}
+
+fn clear_interrupt<UART: Instance>(event: Event) {
+    unsafe { (*UART::ptr()).int_clr.write(|w| w.bits(event.mask())) };
+}
+
+fn is_interrupt_pending<UART: Instance>(event: Event) -> bool {
+    unsafe { (*UART::ptr()).int_st.read().bits() & event.mask() != 0 }
+}
+
+impl<UART: Instance> Rx<UART> {
+    /// Get count of bytes in the receive FIFO
+    pub fn count(&self) -> u8 {
+        unsafe { (*UART::ptr()).status.read().rxfifo_cnt().bits() }
+    }
+
+    /// Check if the receivers is idle
+    pub fn is_idle(&self) -> bool {
+        unsafe { (*UART::ptr()).status.read().st_urx_out().is_rx_idle() }
+    }
+
+    /// Get the position in the RX FIFO of the first character of a detected pattern
+    ///
+    /// Returns None if no pattern has been detected. To be called when the
+    /// [Event::PatternDetected] event fires, before reading from the FIFO and before clearing the
+    /// event.
+    ///
+    /// The position is calculated from the number of bytes in the FIFO, so it is only correct
+    /// when no data is received after the pattern, e.g. by configuring a post idle time.
+    pub fn pattern_position(&self) -> Option<u8> {
+        let uart = unsafe { &*UART::ptr() };
+
+        if uart.int_raw.read().bits() & Event::PatternDetected.mask() == 0 {
+            return None;
+        }
+
+        self.count()
+            .checked_sub(uart.at_cmd_char.read().char_num().bits())
+    }
+
+    /// Change the number of bytes in the RX FIFO above which the [Event::Rxne] event fires
+    pub fn change_fifo_full_threshold(&mut self, threshold: u8) -> &mut Self {
+        (&UART_MUTEX).lock(|_| unsafe {
+            (*UART::ptr())
+                .conf1
+                .modify(|_, w| w.rxfifo_full_thrhd().bits(core::cmp::min(threshold, 0x7f)))
+        });
+        self
+    }
+
+    /// Starts listening for an interrupt event
+    pub fn listen(&mut self, event: Event) {
+        listen::<UART>(event, true);
+    }
+
+    /// Stop listening for an interrupt event
+    pub fn unlisten(&mut self, event: Event) {
+        listen::<UART>(event, false);
+    }
+
+    /// Clear a pending interrupt event
+    pub fn clear_interrupt(&mut self, event: Event) {
+        clear_interrupt::<UART>(event);
+    }
+
+    /// Check if an interrupt event is pending
+    pub fn is_interrupt_pending(&self, event: Event) -> bool {
+        is_interrupt_pending::<UART>(event)
+    }
+}
+
+/// Mask of the line error events in the interrupt registers
+const LINE_ERROR_EVENTS_MASK: u32 = (1 << Event::ParityError as u32)
+    | (1 << Event::FramingError as u32)
+    | (1 << Event::Overflow as u32)
+    | (1 << Event::Break as u32)
+    | (1 << Event::Collision as u32);
+
+/// Reading returns line errors (overrun, BREAK, framing, parity and RS-485 collision errors) that
+/// occurred since
+/// the previous read. The error is returned once, before the next received byte. The erroneous
+/// byte itself is still stored in the FIFO (a BREAK is received as a 0 byte).
+///
+/// The status is taken from the raw interrupt register and is cleared when the error is
+/// returned, so do not clear these events from an interrupt handler when also relying on
+/// these errors.
+impl<UART: Instance> serial::Read<u8> for Rx<UART> {
+    type Error = Error;
+
+    fn read(&mut self) -> nb::Result<u8, Self::Error> {
+        let uart = unsafe { &*UART::ptr() };
+        let errors = uart.int_raw.read().bits() & LINE_ERROR_EVENTS_MASK;
+
+        if errors != 0 {
+            let (error, clear) = if errors & Event::Overflow.mask() != 0 {
+                (Error::Overrun, Event::Overflow.mask())
+            } else if errors & Event::Collision.mask() != 0 {
+                (Error::Collision, Event::Collision.mask())
+            } else if errors & Event::Break.mask() != 0 {
+                // a BREAK also causes a framing error
+                (
+                    Error::Break,
+                    Event::Break.mask() | Event::FramingError.mask(),
+                )
+            } else if errors & Event::FramingError.mask() != 0 {
+                (Error::Framing, Event::FramingError.mask())
+            } else {
+                (Error::Parity, Event::ParityError.mask())
+            };
+
+            uart.int_clr.write(|w| unsafe { w.bits(clear) });
+            return Err(nb::Error::Other(error));
+        }
+
+        if self.count() == 0 {
+            Err(nb::Error::WouldBlock)
+        } else {
+            Ok(unsafe { (*UART::ptr()).rx_fifo.read().bits() })
+        }
+    }
+}
+
+impl<UART: Instance> Tx<UART> {
+    /// Get count of bytes in the transmitter FIFO
+    pub fn count(&self) -> u8 {
+        unsafe { (*UART::ptr()).status.read().txfifo_cnt().bits() }
+    }
+
+    /// Check if the transmitter is idle
+    pub fn is_idle(&self) -> bool {
+        unsafe { (*UART::ptr()).status.read().st_utx_out().is_tx_idle() }
+    }
+
+    /// Send a BREAK (line held low) for the given number of bit times
+    ///
+    /// The BREAK is sent after all data in the TX FIFO has been sent. This function blocks
+    /// until the BREAK has been sent, or returns [Error::Timeout] (and stops the BREAK) if
+    /// that takes longer than `timeout`, e.g. when CTS flow control blocks the transmitter.
+    pub fn send_break<T: Into<MicroSeconds>>(&mut self, bits: u8, timeout: T) -> Result<(), Error> {
+        let uart = unsafe { &*UART::ptr() };
+        let timeout: MicroSeconds = timeout.into();
+        let timeout = u32::from(timeout);
+
+        self.driver_enable(true);
+
+        uart.idle_conf
+            .modify(|_, w| unsafe { w.tx_brk_num().bits(bits) });
+        uart.int_clr
+            .write(|w| unsafe { w.bits(Event::TxBreakDone.mask()) });
+        (&UART_MUTEX).lock(|_| uart.conf0.modify(|_, w| w.txd_brk().set_bit()));
+
+        let mut elapsed = 0;
+        let result = loop {
+            if uart.int_raw.read().bits() & Event::TxBreakDone.mask() != 0 {
+                break Ok(());
+            }
+            if elapsed >= timeout {
+                break Err(Error::Timeout);
+            }
+            crate::clock_control::sleep(BREAK_POLL_INTERVAL);
+            elapsed += u32::from(BREAK_POLL_INTERVAL);
+        };
+
+        (&UART_MUTEX).lock(|_| uart.conf0.modify(|_, w| w.txd_brk().clear_bit()));
+        uart.int_clr
+            .write(|w| unsafe { w.bits(Event::TxBreakDone.mask()) });
+
+        result
+    }
+
+    /// Drive the RS-485 driver enable (via RTS) in RS-485 mode or enable the IrDA transmitter
+    /// in half-duplex IrDA mode
+    fn driver_enable(&mut self, enable: bool) {
+        let uart = unsafe { &*UART::ptr() };
+        let conf0 = uart.conf0.read();
+
+        // RTS is driven high when sw_rts is cleared
+        if uart.rs485_conf.read().rs485_en().bit_is_set() && conf0.sw_rts().bit() == enable {
+            (&UART_MUTEX).lock(|_| uart.conf0.modify(|_, w| w.sw_rts().bit(!enable)));
+        }
+
+        if conf0.irda_en().bit_is_set()
+            && conf0.irda_dplx().bit_is_clear()
+            && conf0.irda_tx_en().bit() != enable
+        {
+            (&UART_MUTEX).lock(|_| uart.conf0.modify(|_, w| w.irda_tx_en().bit(enable)));
+        }
+    }
+
+    /// Check if the RS-485 driver or the half-duplex IrDA transmitter is enabled
+    fn is_driver_enabled(&self) -> bool {
+        let uart = unsafe { &*UART::ptr() };
+        let conf0 = uart.conf0.read();
+
+        (uart.rs485_conf.read().rs485_en().bit_is_set() && conf0.sw_rts().bit_is_clear())
+            || (conf0.irda_en().bit_is_set()
+                && conf0.irda_dplx().bit_is_clear()
+                && conf0.irda_tx_en().bit_is_set())
+    }
+
+    /// Change the number of bytes in the TX FIFO below which the [Event::Txe] event fires
+    pub fn change_fifo_empty_threshold(&mut self, threshold: u8) -> &mut Self {
+        (&UART_MUTEX).lock(|_| unsafe {
+            (*UART::ptr())
+                .conf1
+                .modify(|_, w| w.txfifo_empty_thrhd().bits(core::cmp::min(threshold, 0x7f)))
+        });
+        self
+    }
+
+    /// Starts listening for an interrupt event
+    pub fn listen(&mut self, event: Event) {
+        listen::<UART>(event, true);
+    }
+
+    /// Stop listening for an interrupt event
+    pub fn unlisten(&mut self, event: Event) {
+        listen::<UART>(event, false);
+    }
+
+    /// Clear a pending interrupt event
+    pub fn clear_interrupt(&mut self, event: Event) {
+        clear_interrupt::<UART>(event);
+    }
+
+    /// Check if an interrupt event is pending
+    pub fn is_interrupt_pending(&self, event: Event) -> bool {
+        is_interrupt_pending::<UART>(event)
+    }
+}
+
+/// In RS-485 and half-duplex IrDA mode writing enables the driver and a successful flush
+/// disables the driver, so the caller must flush after the last byte
+impl<UART: Instance> serial::Write<u8> for Tx<UART> {
+    type Error = Infallible;
+
+    fn flush(&mut self) -> nb::Result<(), Self::Error> {
+        if self.is_idle() {
+            self.driver_enable(false);
+            Ok(())
+        } else {
+            Err(nb::Error::WouldBlock)
+        }
+    }
+
+    fn write(&mut self, byte: u8) -> nb::Result<(), Self::Error> {
+        if self.count() >= UART_FIFO_SIZE {
+            return Err(nb::Error::WouldBlock);
+        }
+
+        self.driver_enable(true);
+        if UART::index() == 0 {
+            // shared with the debug log, so not written in a message of the other core
+            if !crate::dprint::try_write_byte(byte) {
+                return Err(nb::Error::WouldBlock);
+            }
+        } else {
+            unsafe { (*UART::ptr()).tx_fifo.write_with_zero(|w| w.bits(byte)) }
+        }
+        Ok(())
+    }
+}
+
+/// Each formatted write is a single message: for UART0 it is written like a
+/// [debug log](crate::dprint) message, so it is not interleaved with debug output of the other
+/// core
+///
+/// In RS-485 and half-duplex IrDA mode the driver is disabled after each message, which
+/// blocks until the message has been sent.
+impl<UART: Instance> core::fmt::Write for Tx<UART>
+where
+    Tx<UART>: embedded_hal::serial::Write<u8>,
+{
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        self.write_message(|writer| writer.write_str(s))
+    }
+
+    fn write_fmt(&mut self, args: core::fmt::Arguments) -> core::fmt::Result {
+        self.write_message(|writer| writer.write_fmt(args))
+    }
+}
+
+impl<UART: Instance> Tx<UART> {
+    fn write_message(
+        &mut self,
+        f: impl FnOnce(&mut dyn core::fmt::Write) -> core::fmt::Result,
+    ) -> core::fmt::Result {
+        let result = if UART::index() == 0 {
+            self.driver_enable(true);
+            crate::dprint::write_message(|writer| f(writer))
+        } else {
+            f(&mut TxWriter(self))
+        };
+
+        if self.is_driver_enabled() {
+            let _ = nb::block!(embedded_hal::serial::Write::flush(self));
+        }
+
+        result
+    }
+}
+
+/// Writer of the bytes of a message
+struct TxWriter<'a, UART: Instance>(&'a mut Tx<UART>);
+
+impl<UART: Instance> core::fmt::Write for TxWriter<'_, UART> {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        use embedded_hal::serial::Write;
+        s.as_bytes()
+            .iter()
+            .try_for_each(|c| nb::block!(self.0.write(*c)))
+            .map_err(|_| core::fmt::Error)
+    }
+}
+
+#[cfg(feature = "log")]
+impl<UART: Instance> crate::logger::Sink for Tx<UART> {
+    fn flush(&mut self) {
+        let _ = nb::block!(embedded_hal::serial::Write::flush(self));
+    }
+
+    fn with_message<R>(f: impl FnOnce() -> R) -> R {
+        if UART::index() == 0 {
+            crate::dprint::write_message(|_| f())
+        } else {
+            f()
+        }
+    }
+}
+
+mod private {
+
+    use super::Pins;
+    use crate::gpio::{InputPin, InputSignal, OutputPin, OutputSignal};
+    use crate::prelude::*;
+    use crate::target::{uart, UART0, UART1, UART2};
+    use core::ops::Deref;
+
+    pub trait Instance: Deref<Target = uart::RegisterBlock> {
+        fn ptr() -> *const uart::RegisterBlock;
+        /// Index of the UART (0-2)
+        fn index() -> usize;
+        /// Enable peripheral
+        fn enable(&mut self) -> &mut Self;
+        /// Disable peripheral
+        fn disable(&mut self) -> &mut Self;
+        /// Reset peripheral
+        fn reset(&mut self) -> &mut Self;
+
+        /// Initialize pins
+        fn init_pins<TX: OutputPin, RX: InputPin, CTS: InputPin, RTS: OutputPin>(
+            &mut self,
+            pins: &mut Pins<TX, RX, CTS, RTS>,
+        ) -> &mut Self;
+    }
+
+    macro_rules! halUart {
+        ($(
+            $UARTX:ident: ($index:expr, $txd:ident, $rxd:ident, $cts:ident, $rts:ident),
+        )+) => {
+            $(
+                impl Instance for $UARTX {
+                    fn ptr() -> *const uart::RegisterBlock {
+                        $UARTX::ptr()
+                    }
+
+                    fn index() -> usize {
+                        $index
+                    }
+
+                    fn reset(&mut self) -> &mut Self {
+                        dport::reset_peripheral(Peripheral::$UARTX);
+                        self
+                    }
+
+                    fn enable(&mut self) -> &mut Self {
+                        dport::enable_peripheral(Peripheral::$UARTX);
+                        self
+                    }
+
+                    fn disable(&mut self) -> &mut Self {
+                        dport::disable_peripheral(Peripheral::$UARTX);
+                        self
+
+                    }
+
+                    fn init_pins<TX: OutputPin, RX: InputPin, CTS: InputPin, RTS: OutputPin>(
+                        &mut self, pins: &mut Pins<TX,RX,CTS,RTS>
+                    ) -> &mut Self {
+                        pins
+                            .tx
+                            .set_to_push_pull_output()
+                            .connect_peripheral_to_output(OutputSignal::$txd);
+
+                        pins
+                            .rx
+                            .set_to_input()
+                            .connect_input_to_peripheral(InputSignal::$rxd);
+
+                        if let Some(cts) = pins.cts.as_mut() {
+                            cts
+                            .set_to_input()
+                            .connect_input_to_peripheral(InputSignal::$cts);
+                        }
+
+                        if let Some(rts) = pins.rts.as_mut() {
+                            rts
+                            .set_to_push_pull_output()
+                            .connect_peripheral_to_output(OutputSignal::$rts);
+                        }
+                        self
+                    }
+                }
+            )+
+        }
+    }
+
+    halUart! {
+        UART0: (0, U0TXD, U0RXD, U0CTS, U0RTS),
+        UART1: (1, U1TXD, U1RXD, U1CTS, U1RTS),
+        UART2: (2, U2TXD, U2RXD, U2CTS, U2RTS),
+    }
+}
@@ -0,0 +1,217 @@
+//! Sharing an SPI controller between devices
+//!
+//! The [SpiBus] owns an SPI2 or SPI3 controller. Each device on the bus has its own hardware
+//! chip select line (CS0, CS1 or CS2) and [Config], and is accessed via a [SpiDevice] handle.
+//! The controller is reconfigured when a transfer is for a different device than the previous
+//! one.
+//!
+//! The controller is protected by a spin lock with interrupts disabled, so the bus can be used
+//! from both cores and from interrupt handlers. Because of this, a transfer delays other
+//! interrupts until it is finished.
+//!
+//! # Example
+//! ```
+//! static BUS: SpiBus<
+//!     SPI<
+//!         esp32::SPI2,
+//!         Gpio19<Output<PushPull>>,
+//!         Gpio23<Output<PushPull>>,
+//!         Gpio25<Input<Floating>>,
+//!         Gpio22<Output<PushPull>>,
+//!         Gpio21<Output<PushPull>>,
+//!     >,
+//! > = SpiBus::new();
+//!
+//! BUS.init(spi);
+//!
+//! let mut display = BUS.device(ChipSelect::Cs0, Config::default().baudrate(26.MHz().into()));
+//! let mut sensor = BUS.device(ChipSelect::Cs1, Config::default().data_mode(MODE_3));
+//!
+//! display.write(&[0x2c, 0x00, 0x1f]).unwrap();
+//! ```
+
+use super::{
+    config::Config, private::GeneralInstance, transaction::Transaction, ChipSelect, Error, SPI,
+};
+use crate::gpio::{InputPin, OutputPin};
+use crate::prelude::*;
+use embedded_hal::blocking::spi::{Transfer, Write};
+
+/// SPI controller `S` shared between devices
+pub struct SpiBus<S> {
+    inner: CriticalSectionSpinLockMutex<Option<Inner<S>>>,
+}
+
+struct Inner<S> {
+    spi: S,
+    /// Device the controller is currently configured for
+    current: Option<(ChipSelect, Config)>,
+}
+
+impl<S> SpiBus<S> {
+    /// Create a new bus
+    ///
+    /// The bus can not be used until it is initialized via [SpiBus::init].
+    pub const fn new() -> Self {
+        SpiBus {
+            inner: CriticalSectionSpinLockMutex::new(None),
+        }
+    }
+}
+
+impl<
+        INSTANCE: GeneralInstance,
+        SCLK: OutputPin,
+        SDO: OutputPin,
+        SDI: InputPin + OutputPin,
+        CS: OutputPin,
+        WP: InputPin + OutputPin,
+        HD: InputPin + OutputPin,
+        CS1: OutputPin,
+        CS2: OutputPin,
+    > SpiBus<SPI<INSTANCE, SCLK, SDO, SDI, CS, WP, HD, CS1, CS2>>
+{
+    /// Set the SPI controller, returning the previous one
+    pub fn init(
+        &self,
+        spi: SPI<INSTANCE, SCLK, SDO, SDI, CS, WP, HD, CS1, CS2>,
+    ) -> Option<SPI<INSTANCE, SCLK, SDO, SDI, CS, WP, HD, CS1, CS2>> {
+        (&self.inner).lock(|inner| {
+            inner
+                .replace(Inner { spi, current: None })
+                .map(|inner| inner.spi)
+        })
+    }
+
+    /// Remove and return the SPI controller
+    pub fn release(&self) -> Option<SPI<INSTANCE, SCLK, SDO, SDI, CS, WP, HD, CS1, CS2>> {
+        (&self.inner).lock(|inner| inner.take().map(|inner| inner.spi))
+    }
+
+    /// Create a handle for the device on the chip select line
+    ///
+    /// The pin of the chip select line must be configured, otherwise the transfers fail with
+    /// [Error::MissingPin].
+    pub fn device(
+        &self,
+        cs: ChipSelect,
+        config: Config,
+    ) -> SpiDevice<'_, SPI<INSTANCE, SCLK, SDO, SDI, CS, WP, HD, CS1, CS2>> {
+        SpiDevice {
+            bus: self,
+            cs,
+            config,
+        }
+    }
+
+    /// Run `f` on the controller configured for the device
+    fn with_device<R, F>(&self, cs: ChipSelect, config: Config, f: F) -> Result<R, Error>
+    where
+        F: FnOnce(&mut SPI<INSTANCE, SCLK, SDO, SDI, CS, WP, HD, CS1, CS2>) -> Result<R, Error>,
+    {
+        (&self.inner).lock(|inner| {
+            let inner = inner.as_mut().ok_or(Error::BusNotInitialized)?;
+
+            if inner.current != Some((cs, config)) {
+                // the configuration is unknown after a failure
+                inner.current = None;
+                inner.spi.select_cs(cs)?.change_config(config)?;
+                inner.current = Some((cs, config));
+            }
+
+            f(&mut inner.spi)
+        })
+    }
+}
+
+/// Handle of a device on a [SpiBus] with the SPI controller `S`
+pub struct SpiDevice<'a, S> {
+    bus: &'a SpiBus<S>,
+    cs: ChipSelect,
+    config: Config,
+}
+
+impl<'a, S> SpiDevice<'a, S> {
+    /// Chip select line of the device
+    pub fn cs(&self) -> ChipSelect {
+        self.cs
+    }
+
+    /// Returns the configuration of the device
+    pub fn config(&self) -> Config {
+        self.config
+    }
+
+    /// Change the configuration of the device, used from the next transfer on
+    pub fn change_config(&mut self, config: Config) -> &mut Self {
+        self.config = config;
+        self
+    }
+}
+
+impl<
+        'a,
+        INSTANCE: GeneralInstance,
+        SCLK: OutputPin,
+        SDO: OutputPin,
+        SDI: InputPin + OutputPin,
+        CS: OutputPin,
+        WP: InputPin + OutputPin,
+        HD: InputPin + OutputPin,
+        CS1: OutputPin,
+        CS2: OutputPin,
+    > SpiDevice<'a, SPI<INSTANCE, SCLK, SDO, SDI, CS, WP, HD, CS1, CS2>>
+{
+    /// Run a transaction with command, address and dummy phases
+    ///
+    /// See [SPI::transaction].
+    pub fn transaction(&mut self, transaction: Transaction) -> Result<(), Error> {
+        self.bus
+            .with_device(self.cs, self.config, |spi| spi.transaction(transaction))
+    }
+}
+
+impl<
+        'a,
+        INSTANCE: GeneralInstance,
+        SCLK: OutputPin,
+        SDO: OutputPin,
+        SDI: InputPin + OutputPin,
+        CS: OutputPin,
+        WP: InputPin + OutputPin,
+        HD: InputPin + OutputPin,
+        CS1: OutputPin,
+        CS2: OutputPin,
+    > Transfer<u8> for SpiDevice<'a, SPI<INSTANCE, SCLK, SDO, SDI, CS, WP, HD, CS1, CS2>>
+{
+    type Error = Error;
+
+    fn transfer<'w>(&mut self, words: &'w mut [u8]) -> Result<&'w [u8], Error> {
+        self.bus.with_device(self.cs, self.config, |spi| {
+            spi.transfer(&mut *words)?;
+            Ok(())
+        })?;
+        Ok(words)
+    }
+}
+
+impl<
+        'a,
+        INSTANCE: GeneralInstance,
+        SCLK: OutputPin,
+        SDO: OutputPin,
+        SDI: InputPin + OutputPin,
+        CS: OutputPin,
+        WP: InputPin + OutputPin,
+        HD: InputPin + OutputPin,
+        CS1: OutputPin,
+        CS2: OutputPin,
+    > Write<u8> for SpiDevice<'a, SPI<INSTANCE, SCLK, SDO, SDI, CS, WP, HD, CS1, CS2>>
+{
+    type Error = Error;
+
+    fn write(&mut self, words: &[u8]) -> Result<(), Error> {
+        self.bus
+            .with_device(self.cs, self.config, |spi| spi.write(words))
+    }
+}
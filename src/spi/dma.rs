@@ -0,0 +1,537 @@
+//! SPI DMA transfers
+//!
+//! SPI2 and SPI3 can be connected to one of the two SPI DMA channels, which move data between
+//! memory and the SPI controller without involvement of the CPU. Transfers are then not limited
+//! by the 64 byte data buffer of the controller.
+//!
+//! Transfers with `&'static` buffers run in the background, returning a [DmaTransfer] handle
+//! which can be polled or waited for and which gives back the buffers when released. The
+//! blocking [Transfer] and [Write] implementations use DMA as well, falling back to the data
+//! buffer of the controller for buffers which are not DMA capable (e.g. constants in flash).
+//!
+//! The buffers and [descriptors](crate::dma::Descriptor) must be located in internal data RAM,
+//! see the [dma](crate::dma) module. Receive buffers must be word aligned and their length a
+//! multiple of 4.
+//!
+//! # Example
+//! ```
+//! static mut TX_DESCRIPTORS: [Descriptor; 8] = [Descriptor::new(); 8];
+//! static mut RX_DESCRIPTORS: [Descriptor; 1] = [Descriptor::new(); 1];
+//! static mut LINES: [u8; 320 * 40 * 2] = [0; 320 * 40 * 2];
+//!
+//! let spi_dma = SpiDma::new(
+//!     spi,
+//!     DmaChannel::Channel1,
+//!     unsafe { &mut TX_DESCRIPTORS },
+//!     unsafe { &mut RX_DESCRIPTORS },
+//! )
+//! .unwrap();
+//!
+//! let mut transfer = spi_dma.start_write(unsafe { &LINES }).unwrap();
+//! // prepare the next lines while the transfer is running
+//! transfer.wait().unwrap();
+//! let (spi_dma, _) = transfer.release();
+//! ```
+
+use super::{private::GeneralInstance, Error, SPI};
+use crate::clock_control::dfs::LockAPB;
+use crate::dma::{self, Descriptor};
+use crate::dport::{self, Peripheral};
+use crate::gpio::{InputPin, OutputPin};
+use core::sync::atomic::{compiler_fence, Ordering};
+use embedded_hal::blocking::spi::{Transfer, Write};
+
+/// Size of the buffer on the stack receiving the data of blocking full duplex transfers
+const BOUNCE_BUFFER_WORDS: usize = 64;
+const BOUNCE_BUFFER_SIZE: usize = BOUNCE_BUFFER_WORDS * 4;
+
+// bit positions in the SPI DMA interrupt registers
+const OUTLINK_DSCR_ERROR: u32 = 1 << 1;
+const INLINK_DSCR_ERROR: u32 = 1 << 2;
+
+/// Length rounded up to whole words
+fn word_length(length: usize) -> usize {
+    (length + 3) & !3
+}
+
+/// SPI DMA channel
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum DmaChannel {
+    Channel1 = 1,
+    Channel2 = 2,
+}
+
+/// SPI controller using DMA
+pub struct SpiDma<
+    INSTANCE: GeneralInstance,
+    SCLK: OutputPin,
+    SDO: OutputPin,
+    SDI: InputPin + OutputPin,
+    CS: OutputPin,
+    WP: InputPin + OutputPin,
+    HD: InputPin + OutputPin,
+    CS1: OutputPin,
+    CS2: OutputPin,
+> {
+    spi: SPI<INSTANCE, SCLK, SDO, SDI, CS, WP, HD, CS1, CS2>,
+    channel: DmaChannel,
+    tx_descriptors: &'static mut [Descriptor],
+    rx_descriptors: &'static mut [Descriptor],
+}
+
+impl<
+        INSTANCE: GeneralInstance,
+        SCLK: OutputPin,
+        SDO: OutputPin,
+        SDI: InputPin + OutputPin,
+        CS: OutputPin,
+        WP: InputPin + OutputPin,
+        HD: InputPin + OutputPin,
+        CS1: OutputPin,
+        CS2: OutputPin,
+    > SpiDma<INSTANCE, SCLK, SDO, SDI, CS, WP, HD, CS1, CS2>
+{
+    /// Connect the SPI controller to a DMA channel
+    ///
+    /// The descriptors determine the maximum buffer sizes
+    /// (see [descriptors_needed](crate::dma::descriptors_needed)).
+    pub fn new(
+        spi: SPI<INSTANCE, SCLK, SDO, SDI, CS, WP, HD, CS1, CS2>,
+        channel: DmaChannel,
+        tx_descriptors: &'static mut [Descriptor],
+        rx_descriptors: &'static mut [Descriptor],
+    ) -> Result<Self, Error> {
+        for descriptors in [&tx_descriptors[..], &rx_descriptors[..]].iter() {
+            if descriptors.is_empty() {
+                return Err(Error::Dma(dma::Error::TooFewDescriptors));
+            }
+            if !dma::is_dma_capable(
+                descriptors.as_ptr() as usize,
+                descriptors.len() * core::mem::size_of::<Descriptor>(),
+            ) {
+                return Err(Error::Dma(dma::Error::NotDmaCapable));
+            }
+        }
+
+        if !dport::select_spi_dma_channel(INSTANCE::index(), channel as u32) {
+            return Err(Error::DmaChannelInUse);
+        }
+        dport::enable_peripheral(Peripheral::SPI_DMA);
+
+        spi.instance.dma_conf.modify(|_, w| {
+            w.indscr_burst_en()
+                .set_bit()
+                .outdscr_burst_en()
+                .set_bit()
+                .out_data_burst_en()
+                .set_bit()
+        });
+        spi.instance.dma_int_ena.write(|w| unsafe { w.bits(0) });
+
+        Ok(SpiDma {
+            spi,
+            channel,
+            tx_descriptors,
+            rx_descriptors,
+        })
+    }
+
+    /// DMA channel used by the controller
+    pub fn channel(&self) -> DmaChannel {
+        self.channel
+    }
+
+    /// Disconnect the DMA channel and release the resources
+    pub fn release(
+        self,
+    ) -> (
+        SPI<INSTANCE, SCLK, SDO, SDI, CS, WP, HD, CS1, CS2>,
+        &'static mut [Descriptor],
+        &'static mut [Descriptor],
+    ) {
+        self.enable_data_phases();
+        dport::select_spi_dma_channel(INSTANCE::index(), 0);
+
+        (self.spi, self.tx_descriptors, self.rx_descriptors)
+    }
+
+    /// Start sending the buffer in the background
+    ///
+    /// Received data is discarded.
+    pub fn start_write(
+        mut self,
+        buffer: &'static [u8],
+    ) -> Result<DmaTransfer<INSTANCE, SCLK, SDO, SDI, CS, WP, HD, CS1, CS2, &'static [u8]>, Error>
+    {
+        dma::link_tx(self.tx_descriptors, buffer)?;
+        let apb_lock = self.start(buffer.len(), 0);
+
+        Ok(DmaTransfer::new(self, buffer, apb_lock))
+    }
+
+    /// Start receiving into the buffer in the background
+    ///
+    /// The transmit line stays idle during the transfer.
+    pub fn start_read(
+        mut self,
+        buffer: &'static mut [u8],
+    ) -> Result<DmaTransfer<INSTANCE, SCLK, SDO, SDI, CS, WP, HD, CS1, CS2, &'static mut [u8]>, Error>
+    {
+        dma::link_rx(self.rx_descriptors, buffer, false)?;
+        let apb_lock = self.start(0, buffer.len());
+
+        Ok(DmaTransfer::new(self, buffer, apb_lock))
+    }
+
+    /// Start a full duplex transfer in the background
+    ///
+    /// Sends the transmit buffer while receiving into the receive buffer, which must have the
+    /// same length.
+    pub fn start_transfer(
+        mut self,
+        tx_buffer: &'static [u8],
+        rx_buffer: &'static mut [u8],
+    ) -> Result<
+        DmaTransfer<
+            INSTANCE,
+            SCLK,
+            SDO,
+            SDI,
+            CS,
+            WP,
+            HD,
+            CS1,
+            CS2,
+            (&'static [u8], &'static mut [u8]),
+        >,
+        Error,
+    > {
+        if tx_buffer.len() != rx_buffer.len() {
+            return Err(Error::LengthMismatch);
+        }
+
+        dma::link_tx(self.tx_descriptors, tx_buffer)?;
+        dma::link_rx(self.rx_descriptors, rx_buffer, false)?;
+        let apb_lock = self.start(tx_buffer.len(), rx_buffer.len());
+
+        Ok(DmaTransfer::new(self, (tx_buffer, rx_buffer), apb_lock))
+    }
+
+    /// Enable both data phases, as used by the transfers via the data buffer of the controller
+    fn enable_data_phases(&self) {
+        self.spi
+            .instance
+            .user
+            .modify(|_, w| w.usr_mosi().set_bit().usr_miso().set_bit());
+    }
+
+    /// Start a transaction with the linked descriptors
+    ///
+    /// A length of 0 disables the corresponding phase.
+    fn start(&mut self, tx_length: usize, rx_length: usize) -> LockAPB {
+        let apb_lock = self.spi.clock_control.lock_apb_frequency();
+        let spi = &self.spi.instance;
+
+        while spi.cmd.read().usr().bit_is_set() {}
+
+        spi.dma_conf.modify(|_, w| {
+            w.in_rst()
+                .set_bit()
+                .out_rst()
+                .set_bit()
+                .ahbm_rst()
+                .set_bit()
+                .ahbm_fifo_rst()
+                .set_bit()
+        });
+        spi.dma_conf.modify(|_, w| {
+            w.in_rst()
+                .clear_bit()
+                .out_rst()
+                .clear_bit()
+                .ahbm_rst()
+                .clear_bit()
+                .ahbm_fifo_rst()
+                .clear_bit()
+        });
+        spi.dma_int_clr.write(|w| unsafe { w.bits(0xffff_ffff) });
+
+        // make sure the descriptors and buffers are written before the DMA engine reads them
+        compiler_fence(Ordering::SeqCst);
+
+        if rx_length > 0 {
+            let address = self.rx_descriptors[0].address();
+            spi.dma_in_link.modify(|_, w| unsafe {
+                w.inlink_addr()
+                    .bits(address & 0xfffff)
+                    .inlink_start()
+                    .set_bit()
+            });
+        }
+        if tx_length > 0 {
+            let address = self.tx_descriptors[0].address();
+            spi.dma_out_link.modify(|_, w| unsafe {
+                w.outlink_addr()
+                    .bits(address & 0xfffff)
+                    .outlink_start()
+                    .set_bit()
+            });
+        }
+
+        spi.user.modify(|_, w| {
+            w.usr_mosi()
+                .bit(tx_length > 0)
+                .usr_miso()
+                .bit(rx_length > 0)
+        });
+        spi.mosi_dlen.write(|w| unsafe {
+            w.usr_mosi_dbitlen()
+                .bits((tx_length as u32 * 8).saturating_sub(1))
+        });
+        spi.miso_dlen.write(|w| unsafe {
+            w.usr_miso_dbitlen()
+                .bits((rx_length as u32 * 8).saturating_sub(1))
+        });
+
+        spi.cmd.modify(|_, w| w.usr().set_bit());
+
+        apb_lock
+    }
+
+    /// Check if the current transaction has finished
+    fn poll(&mut self) -> nb::Result<(), Error> {
+        let spi = &self.spi.instance;
+
+        if spi.dma_int_raw.read().bits() & (OUTLINK_DSCR_ERROR | INLINK_DSCR_ERROR) != 0 {
+            return Err(nb::Error::Other(Error::Dma(dma::Error::DescriptorError)));
+        }
+
+        if spi.cmd.read().usr().bit_is_set() {
+            return Err(nb::Error::WouldBlock);
+        }
+
+        // do not read the received data before the transaction has finished
+        compiler_fence(Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Stop the DMA engine and wait for the SPI transaction to end
+    fn abort(&mut self) {
+        let spi = &self.spi.instance;
+        spi.dma_out_link.modify(|_, w| w.outlink_stop().set_bit());
+        spi.dma_in_link.modify(|_, w| w.inlink_stop().set_bit());
+        while spi.cmd.read().usr().bit_is_set() {}
+    }
+
+    /// Abort the transaction unless it has finished successfully
+    fn stop(&mut self) {
+        if self.poll().is_err() {
+            self.abort();
+        }
+    }
+
+    /// Run a blocking transaction with the linked descriptors
+    fn run(&mut self, tx_length: usize, rx_length: usize) -> Result<(), Error> {
+        let apb_lock = self.start(tx_length, rx_length);
+        let result = nb::block!(self.poll());
+        if result.is_err() {
+            self.abort();
+        }
+        drop(apb_lock);
+        result
+    }
+}
+
+/// Background SPI DMA transfer
+///
+/// `BUFFERS` are the buffers used by the transfer, which are given back by [DmaTransfer::release].
+/// Dropping the transfer aborts it and releases the DMA channel, the SPI controller is dropped as
+/// well.
+pub struct DmaTransfer<
+    INSTANCE: GeneralInstance,
+    SCLK: OutputPin,
+    SDO: OutputPin,
+    SDI: InputPin + OutputPin,
+    CS: OutputPin,
+    WP: InputPin + OutputPin,
+    HD: InputPin + OutputPin,
+    CS1: OutputPin,
+    CS2: OutputPin,
+    BUFFERS,
+> {
+    /// Taken by [DmaTransfer::release]
+    inner: Option<(
+        SpiDma<INSTANCE, SCLK, SDO, SDI, CS, WP, HD, CS1, CS2>,
+        BUFFERS,
+    )>,
+    _apb_lock: LockAPB,
+}
+
+impl<
+        INSTANCE: GeneralInstance,
+        SCLK: OutputPin,
+        SDO: OutputPin,
+        SDI: InputPin + OutputPin,
+        CS: OutputPin,
+        WP: InputPin + OutputPin,
+        HD: InputPin + OutputPin,
+        CS1: OutputPin,
+        CS2: OutputPin,
+        BUFFERS,
+    > DmaTransfer<INSTANCE, SCLK, SDO, SDI, CS, WP, HD, CS1, CS2, BUFFERS>
+{
+    fn new(
+        spi: SpiDma<INSTANCE, SCLK, SDO, SDI, CS, WP, HD, CS1, CS2>,
+        buffers: BUFFERS,
+        apb_lock: LockAPB,
+    ) -> Self {
+        DmaTransfer {
+            inner: Some((spi, buffers)),
+            _apb_lock: apb_lock,
+        }
+    }
+
+    fn spi(&mut self) -> &mut SpiDma<INSTANCE, SCLK, SDO, SDI, CS, WP, HD, CS1, CS2> {
+        // only None after release
+        &mut self.inner.as_mut().unwrap().0
+    }
+
+    /// Check if the transfer has finished
+    pub fn poll(&mut self) -> nb::Result<(), Error> {
+        self.spi().poll()
+    }
+
+    /// Returns true if the transfer has finished (or failed)
+    pub fn is_done(&mut self) -> bool {
+        match self.poll() {
+            Err(nb::Error::WouldBlock) => false,
+            _ => true,
+        }
+    }
+
+    /// Wait for the transfer to finish
+    pub fn wait(&mut self) -> Result<(), Error> {
+        nb::block!(self.poll())
+    }
+
+    /// Release the controller and buffers, aborting the transfer if it is still running or has
+    /// failed
+    pub fn release(
+        mut self,
+    ) -> (
+        SpiDma<INSTANCE, SCLK, SDO, SDI, CS, WP, HD, CS1, CS2>,
+        BUFFERS,
+    ) {
+        self.spi().stop();
+        self.inner.take().unwrap()
+    }
+}
+
+impl<
+        INSTANCE: GeneralInstance,
+        SCLK: OutputPin,
+        SDO: OutputPin,
+        SDI: InputPin + OutputPin,
+        CS: OutputPin,
+        WP: InputPin + OutputPin,
+        HD: InputPin + OutputPin,
+        CS1: OutputPin,
+        CS2: OutputPin,
+        BUFFERS,
+    > Drop for DmaTransfer<INSTANCE, SCLK, SDO, SDI, CS, WP, HD, CS1, CS2, BUFFERS>
+{
+    fn drop(&mut self) {
+        if let Some((mut spi, _)) = self.inner.take() {
+            spi.stop();
+            spi.release();
+        }
+    }
+}
+
+impl<
+        INSTANCE: GeneralInstance,
+        SCLK: OutputPin,
+        SDO: OutputPin,
+        SDI: InputPin + OutputPin,
+        CS: OutputPin,
+        WP: InputPin + OutputPin,
+        HD: InputPin + OutputPin,
+        CS1: OutputPin,
+        CS2: OutputPin,
+    > Transfer<u8> for SpiDma<INSTANCE, SCLK, SDO, SDI, CS, WP, HD, CS1, CS2>
+{
+    type Error = Error;
+
+    /// The data is received into a buffer on the stack and copied back after each part of
+    /// [BOUNCE_BUFFER_SIZE] bytes, as the DMA engine does not guarantee to read a location before
+    /// writing the received data to it. CS stays active between the parts.
+    fn transfer<'w>(&mut self, words: &'w mut [u8]) -> Result<&'w [u8], Error> {
+        let mut bounce_buffer = [0u32; BOUNCE_BUFFER_WORDS];
+        let bounce_buffer =
+            unsafe { &mut *(&mut bounce_buffer as *mut _ as *mut [u8; BOUNCE_BUFFER_SIZE]) };
+
+        // all parts can be linked if the first one can
+        let first = words.len().min(BOUNCE_BUFFER_SIZE);
+        if dma::link_tx(self.tx_descriptors, &words[..first]).is_err()
+            || dma::link_rx(
+                self.rx_descriptors,
+                &mut bounce_buffer[..word_length(first)],
+                false,
+            )
+            .is_err()
+        {
+            self.enable_data_phases();
+            return self.spi.transfer(words);
+        }
+
+        self.spi
+            .instance
+            .pin
+            .modify(|_, w| w.cs_keep_active().set_bit());
+
+        let result =
+            words
+                .chunks_mut(BOUNCE_BUFFER_SIZE)
+                .try_for_each(|chunk| -> Result<(), Error> {
+                    // the DMA engine receives whole words
+                    let received = &mut bounce_buffer[..word_length(chunk.len())];
+                    dma::link_tx(self.tx_descriptors, chunk)?;
+                    dma::link_rx(self.rx_descriptors, received, false)?;
+                    self.run(chunk.len(), chunk.len())?;
+                    chunk.copy_from_slice(&received[..chunk.len()]);
+                    Ok(())
+                });
+
+        self.spi
+            .instance
+            .pin
+            .modify(|_, w| w.cs_keep_active().clear_bit());
+
+        result?;
+        Ok(words)
+    }
+}
+
+impl<
+        INSTANCE: GeneralInstance,
+        SCLK: OutputPin,
+        SDO: OutputPin,
+        SDI: InputPin + OutputPin,
+        CS: OutputPin,
+        WP: InputPin + OutputPin,
+        HD: InputPin + OutputPin,
+        CS1: OutputPin,
+        CS2: OutputPin,
+    > Write<u8> for SpiDma<INSTANCE, SCLK, SDO, SDI, CS, WP, HD, CS1, CS2>
+{
+    type Error = Error;
+
+    fn write(&mut self, words: &[u8]) -> Result<(), Error> {
+        if dma::link_tx(self.tx_descriptors, words).is_err() {
+            self.enable_data_phases();
+            return self.spi.write(words);
+        }
+
+        self.run(words.len(), 0)
+    }
+}
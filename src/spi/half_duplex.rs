@@ -0,0 +1,197 @@
+//! SPI half duplex 3-wire, dual and quad modes
+//!
+//! In the 3-wire mode a single data line (the SDO pin) is used in both directions: the
+//! controller first sends the data of the MOSI phase and then receives the data of the MISO
+//! phase on the same line. The SDI pin is not used.
+//!
+//! In the dual and quad modes (see [change_io_mode](HalfDuplex::change_io_mode)) the data is
+//! transferred over 2 (SDO, SDI) or 4 (SDO, SDI, WP, HD) lines, optionally including the
+//! address phase. These modes are only available via [HalfDuplex]: the full duplex [SPI] and
+//! the [SpiDma](super::dma::SpiDma) always use a single line in each direction.
+//!
+//! *Note: the esp32 cannot send the command phase on 2 or 4 lines, it always uses a single line
+//! (SDO). Devices expecting a dual or quad command (e.g. flash chips in QPI mode) are not
+//! supported.*
+//!
+//! Each hardware transaction transfers at most 64 bytes in each phase. For longer writes and
+//! reads the CS line is kept active over multiple transactions. Command, address and dummy
+//! phases can be added with [transaction](HalfDuplex::transaction).
+//!
+//! # Example
+//! ```
+//! let mut sensor = HalfDuplex::new(spi);
+//!
+//! let mut id = [0u8; 1];
+//! sensor.write_read(&[0xd0 | 0x80], &mut id).unwrap();
+//! sensor.write(&[0x74 & 0x7f, 0x27]).unwrap();
+//!
+//! let mut flash = HalfDuplex::new(spi);
+//! flash.change_io_mode(IoMode::Quad).unwrap();
+//! ```
+
+use super::{config::IoMode, private::GeneralInstance, transaction::Transaction, Error, SPI};
+use crate::gpio::{InputPin, OutputPin};
+use embedded_hal::blocking::spi::Write;
+
+/// SPI controller in half duplex 3-wire, dual or quad mode
+pub struct HalfDuplex<
+    INSTANCE: GeneralInstance,
+    SCLK: OutputPin,
+    SDIO: InputPin + OutputPin,
+    SDI: InputPin + OutputPin,
+    CS: OutputPin,
+    WP: InputPin + OutputPin,
+    HD: InputPin + OutputPin,
+    CS1: OutputPin,
+    CS2: OutputPin,
+> {
+    spi: SPI<INSTANCE, SCLK, SDIO, SDI, CS, WP, HD, CS1, CS2>,
+}
+
+impl<
+        INSTANCE: GeneralInstance,
+        SCLK: OutputPin,
+        SDIO: InputPin + OutputPin,
+        SDI: InputPin + OutputPin,
+        CS: OutputPin,
+        WP: InputPin + OutputPin,
+        HD: InputPin + OutputPin,
+        CS1: OutputPin,
+        CS2: OutputPin,
+    > HalfDuplex<INSTANCE, SCLK, SDIO, SDI, CS, WP, HD, CS1, CS2>
+{
+    /// Switch the SPI controller to the half duplex 3-wire mode
+    ///
+    /// The SDO pin becomes the bidirectional data line.
+    pub fn new(mut spi: SPI<INSTANCE, SCLK, SDIO, SDI, CS, WP, HD, CS1, CS2>) -> Self {
+        spi.instance.init_sio_pin(&mut spi.pins.sdo);
+
+        spi.instance.user.modify(|_, w| {
+            w.doutdin()
+                .clear_bit()
+                .sio()
+                .set_bit()
+                .usr_mosi()
+                .clear_bit()
+                .usr_miso()
+                .clear_bit()
+        });
+
+        HalfDuplex { spi }
+    }
+
+    /// Change the number of data lines
+    ///
+    /// The dual modes require the SDI pin, the quad modes also the WP and HD pins. The command
+    /// phase of [transaction](HalfDuplex::transaction) always uses a single line.
+    pub fn change_io_mode(&mut self, mode: IoMode) -> Result<&mut Self, Error> {
+        let pins = &mut self.spi.pins;
+        let quad = mode == IoMode::Quad || mode == IoMode::QuadIo;
+
+        if quad && (pins.wp.is_none() || pins.hd.is_none()) {
+            return Err(Error::MissingPin);
+        }
+
+        match &mut pins.sdi {
+            Some(sdi) => {
+                self.spi.instance.init_dual_pin(sdi, mode != IoMode::Single);
+            }
+            None if mode != IoMode::Single => return Err(Error::MissingPin),
+            None => {}
+        }
+
+        self.set_io_mode(mode);
+
+        Ok(self)
+    }
+
+    fn set_io_mode(&mut self, mode: IoMode) {
+        self.spi.instance.ctrl.modify(|_, w| {
+            w.fread_dual()
+                .bit(mode == IoMode::Dual)
+                .fread_dio()
+                .bit(mode == IoMode::DualIo)
+                .fread_quad()
+                .bit(mode == IoMode::Quad)
+                .fread_qio()
+                .bit(mode == IoMode::QuadIo)
+                .fastrd_mode()
+                .bit(mode != IoMode::Single)
+        });
+
+        self.spi.instance.user.modify(|_, w| {
+            w.sio()
+                .bit(mode == IoMode::Single)
+                .fwrite_dual()
+                .bit(mode == IoMode::Dual)
+                .fwrite_dio()
+                .bit(mode == IoMode::DualIo)
+                .fwrite_quad()
+                .bit(mode == IoMode::Quad)
+                .fwrite_qio()
+                .bit(mode == IoMode::QuadIo)
+        });
+    }
+
+    /// Switch back to the full duplex mode and release the SPI controller
+    pub fn release(mut self) -> SPI<INSTANCE, SCLK, SDIO, SDI, CS, WP, HD, CS1, CS2> {
+        self.set_io_mode(IoMode::Single);
+        if let Some(sdi) = &mut self.spi.pins.sdi {
+            self.spi.instance.init_dual_pin(sdi, false);
+        }
+
+        self.spi.instance.user.modify(|_, w| {
+            w.doutdin()
+                .set_bit()
+                .sio()
+                .clear_bit()
+                .usr_mosi()
+                .set_bit()
+                .usr_miso()
+                .set_bit()
+        });
+        self.spi.pins.sdo.enable_input(false);
+
+        self.spi
+    }
+
+    /// Run a transaction with command, address and dummy phases in the current mode
+    ///
+    /// This function locks the APB frequency.
+    pub fn transaction(&mut self, transaction: Transaction) -> Result<(), Error> {
+        self.spi.transaction_internal(transaction)
+    }
+
+    /// Write data, then read into the buffer while keeping CS active
+    ///
+    /// This function locks the APB frequency.
+    pub fn write_read(&mut self, write: &[u8], read: &mut [u8]) -> Result<(), Error> {
+        self.transaction(Transaction::new().write(write).read(read))
+    }
+
+    /// Read into the buffer
+    ///
+    /// This function locks the APB frequency.
+    pub fn read(&mut self, read: &mut [u8]) -> Result<(), Error> {
+        self.write_read(&[], read)
+    }
+}
+
+impl<
+        INSTANCE: GeneralInstance,
+        SCLK: OutputPin,
+        SDIO: InputPin + OutputPin,
+        SDI: InputPin + OutputPin,
+        CS: OutputPin,
+        WP: InputPin + OutputPin,
+        HD: InputPin + OutputPin,
+        CS1: OutputPin,
+        CS2: OutputPin,
+    > Write<u8> for HalfDuplex<INSTANCE, SCLK, SDIO, SDI, CS, WP, HD, CS1, CS2>
+{
+    type Error = Error;
+
+    fn write(&mut self, words: &[u8]) -> Result<(), Error> {
+        self.write_read(words, &mut [])
+    }
+}
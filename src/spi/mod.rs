@@ -1,6 +1,6 @@
 //! SPI peripheral control
 //!
-//! Currently only implements full duplex controller mode support.
+//! Implements controller mode support.
 //!
 //! SPI0 is reserved for accessing flash and sram and therefore not usable for other purposes.
 //! SPI1 shares its external pins with SPI0 and therefore has severe restrictions in use.
@@ -14,11 +14,13 @@
 //! The primitive [FullDuplex::read] and [FullDuplex::send] do not lock the APB frequency and
 //! therefore may run at a different frequency.
 //!
-//! # TODO
-//! - Quad SPI
-//! - Half Duplex
-//! - DMA
-//! - Multiple CS pins
+//! For large transfers on SPI2 and SPI3 see [SpiDma](dma::SpiDma), for the 3-wire, dual and
+//! quad half duplex modes see [HalfDuplex] (the dual and quad modes are only available in half
+//! duplex mode, and the command phase always uses a single line). Transactions with command,
+//! address and dummy phases are run with [SPI::transaction] and [HalfDuplex::transaction].
+//!
+//! SPI2 and SPI3 support up to three devices with hardware chip select lines. The [SpiBus]
+//! shares a controller between devices with their own configuration, see the [bus] module.
 
 use crate::prelude::*;
 
@@ -33,7 +35,19 @@ use {
     embedded_hal::spi::FullDuplex,
 };
 
-use private::Instance;
+pub mod bus;
+pub mod dma;
+pub mod half_duplex;
+pub mod transaction;
+
+pub use bus::{SpiBus, SpiDevice};
+pub use half_duplex::HalfDuplex;
+pub use transaction::Transaction;
+
+use private::{GeneralInstance, Instance};
+
+/// Number of bytes in the data buffer of the controller
+const BUFFER_SIZE: usize = 64;
 
 /// SPI Errors
 #[derive(Debug)]
@@ -42,20 +56,60 @@ pub enum Error {
     BaudrateTooLow,
     ConversionFailed,
     PinError,
+    /// DMA descriptor or buffer error
+    Dma(crate::dma::Error),
+    /// The DMA channel is already used by the other SPI controller
+    DmaChannelInUse,
+    /// Transmit and receive buffers have different lengths
+    LengthMismatch,
+    /// A pin required for the mode is not configured
+    MissingPin,
+    /// Command or address phase length out of range
+    InvalidPhaseLength,
+    /// The [SpiBus] has not been initialized or the controller was released
+    BusNotInitialized,
+}
+
+impl From<crate::dma::Error> for Error {
+    fn from(error: crate::dma::Error) -> Self {
+        Error::Dma(error)
+    }
 }
 
 /// Pins used by the SPI interface
+///
+/// `cs` is the CS0 line, the CS1 and CS2 lines are only available on SPI2 and SPI3 and are
+/// selected with [select_cs](SPI::select_cs) or via the [SpiBus].
+///
+/// The WP (IO2) and HD (IO3) pins are only needed for the quad modes, see
+/// [change_io_mode](HalfDuplex::change_io_mode).
 pub struct Pins<
     SCLK: OutputPin,
     SDO: OutputPin,
     // default pins to allow type inference
     SDI: InputPin + OutputPin = crate::gpio::Gpio1<crate::gpio::Input<crate::gpio::Floating>>,
     CS: OutputPin = crate::gpio::Gpio2<crate::gpio::Output<crate::gpio::PushPull>>,
+    WP: InputPin + OutputPin = crate::gpio::Gpio1<crate::gpio::Input<crate::gpio::Floating>>,
+    HD: InputPin + OutputPin = crate::gpio::Gpio1<crate::gpio::Input<crate::gpio::Floating>>,
+    CS1: OutputPin = crate::gpio::Gpio2<crate::gpio::Output<crate::gpio::PushPull>>,
+    CS2: OutputPin = crate::gpio::Gpio2<crate::gpio::Output<crate::gpio::PushPull>>,
 > {
     pub sclk: SCLK,
     pub sdo: SDO,
     pub sdi: Option<SDI>,
     pub cs: Option<CS>,
+    pub wp: Option<WP>,
+    pub hd: Option<HD>,
+    pub cs1: Option<CS1>,
+    pub cs2: Option<CS2>,
+}
+
+/// Hardware chip select line
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum ChipSelect {
+    Cs0,
+    Cs1,
+    Cs2,
 }
 
 /// SPI configuration
@@ -71,7 +125,7 @@ pub mod config {
     }
 
     /// SPI configuration
-    #[derive(Copy, Clone)]
+    #[derive(PartialEq, Eq, Copy, Clone)]
     pub struct Config {
         pub baudrate: Hertz,
         pub data_mode: embedded_hal::spi::Mode,
@@ -95,6 +149,25 @@ pub mod config {
         }
     }
 
+    /// Number of data lines used in the address and data phases
+    ///
+    /// Only used by [HalfDuplex](super::HalfDuplex), see
+    /// [change_io_mode](super::HalfDuplex::change_io_mode). The command phase always uses a
+    /// single line, as the esp32 has no dual or quad command phase.
+    #[derive(PartialEq, Eq, Copy, Clone, Debug)]
+    pub enum IoMode {
+        /// Single bidirectional data line (SDO), i.e. the 3-wire mode
+        Single,
+        /// Data phases on 2 lines (SDO and SDI), address on a single line
+        Dual,
+        /// Address and data phases on 2 lines (SDO and SDI)
+        DualIo,
+        /// Data phases on 4 lines (SDO, SDI, WP and HD), address on a single line
+        Quad,
+        /// Address and data phases on 4 lines (SDO, SDI, WP and HD)
+        QuadIo,
+    }
+
     impl Default for Config {
         fn default() -> Config {
             Config {
@@ -114,9 +187,13 @@ pub struct SPI<
     // default pins to allow type inference
     SDI: InputPin + OutputPin = crate::gpio::Gpio1<crate::gpio::Input<crate::gpio::Floating>>,
     CS: OutputPin = crate::gpio::Gpio2<crate::gpio::Output<crate::gpio::PushPull>>,
+    WP: InputPin + OutputPin = crate::gpio::Gpio1<crate::gpio::Input<crate::gpio::Floating>>,
+    HD: InputPin + OutputPin = crate::gpio::Gpio1<crate::gpio::Input<crate::gpio::Floating>>,
+    CS1: OutputPin = crate::gpio::Gpio2<crate::gpio::Output<crate::gpio::PushPull>>,
+    CS2: OutputPin = crate::gpio::Gpio2<crate::gpio::Output<crate::gpio::PushPull>>,
 > {
     instance: INSTANCE,
-    pins: Pins<SCLK, SDO, SDI, CS>,
+    pins: Pins<SCLK, SDO, SDI, CS, WP, HD, CS1, CS2>,
     clock_control: ClockControlConfig,
 }
 
@@ -131,7 +208,8 @@ impl<CS: OutputPin>
 {
     /// Create new instance of SPI controller for SPI1
     ///
-    /// SPI1 can only use fixed pin for SCLK, SDO and SDI as they are shared with SPI0.
+    /// SPI1 can only use fixed pin for SCLK, SDO and SDI as they are shared with SPI0. It has a
+    /// single chip select line, so [Error::PinError] is returned when the CS1 or CS2 pin is set.
     pub fn new(
         instance: SPI1,
         pins: Pins<
@@ -143,17 +221,28 @@ impl<CS: OutputPin>
         config: config::Config,
         clock_control: ClockControlConfig,
     ) -> Result<Self, Error> {
+        if pins.cs1.is_some() || pins.cs2.is_some() {
+            return Err(Error::PinError);
+        }
         SPI::new_internal(instance, pins, config, clock_control)
     }
 }
 
-impl<SCLK: OutputPin, SDO: OutputPin, SDI: InputPin + OutputPin, CS: OutputPin>
-    SPI<SPI2, SCLK, SDO, SDI, CS>
+impl<
+        SCLK: OutputPin,
+        SDO: OutputPin,
+        SDI: InputPin + OutputPin,
+        CS: OutputPin,
+        WP: InputPin + OutputPin,
+        HD: InputPin + OutputPin,
+        CS1: OutputPin,
+        CS2: OutputPin,
+    > SPI<SPI2, SCLK, SDO, SDI, CS, WP, HD, CS1, CS2>
 {
     /// Create new instance of SPI controller for SPI2
     pub fn new(
         instance: SPI2,
-        pins: Pins<SCLK, SDO, SDI, CS>,
+        pins: Pins<SCLK, SDO, SDI, CS, WP, HD, CS1, CS2>,
         config: config::Config,
         clock_control: ClockControlConfig,
     ) -> Result<Self, Error> {
@@ -161,13 +250,21 @@ impl<SCLK: OutputPin, SDO: OutputPin, SDI: InputPin + OutputPin, CS: OutputPin>
     }
 }
 
-impl<SCLK: OutputPin, SDO: OutputPin, SDI: InputPin + OutputPin, CS: OutputPin>
-    SPI<SPI3, SCLK, SDO, SDI, CS>
+impl<
+        SCLK: OutputPin,
+        SDO: OutputPin,
+        SDI: InputPin + OutputPin,
+        CS: OutputPin,
+        WP: InputPin + OutputPin,
+        HD: InputPin + OutputPin,
+        CS1: OutputPin,
+        CS2: OutputPin,
+    > SPI<SPI3, SCLK, SDO, SDI, CS, WP, HD, CS1, CS2>
 {
     /// Create new instance of SPI controller for SPI3
     pub fn new(
         instance: SPI3,
-        pins: Pins<SCLK, SDO, SDI, CS>,
+        pins: Pins<SCLK, SDO, SDI, CS, WP, HD, CS1, CS2>,
         config: config::Config,
         clock_control: ClockControlConfig,
     ) -> Result<Self, Error> {
@@ -181,12 +278,16 @@ impl<
         SDO: OutputPin,
         SDI: InputPin + OutputPin,
         CS: OutputPin,
-    > SPI<INSTANCE, SCLK, SDO, SDI, CS>
+        WP: InputPin + OutputPin,
+        HD: InputPin + OutputPin,
+        CS1: OutputPin,
+        CS2: OutputPin,
+    > SPI<INSTANCE, SCLK, SDO, SDI, CS, WP, HD, CS1, CS2>
 {
     /// Internal implementation of new shared by all SPI controllers
     fn new_internal(
         instance: INSTANCE,
-        pins: Pins<SCLK, SDO, SDI, CS>,
+        pins: Pins<SCLK, SDO, SDI, CS, WP, HD, CS1, CS2>,
         config: config::Config,
         clock_control: ClockControlConfig,
     ) -> Result<Self, Error> {
@@ -226,13 +327,18 @@ impl<
             spi.instance.clock.write(|w| w.bits(0));
         }
 
-        spi.change_data_mode(config.data_mode)
-            .change_bit_order(config.bit_order)
-            .change_baudrate(config.baudrate)?;
+        spi.change_config(config)?;
 
         Ok(spi)
     }
 
+    /// Change the baudrate, data mode and bit order
+    pub fn change_config(&mut self, config: config::Config) -> Result<&mut Self, Error> {
+        self.change_data_mode(config.data_mode)
+            .change_bit_order(config.bit_order)
+            .change_baudrate(config.baudrate)
+    }
+
     /// Convert SPI division factor back to frequency
     fn divider_to_frequency(apb_freq: Hertz, div1: u32, div2: u32) -> Hertz {
         apb_freq / ((div1 + 1) * (div2 + 1))
@@ -367,6 +473,25 @@ impl<
         self
     }
 
+    /// Run a transaction with command, address and dummy phases
+    ///
+    /// The data is written on SDO and then read on SDI, i.e. the data phases are half duplex.
+    /// The previous mode of the controller is restored afterwards.
+    ///
+    /// This function locks the APB frequency.
+    pub fn transaction(&mut self, transaction: Transaction) -> Result<(), Error> {
+        while self.instance.cmd.read().usr().bit_is_set() {}
+
+        let user = self.instance.user.read().bits();
+        self.instance.user.modify(|_, w| w.doutdin().clear_bit());
+
+        let result = self.transaction_internal(transaction);
+
+        self.instance.user.write(|w| unsafe { w.bits(user) });
+
+        result
+    }
+
     /// Release and return the raw interface to the underlying SPI peripheral
     pub fn release(self) -> INSTANCE {
         self.instance
@@ -444,6 +569,136 @@ impl<
         Ok(words)
     }
 
+    /// Run a transaction in the current mode of the controller
+    ///
+    /// The controller must be in half duplex mode (`doutdin` cleared). The data phases are
+    /// split into hardware transactions of at most [BUFFER_SIZE] bytes while keeping CS active.
+    ///
+    /// This function locks the APB bus frequency.
+    fn transaction_internal(&mut self, transaction: Transaction) -> Result<(), Error> {
+        if !transaction.has_valid_lengths() {
+            return Err(Error::InvalidPhaseLength);
+        }
+        if transaction.is_empty() {
+            return Ok(());
+        }
+
+        let Transaction {
+            command,
+            address,
+            dummy_cycles,
+            write,
+            read,
+        } = transaction;
+
+        let apb_lock = self.clock_control.lock_apb_frequency();
+        let spi = &self.instance;
+
+        while spi.cmd.read().usr().bit_is_set() {}
+
+        let lsb_first = spi.ctrl.read().wr_bit_order().bit_is_set();
+
+        if let Some((value, bits)) = command {
+            spi.user2.write(|w| unsafe {
+                w.usr_command_bitlen()
+                    .bits(bits - 1)
+                    .usr_command_value()
+                    .bits(transaction::command_register_value(value, bits, lsb_first))
+            });
+        }
+        if let Some((value, bits)) = address {
+            spi.user1
+                .modify(|_, w| unsafe { w.usr_addr_bitlen().bits(bits - 1) });
+            spi.addr.write(|w| unsafe {
+                w.bits(transaction::address_register_value(value, bits, lsb_first))
+            });
+        }
+        if dummy_cycles > 0 {
+            spi.user1
+                .modify(|_, w| unsafe { w.usr_dummy_cyclelen().bits(dummy_cycles - 1) });
+        }
+        spi.user.modify(|_, w| {
+            w.usr_command()
+                .bit(command.is_some())
+                .usr_addr()
+                .bit(address.is_some())
+                .usr_dummy()
+                .bit(dummy_cycles > 0)
+        });
+
+        spi.pin.modify(|_, w| w.cs_keep_active().set_bit());
+
+        // the last part of the write is combined with the first part of the read
+        let (write_head, write_tail) = match write.len() {
+            0 => (write, write),
+            length => write.split_at((length - 1) / BUFFER_SIZE * BUFFER_SIZE),
+        };
+        let (read_head, read_tail) = read.split_at_mut(read.len().min(BUFFER_SIZE));
+
+        for chunk in write_head.chunks(BUFFER_SIZE) {
+            self.exchange(chunk, &mut []);
+        }
+        self.exchange(write_tail, read_head);
+        for chunk in read_tail.chunks_mut(BUFFER_SIZE) {
+            self.exchange(&[], chunk);
+        }
+
+        self.instance
+            .pin
+            .modify(|_, w| w.cs_keep_active().clear_bit());
+
+        drop(apb_lock);
+
+        Ok(())
+    }
+
+    /// Run a single hardware transaction of at most [BUFFER_SIZE] bytes per data phase
+    ///
+    /// The command, address and dummy phases are disabled afterwards, so they are only part of
+    /// the first hardware transaction.
+    fn exchange(&mut self, write: &[u8], read: &mut [u8]) {
+        let spi = &self.instance;
+
+        for (index, chunk) in write.chunks(4).enumerate() {
+            let mut bytes = [0u8; 4];
+            bytes[..chunk.len()].copy_from_slice(chunk);
+            spi.w[index].write(|w| unsafe { w.bits(u32::from_le_bytes(bytes)) });
+        }
+
+        spi.user.modify(|_, w| {
+            w.usr_mosi()
+                .bit(!write.is_empty())
+                .usr_miso()
+                .bit(!read.is_empty())
+        });
+        spi.mosi_dlen.write(|w| unsafe {
+            w.usr_mosi_dbitlen()
+                .bits((write.len() as u32 * 8).saturating_sub(1))
+        });
+        spi.miso_dlen.write(|w| unsafe {
+            w.usr_miso_dbitlen()
+                .bits((read.len() as u32 * 8).saturating_sub(1))
+        });
+
+        spi.cmd.modify(|_, w| w.usr().set_bit());
+        while spi.cmd.read().usr().bit_is_set() {}
+
+        spi.user.modify(|_, w| {
+            w.usr_command()
+                .clear_bit()
+                .usr_addr()
+                .clear_bit()
+                .usr_dummy()
+                .clear_bit()
+        });
+
+        // the received data is stored from the start of the buffer
+        for (index, chunk) in read.chunks_mut(4).enumerate() {
+            let bytes = spi.w[index].read().bits().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+
     /// Generic write function for iterators
     ///
     /// This function locks the APB bus frequency and chunks the output of the iterator
@@ -507,6 +762,46 @@ impl U8orU16orU32 for u8 {}
 impl U8orU16orU32 for u16 {}
 impl U8orU16orU32 for u32 {}
 
+impl<
+        INSTANCE: GeneralInstance,
+        SCLK: OutputPin,
+        SDO: OutputPin,
+        SDI: InputPin + OutputPin,
+        CS: OutputPin,
+        WP: InputPin + OutputPin,
+        HD: InputPin + OutputPin,
+        CS1: OutputPin,
+        CS2: OutputPin,
+    > SPI<INSTANCE, SCLK, SDO, SDI, CS, WP, HD, CS1, CS2>
+{
+    /// Select the hardware chip select line used by the following transfers
+    ///
+    /// The pin of the line must be configured.
+    pub fn select_cs(&mut self, cs: ChipSelect) -> Result<&mut Self, Error> {
+        let configured = match cs {
+            ChipSelect::Cs0 => self.pins.cs.is_some(),
+            ChipSelect::Cs1 => self.pins.cs1.is_some(),
+            ChipSelect::Cs2 => self.pins.cs2.is_some(),
+        };
+        if !configured {
+            return Err(Error::MissingPin);
+        }
+
+        while self.instance.cmd.read().usr().bit_is_set() {}
+
+        self.instance.pin.modify(|_, w| {
+            w.cs0_dis()
+                .bit(cs != ChipSelect::Cs0)
+                .cs1_dis()
+                .bit(cs != ChipSelect::Cs1)
+                .cs2_dis()
+                .bit(cs != ChipSelect::Cs2)
+        });
+
+        Ok(self)
+    }
+}
+
 /// Full-duplex implementation for writing/reading via SPI
 ///
 /// *Note: these functions do not lock the frequency of the APB bus, so transactions may be
@@ -518,7 +813,11 @@ impl<
         SDO: OutputPin,
         SDI: InputPin + OutputPin,
         CS: OutputPin,
-    > FullDuplex<T> for SPI<INSTANCE, SCLK, SDO, SDI, CS>
+        WP: InputPin + OutputPin,
+        HD: InputPin + OutputPin,
+        CS1: OutputPin,
+        CS2: OutputPin,
+    > FullDuplex<T> for SPI<INSTANCE, SCLK, SDO, SDI, CS, WP, HD, CS1, CS2>
 {
     type Error = Error;
 
@@ -564,7 +863,11 @@ impl<
         SDO: OutputPin,
         SDI: InputPin + OutputPin,
         CS: OutputPin,
-    > Transfer<u8> for SPI<INSTANCE, SCLK, SDO, SDI, CS>
+        WP: InputPin + OutputPin,
+        HD: InputPin + OutputPin,
+        CS1: OutputPin,
+        CS2: OutputPin,
+    > Transfer<u8> for SPI<INSTANCE, SCLK, SDO, SDI, CS, WP, HD, CS1, CS2>
 {
     type Error = Error;
 
@@ -579,7 +882,11 @@ impl<
         SDO: OutputPin,
         SDI: InputPin + OutputPin,
         CS: OutputPin,
-    > Transfer<u16> for SPI<INSTANCE, SCLK, SDO, SDI, CS>
+        WP: InputPin + OutputPin,
+        HD: InputPin + OutputPin,
+        CS1: OutputPin,
+        CS2: OutputPin,
+    > Transfer<u16> for SPI<INSTANCE, SCLK, SDO, SDI, CS, WP, HD, CS1, CS2>
 {
     type Error = Error;
 
@@ -597,7 +904,11 @@ impl<
         SDO: OutputPin,
         SDI: InputPin + OutputPin,
         CS: OutputPin,
-    > Transfer<u32> for SPI<INSTANCE, SCLK, SDO, SDI, CS>
+        WP: InputPin + OutputPin,
+        HD: InputPin + OutputPin,
+        CS1: OutputPin,
+        CS2: OutputPin,
+    > Transfer<u32> for SPI<INSTANCE, SCLK, SDO, SDI, CS, WP, HD, CS1, CS2>
 {
     type Error = Error;
 
@@ -616,7 +927,11 @@ impl<
         SDO: OutputPin,
         SDI: InputPin + OutputPin,
         CS: OutputPin,
-    > Write<u8> for SPI<INSTANCE, SCLK, SDO, SDI, CS>
+        WP: InputPin + OutputPin,
+        HD: InputPin + OutputPin,
+        CS1: OutputPin,
+        CS2: OutputPin,
+    > Write<u8> for SPI<INSTANCE, SCLK, SDO, SDI, CS, WP, HD, CS1, CS2>
 {
     type Error = Error;
 
@@ -631,7 +946,11 @@ impl<
         SDO: OutputPin,
         SDI: InputPin + OutputPin,
         CS: OutputPin,
-    > Write<u16> for SPI<INSTANCE, SCLK, SDO, SDI, CS>
+        WP: InputPin + OutputPin,
+        HD: InputPin + OutputPin,
+        CS1: OutputPin,
+        CS2: OutputPin,
+    > Write<u16> for SPI<INSTANCE, SCLK, SDO, SDI, CS, WP, HD, CS1, CS2>
 {
     type Error = Error;
 
@@ -647,7 +966,11 @@ impl<
         SDO: OutputPin,
         SDI: InputPin + OutputPin,
         CS: OutputPin,
-    > Write<u32> for SPI<INSTANCE, SCLK, SDO, SDI, CS>
+        WP: InputPin + OutputPin,
+        HD: InputPin + OutputPin,
+        CS1: OutputPin,
+        CS2: OutputPin,
+    > Write<u32> for SPI<INSTANCE, SCLK, SDO, SDI, CS, WP, HD, CS1, CS2>
 {
     type Error = Error;
 
@@ -663,7 +986,11 @@ impl<
         SDO: OutputPin,
         SDI: InputPin + OutputPin,
         CS: OutputPin,
-    > WriteIter<u8> for SPI<INSTANCE, SCLK, SDO, SDI, CS>
+        WP: InputPin + OutputPin,
+        HD: InputPin + OutputPin,
+        CS1: OutputPin,
+        CS2: OutputPin,
+    > WriteIter<u8> for SPI<INSTANCE, SCLK, SDO, SDI, CS, WP, HD, CS1, CS2>
 {
     type Error = Error;
 
@@ -681,7 +1008,11 @@ impl<
         SDO: OutputPin,
         SDI: InputPin + OutputPin,
         CS: OutputPin,
-    > WriteIter<u16> for SPI<INSTANCE, SCLK, SDO, SDI, CS>
+        WP: InputPin + OutputPin,
+        HD: InputPin + OutputPin,
+        CS1: OutputPin,
+        CS2: OutputPin,
+    > WriteIter<u16> for SPI<INSTANCE, SCLK, SDO, SDI, CS, WP, HD, CS1, CS2>
 {
     type Error = Error;
 
@@ -699,7 +1030,11 @@ impl<
         SDO: OutputPin,
         SDI: InputPin + OutputPin,
         CS: OutputPin,
-    > WriteIter<u32> for SPI<INSTANCE, SCLK, SDO, SDI, CS>
+        WP: InputPin + OutputPin,
+        HD: InputPin + OutputPin,
+        CS1: OutputPin,
+        CS2: OutputPin,
+    > WriteIter<u32> for SPI<INSTANCE, SCLK, SDO, SDI, CS, WP, HD, CS1, CS2>
 {
     type Error = Error;
 
@@ -729,9 +1064,34 @@ mod private {
         fn reset(&mut self) -> &mut Self;
 
         /// Initialize pins
-        fn init_pins<SCLK: OutputPin, SDO: OutputPin, SDI: InputPin + OutputPin, CS: OutputPin>(
+        fn init_pins<
+            SCLK: OutputPin,
+            SDO: OutputPin,
+            SDI: InputPin + OutputPin,
+            CS: OutputPin,
+            WP: InputPin + OutputPin,
+            HD: InputPin + OutputPin,
+            CS1: OutputPin,
+            CS2: OutputPin,
+        >(
             &mut self,
-            pins: &mut Pins<SCLK, SDO, SDI, CS>,
+            pins: &mut Pins<SCLK, SDO, SDI, CS, WP, HD, CS1, CS2>,
+        ) -> &mut Self;
+    }
+
+    /// General purpose SPI controllers (SPI2 and SPI3): freely assignable pins and DMA support
+    pub trait GeneralInstance: Instance {
+        /// Number of the SPI controller
+        fn index() -> u32;
+
+        /// Also connect the SDO pin as input, for the 3-wire mode
+        fn init_sio_pin<SDO: InputPin + OutputPin>(&mut self, sdo: &mut SDO) -> &mut Self;
+
+        /// Connect or disconnect the SDI pin as output, for the dual and quad modes
+        fn init_dual_pin<SDI: InputPin + OutputPin>(
+            &mut self,
+            sdi: &mut SDI,
+            output: bool,
         ) -> &mut Self;
     }
 
@@ -763,14 +1123,24 @@ mod private {
             self
         }
 
-        fn init_pins<SCLK: OutputPin, SDO: OutputPin, SDI: InputPin + OutputPin, CS: OutputPin>(
+        fn init_pins<
+            SCLK: OutputPin,
+            SDO: OutputPin,
+            SDI: InputPin + OutputPin,
+            CS: OutputPin,
+            WP: InputPin + OutputPin,
+            HD: InputPin + OutputPin,
+            CS1: OutputPin,
+            CS2: OutputPin,
+        >(
             &mut self,
-            pins: &mut Pins<SCLK, SDO, SDI, CS>,
+            pins: &mut Pins<SCLK, SDO, SDI, CS, WP, HD, CS1, CS2>,
         ) -> &mut Self {
             // SCLK, SDO & SDI, pins are initialized and in use by SPI0, cannot change
 
             // use CS2 signal, as CS is shared between SPI0 and SPI1 and CS0 is for flash,
-            // CS1 is for psram?
+            // CS1 is for psram? Therefore the additional CS pins are not used (SPI1::new
+            // rejects them).
 
             if let Some(cs) = &mut pins.cs {
                 cs.set_to_push_pull_output()
@@ -786,9 +1156,34 @@ mod private {
 
     macro_rules! modules {
         ($(
-            $MODULE:ident: ($sclk:ident, $sdo:ident, $sdi:ident, $cs:ident),
+            $MODULE:ident: (
+                $index:literal, $sclk:ident, $sdo:ident, $sdi:ident,
+                $cs:ident, $cs1:ident, $cs2:ident, $wp:ident, $hd:ident
+            ),
         )+) => {
             $(
+                impl GeneralInstance for $MODULE {
+                    fn index() -> u32 {
+                        $index
+                    }
+
+                    fn init_sio_pin<SDO: InputPin + OutputPin>(&mut self, sdo: &mut SDO) -> &mut Self {
+                        sdo.enable_input(true)
+                            .connect_input_to_peripheral(InputSignal::$sdo);
+                        self
+                    }
+
+                    fn init_dual_pin<SDI: InputPin + OutputPin>(&mut self, sdi: &mut SDI, output: bool) -> &mut Self {
+                        if output {
+                            sdi.enable_output(true)
+                                .connect_peripheral_to_output(OutputSignal::$sdi);
+                        } else {
+                            sdi.enable_output(false);
+                        }
+                        self
+                    }
+                }
+
                 impl Instance for $MODULE {
                     fn ptr() -> *const spi::RegisterBlock {
                         $MODULE::ptr()
@@ -810,8 +1205,8 @@ mod private {
 
                     }
 
-                    fn init_pins<SCLK: OutputPin, SDO: OutputPin, SDI: InputPin + OutputPin, CS: OutputPin>(
-                        &mut self, pins: &mut Pins<SCLK,SDO,SDI,CS>
+                    fn init_pins<SCLK: OutputPin, SDO: OutputPin, SDI: InputPin + OutputPin, CS: OutputPin, WP: InputPin + OutputPin, HD: InputPin + OutputPin, CS1: OutputPin, CS2: OutputPin>(
+                        &mut self, pins: &mut Pins<SCLK,SDO,SDI, CS, WP, HD, CS1, CS2>
                     ) -> &mut Self {
                         pins
                             .sclk
@@ -836,6 +1231,36 @@ mod private {
                                 .connect_peripheral_to_output(OutputSignal::$cs);
                         }
 
+                        if let Some(cs1) = &mut pins.cs1 {
+                            cs1
+                                .set_to_push_pull_output()
+                                .connect_peripheral_to_output(OutputSignal::$cs1);
+                        }
+
+                        if let Some(cs2) = &mut pins.cs2 {
+                            cs2
+                                .set_to_push_pull_output()
+                                .connect_peripheral_to_output(OutputSignal::$cs2);
+                        }
+
+                        if let Some(wp) = &mut pins.wp {
+                            wp
+                                .set_to_push_pull_output()
+                                .connect_peripheral_to_output(OutputSignal::$wp);
+                            wp
+                                .enable_input(true)
+                                .connect_input_to_peripheral(InputSignal::$wp);
+                        }
+
+                        if let Some(hd) = &mut pins.hd {
+                            hd
+                                .set_to_push_pull_output()
+                                .connect_peripheral_to_output(OutputSignal::$hd);
+                            hd
+                                .enable_input(true)
+                                .connect_input_to_peripheral(InputSignal::$hd);
+                        }
+
                         // Use CS0
                         self
                             .pin
@@ -850,7 +1275,7 @@ mod private {
     }
 
     modules! {
-        SPI2: (HSPICLK, HSPID, HSPIQ, HSPICS0),
-        SPI3: (VSPICLK, VSPID, VSPIQ, VSPICS0),
+        SPI2: (2, HSPICLK, HSPID, HSPIQ, HSPICS0, HSPICS1, HSPICS2, HSPIWP, HSPIHD),
+        SPI3: (3, VSPICLK, VSPID, VSPIQ, VSPICS0, VSPICS1, VSPICS2, VSPIWP, VSPIHD),
     }
 }
@@ -0,0 +1,144 @@
+//! SPI transactions with command, address and dummy phases
+//!
+//! A hardware transaction consists of the following phases, each of which is optional:
+//! - command: up to 16 bits, always on a single line
+//! - address: up to 32 bits, on 2 or 4 lines in the [DualIo](super::config::IoMode::DualIo)
+//!   and [QuadIo](super::config::IoMode::QuadIo) modes
+//! - dummy: a number of clock cycles without data, e.g. the wait cycles of a fast read
+//! - write (MOSI) data
+//! - read (MISO) data
+//!
+//! The data phases are not limited to the 64 byte buffer of the controller: longer data is
+//! transferred in multiple hardware transactions while keeping CS active, of which only the
+//! first includes the command, address and dummy phases.
+//!
+//! The transactions are run by [SPI::transaction](super::SPI::transaction) and
+//! [HalfDuplex::transaction](super::HalfDuplex::transaction).
+//!
+//! *Note: this module does not depend on the hardware, so it is also compiled and tested on the
+//! host (see the `host-tests` directory).*
+//!
+//! # Example
+//! ```
+//! // fast read of a flash chip
+//! let mut data = [0u8; 256];
+//! spi.transaction(
+//!     Transaction::new()
+//!         .command(0x0b, 8)
+//!         .address(0x1000, 24)
+//!         .dummy_cycles(8)
+//!         .read(&mut data),
+//! )
+//! .unwrap();
+//! ```
+
+/// Maximum length of the command phase in bits
+pub const MAX_COMMAND_BITS: u8 = 16;
+/// Maximum length of the address phase in bits
+pub const MAX_ADDRESS_BITS: u8 = 32;
+
+/// SPI transaction builder
+///
+/// By default a transaction has no phases.
+pub struct Transaction<'a> {
+    pub(super) command: Option<(u16, u8)>,
+    pub(super) address: Option<(u32, u8)>,
+    pub(super) dummy_cycles: u8,
+    pub(super) write: &'a [u8],
+    pub(super) read: &'a mut [u8],
+}
+
+impl<'a> Transaction<'a> {
+    /// Create a new transaction without any phases
+    pub fn new() -> Self {
+        Transaction {
+            command: None,
+            address: None,
+            dummy_cycles: 0,
+            write: &[],
+            read: &mut [],
+        }
+    }
+
+    /// Send the lowest `bits` (1 to 16) bits of `value` as command
+    pub fn command(mut self, value: u16, bits: u8) -> Self {
+        self.command = Some((value, bits));
+        self
+    }
+
+    /// Send the lowest `bits` (1 to 32) bits of `value` as address
+    pub fn address(mut self, value: u32, bits: u8) -> Self {
+        self.address = Some((value, bits));
+        self
+    }
+
+    /// Number of dummy clock cycles between the address and the data phases
+    pub fn dummy_cycles(mut self, cycles: u8) -> Self {
+        self.dummy_cycles = cycles;
+        self
+    }
+
+    /// Data to write
+    pub fn write(mut self, data: &'a [u8]) -> Self {
+        self.write = data;
+        self
+    }
+
+    /// Buffer to read into, the data is read after the data is written
+    pub fn read(mut self, buffer: &'a mut [u8]) -> Self {
+        self.read = buffer;
+        self
+    }
+
+    /// Returns true if the lengths of the command and address phases are within the limits
+    pub fn has_valid_lengths(&self) -> bool {
+        let command_valid = match self.command {
+            Some((_, bits)) => bits > 0 && bits <= MAX_COMMAND_BITS,
+            None => true,
+        };
+        let address_valid = match self.address {
+            Some((_, bits)) => bits > 0 && bits <= MAX_ADDRESS_BITS,
+            None => true,
+        };
+        command_valid && address_valid
+    }
+
+    /// Returns true if the transaction has no phases
+    pub fn is_empty(&self) -> bool {
+        self.command.is_none()
+            && self.address.is_none()
+            && self.dummy_cycles == 0
+            && self.write.is_empty()
+            && self.read.is_empty()
+    }
+}
+
+impl Default for Transaction<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Value of the command register field
+///
+/// The controller sends bits 7 to 0 and then bits 15 to 8 in MSB first mode, so the command is
+/// moved to the top and the bytes are swapped.
+pub fn command_register_value(value: u16, bits: u8, lsb_first: bool) -> u16 {
+    if lsb_first {
+        value
+    } else {
+        ((value as u32) << (32 - bits as u32)).swap_bytes() as u16
+    }
+}
+
+/// Value of the address register
+///
+/// The controller sends from bit 31 downwards in MSB first mode, and starting with the lowest
+/// bit of the highest byte in LSB first mode.
+pub fn address_register_value(value: u32, bits: u8, lsb_first: bool) -> u32 {
+    if lsb_first {
+        value.swap_bytes()
+    } else {
+        value << (32 - bits as u32)
+    }
+}